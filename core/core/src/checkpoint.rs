@@ -0,0 +1,293 @@
+//! On-disk checkpoints of per-session tracker state.
+//!
+//! `SessionTracker` state only lives in memory, so a proxy crash or an
+//! agent restart used to reset the graph even when the editor resumed the
+//! ACP session with `session/load`. `ContextTracker` now writes each
+//! session's state to `~/.eisen/checkpoints/<agent_id>/<session_id>.json`
+//! every `CHECKPOINT_INTERVAL` (and on shutdown), and rehydrates it the
+//! first time it sees that session ID again.
+//!
+//! Checkpoints older than the store's max age (`DEFAULT_MAX_AGE`) are
+//! ignored and deleted.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::session_store::default_eisen_dir;
+use crate::tracker::ContextTracker;
+use crate::types::{Cost, FileNode, SessionKey, SessionMode, Spend, TimelineEvent};
+
+/// Checkpoint directory, relative to the eisen dir.
+pub const CHECKPOINT_DIR_NAME: &str = "checkpoints";
+
+/// Checkpoints older than this are not restored.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often `run` writes dirty sessions to disk.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Persisted state of one `SessionTracker`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionCheckpoint {
+    pub agent_id: String,
+    pub session_id: String,
+    pub session_mode: SessionMode,
+    pub files: Vec<FileNode>,
+    pub seq: u64,
+    pub current_turn: u32,
+    pub last_used_tokens: u32,
+    pub context_size: u32,
+    #[serde(default)]
+    pub hot_zone: Vec<String>,
+    #[serde(default)]
+    pub timeline: Vec<TimelineEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spend: Option<Spend>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_spend: Option<Cost>,
+    pub saved_at_ms: u64,
+}
+
+impl SessionCheckpoint {
+    pub fn key(&self) -> SessionKey {
+        SessionKey::new(&self.agent_id, &self.session_id)
+    }
+}
+
+/// Directory of session checkpoints, one JSON file per `SessionKey`.
+#[derive(Debug, Clone)]
+pub struct CheckpointStore {
+    dir: PathBuf,
+    max_age: Duration,
+}
+
+impl CheckpointStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Store under `~/.eisen/checkpoints` (or `$EISEN_DIR/checkpoints`).
+    pub fn open_default() -> Self {
+        Self::new(default_eisen_dir().join(CHECKPOINT_DIR_NAME))
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path_for(&self, key: &SessionKey) -> PathBuf {
+        self.dir
+            .join(file_component(&key.agent_id))
+            .join(format!("{}.json", file_component(&key.session_id)))
+    }
+
+    fn is_expired(&self, saved_at_ms: u64, now_ms: u64) -> bool {
+        now_ms.saturating_sub(saved_at_ms) > self.max_age.as_millis() as u64
+    }
+
+    pub fn save(&self, checkpoint: &SessionCheckpoint) -> Result<()> {
+        let path = self.path_for(&checkpoint.key());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create checkpoint dir {}", parent.display()))?;
+        }
+        let serialized =
+            serde_json::to_string(checkpoint).context("failed to serialize checkpoint")?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serialized)
+            .with_context(|| format!("failed to write checkpoint {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path).with_context(|| {
+            format!(
+                "failed to move checkpoint {} -> {}",
+                tmp_path.display(),
+                path.display()
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Load the checkpoint for `key`. Returns `None` if there is none or it
+    /// has expired; expired checkpoints are deleted.
+    pub fn load(&self, key: &SessionKey, now_ms: u64) -> Result<Option<SessionCheckpoint>> {
+        let path = self.path_for(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read checkpoint {}", path.display()))?;
+        let checkpoint: SessionCheckpoint = serde_json::from_str(&raw)
+            .with_context(|| format!("failed to parse checkpoint {}", path.display()))?;
+        // Sanitized file names can collide; the stored key is authoritative.
+        if &checkpoint.key() != key {
+            return Ok(None);
+        }
+        if self.is_expired(checkpoint.saved_at_ms, now_ms) {
+            debug!(path = %path.display(), "discarding expired checkpoint");
+            let _ = fs::remove_file(&path);
+            return Ok(None);
+        }
+        Ok(Some(checkpoint))
+    }
+
+    pub fn remove(&self, key: &SessionKey) -> Result<()> {
+        let path = self.path_for(key);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("failed to remove checkpoint {}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete every checkpoint last written more than `max_age` ago.
+    /// Returns the number of files removed.
+    pub fn prune(&self, now: SystemTime) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let mut removed = 0;
+        let agents = fs::read_dir(&self.dir)
+            .with_context(|| format!("failed to list checkpoints in {}", self.dir.display()))?;
+        for agent_dir in agents.flatten() {
+            let Ok(entries) = fs::read_dir(agent_dir.path()) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let modified = entry.metadata().and_then(|m| m.modified());
+                let expired = modified
+                    .ok()
+                    .and_then(|m| now.duration_since(m).ok())
+                    .is_some_and(|age| age > self.max_age);
+                if expired && fs::remove_file(entry.path()).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// File-name-safe form of an agent or session ID.
+fn file_component(id: &str) -> String {
+    if id.is_empty() {
+        return "_".to_string();
+    }
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Periodically write sessions that changed since their last checkpoint.
+pub async fn run(tracker: Arc<Mutex<ContextTracker>>) {
+    let mut interval = tokio::time::interval(CHECKPOINT_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = tracker.lock().await.save_checkpoints() {
+            warn!(
+                error = format!("{e:#}"),
+                "failed to checkpoint tracker state"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, LineRange, Source};
+
+    fn checkpoint(session_id: &str, saved_at_ms: u64) -> SessionCheckpoint {
+        SessionCheckpoint {
+            agent_id: "opencode-a1".to_string(),
+            session_id: session_id.to_string(),
+            session_mode: SessionMode::SingleAgent,
+            files: vec![FileNode {
+                path: "src/main.rs".to_string(),
+                heat: 1.0,
+                in_context: true,
+                last_action: Action::Write,
+                turn_accessed: 2,
+                timestamp_ms: saved_at_ms,
+                estimated_tokens: 40,
+                line_ranges: vec![LineRange::new(3, 9)],
+                source: Source::Diff,
+                confidence: 1.0,
+            }],
+            seq: 7,
+            current_turn: 2,
+            last_used_tokens: 1200,
+            context_size: 200_000,
+            hot_zone: vec![],
+            timeline: vec![],
+            spend: None,
+            model: None,
+            estimated_spend: None,
+            saved_at_ms,
+        }
+    }
+
+    #[test]
+    fn checkpoints_roundtrip_by_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore::new(dir.path().to_path_buf());
+        let saved = checkpoint("sess/../1", 1_000);
+        store.save(&saved).unwrap();
+
+        let key = saved.key();
+        let loaded = store.load(&key, 2_000).unwrap().unwrap();
+        assert_eq!(loaded.seq, 7);
+        assert_eq!(loaded.files[0].path, "src/main.rs");
+        assert_eq!(loaded.files[0].line_ranges, vec![LineRange::new(3, 9)]);
+        // Sanitized IDs stay inside the store
+        assert!(dir.path().join("opencode-a1/sess____1.json").exists());
+
+        // A different session that sanitizes to the same file is not a match
+        let other = SessionKey::new("opencode-a1", "sess_.._1");
+        assert!(store.load(&other, 2_000).unwrap().is_none());
+
+        store.remove(&key).unwrap();
+        assert!(store.load(&key, 2_000).unwrap().is_none());
+        store.remove(&key).unwrap();
+    }
+
+    #[test]
+    fn expired_checkpoints_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let store =
+            CheckpointStore::new(dir.path().to_path_buf()).with_max_age(Duration::from_secs(60));
+        let saved = checkpoint("s1", 1_000);
+        store.save(&saved).unwrap();
+
+        assert!(store.load(&saved.key(), 61_000).unwrap().is_some());
+        assert!(store.load(&saved.key(), 61_001).unwrap().is_none());
+        assert!(!store.path_for(&saved.key()).exists());
+
+        store.save(&saved).unwrap();
+        assert_eq!(store.prune(SystemTime::now()).unwrap(), 0);
+        let later = SystemTime::now() + Duration::from_secs(120);
+        assert_eq!(store.prune(later).unwrap(), 1);
+    }
+}
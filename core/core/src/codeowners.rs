@@ -0,0 +1,414 @@
+//! Zone suggestions derived from a CODEOWNERS file.
+//!
+//! `eisen-core zones suggest` walks the workspace `SymbolTree`, assigns
+//! every file to its owners using CODEOWNERS rules (last matching rule
+//! wins), and proposes one zone profile per owner. The output is a zone
+//! file (see `zone_file.rs`) that `observe` can load as-is.
+//!
+//! Each profile lists the owner's rules in CODEOWNERS order, with `!`
+//! exclusions for later rules that hand part of that area to someone else.
+//! Zone patterns are last-match-wins too, so the profile allows exactly the
+//! files CODEOWNERS gives the owner.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tracing::warn;
+
+use crate::parser::tree::SymbolTree;
+use crate::types::{CompiledZone, ZoneConfig};
+
+/// Where GitHub looks for CODEOWNERS, in order.
+const CODEOWNERS_LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// One `pattern owner...` line.
+#[derive(Debug, Clone)]
+pub struct OwnerRule {
+    pub pattern: String,
+    /// Empty for rules that explicitly leave files unowned
+    pub owners: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CodeOwners {
+    pub rules: Vec<OwnerRule>,
+}
+
+impl CodeOwners {
+    /// First CODEOWNERS file found in the workspace.
+    pub fn find(root: &Path) -> Option<PathBuf> {
+        CODEOWNERS_LOCATIONS
+            .iter()
+            .map(|location| root.join(location))
+            .find(|path| path.is_file())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Self::parse(&raw))
+    }
+
+    /// Parse CODEOWNERS text. Comments, blank lines and GitLab-style
+    /// `[Section]` headers are skipped.
+    pub fn parse(raw: &str) -> Self {
+        let rules = raw
+            .lines()
+            .map(|line| line.split(" #").next().unwrap_or(line).trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter(|line| !line.starts_with('[') && !line.starts_with("^["))
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let pattern = parts.next()?.to_string();
+                Some(OwnerRule {
+                    pattern,
+                    owners: parts.map(str::to_string).collect(),
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+}
+
+/// A proposed zone profile for one owner.
+#[derive(Debug, Clone)]
+pub struct ZoneSuggestion {
+    /// Owner as written in CODEOWNERS (e.g. `@org/team-ui`)
+    pub owner: String,
+    /// Profile name derived from the owner
+    pub profile: String,
+    pub config: ZoneConfig,
+    /// Files currently owned
+    pub files: usize,
+    /// Sum of `NodeData.tokens` over those files
+    pub tokens: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Suggestions {
+    pub zones: Vec<ZoneSuggestion>,
+    pub unowned_files: usize,
+    pub unowned_tokens: u64,
+}
+
+/// Translate a CODEOWNERS pattern into an explicitly anchored zone pattern.
+///
+/// CODEOWNERS follows gitignore rules: a pattern without a `/` (other than
+/// a trailing one) matches at any depth, everything else is anchored.
+/// Directory patterns become `dir/**` so they match the files themselves,
+/// which lets a later `!` exclusion override a broader rule like `*`.
+fn zone_pattern(pattern: &str) -> String {
+    let pattern = match pattern.strip_suffix('/') {
+        Some(dir) => format!("{dir}/**"),
+        None => pattern.to_string(),
+    };
+    if let Some(anchored) = pattern.strip_prefix('/') {
+        return anchored.to_string();
+    }
+    if pattern.trim_end_matches("/**").contains('/') {
+        pattern
+    } else {
+        format!("**/{pattern}")
+    }
+}
+
+/// `!` exclusions for a pattern. A pattern without a trailing `**` may name
+/// a directory, so its contents are excluded as well.
+fn exclusions(pattern: &str) -> Vec<String> {
+    if pattern.ends_with("**") {
+        vec![format!("!{pattern}")]
+    } else {
+        vec![format!("!{pattern}"), format!("!{pattern}/**")]
+    }
+}
+
+/// Profile name for an owner: `@org/team-ui` -> `team-ui`,
+/// `@alice` -> `alice`, `bob@example.com` -> `bob`.
+fn profile_name(owner: &str) -> String {
+    let name = match owner.strip_prefix('@') {
+        Some(handle) => handle.rsplit('/').next().unwrap_or(handle),
+        None => owner.split('@').next().unwrap_or(owner),
+    };
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "owner".to_string()
+    } else {
+        name
+    }
+}
+
+/// Workspace-relative paths and token counts of every file in the tree.
+fn tree_files(tree: &SymbolTree, root: &Path) -> Vec<(String, u64)> {
+    tree.arena()
+        .iter()
+        .filter(|node| !node.is_removed())
+        .map(|node| node.get())
+        .filter(|data| data.kind.is_file())
+        .filter_map(|data| {
+            let relative = Path::new(&data.path).strip_prefix(root).ok()?;
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            Some((relative, data.tokens.unwrap_or(0) as u64))
+        })
+        .collect()
+}
+
+/// Propose one zone profile per CODEOWNERS owner.
+pub fn suggest(codeowners: &CodeOwners, tree: &SymbolTree, root: &Path) -> Suggestions {
+    // Compile each rule the same way observe will compile the profile
+    let rules: Vec<(&OwnerRule, String, CompiledZone)> = codeowners
+        .rules
+        .iter()
+        .filter_map(|rule| {
+            let pattern = zone_pattern(&rule.pattern);
+            match ZoneConfig::new(vec![pattern.clone()]).compile() {
+                Ok(zone) => Some((rule, pattern, zone)),
+                Err(e) => {
+                    warn!(pattern = rule.pattern.as_str(), error = %e, "skipping CODEOWNERS rule");
+                    None
+                }
+            }
+        })
+        .collect();
+
+    // owner -> (files, tokens) and owner -> rule indexes to exclude
+    let mut totals: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+    let mut excluded: BTreeMap<&str, BTreeSet<usize>> = BTreeMap::new();
+    let mut suggestions = Suggestions::default();
+
+    for (path, tokens) in tree_files(tree, root) {
+        let matching: Vec<usize> = (0..rules.len())
+            .filter(|&i| rules[i].2.is_allowed(&path))
+            .collect();
+        let Some(&winner) = matching.last() else {
+            suggestions.unowned_files += 1;
+            suggestions.unowned_tokens += tokens;
+            continue;
+        };
+        let winners = &rules[winner].0.owners;
+        if winners.is_empty() {
+            suggestions.unowned_files += 1;
+            suggestions.unowned_tokens += tokens;
+        }
+        for owner in winners {
+            let entry = totals.entry(owner.as_str()).or_default();
+            entry.0 += 1;
+            entry.1 += tokens;
+        }
+        // Earlier rules whose owners lost this file need an exclusion
+        for &i in &matching[..matching.len() - 1] {
+            for owner in &rules[i].0.owners {
+                if !winners.contains(owner) {
+                    excluded.entry(owner.as_str()).or_default().insert(winner);
+                }
+            }
+        }
+    }
+
+    let mut owners: Vec<&str> = Vec::new();
+    for (rule, _, _) in &rules {
+        for owner in &rule.owners {
+            if !owners.contains(&owner.as_str()) {
+                owners.push(owner);
+            }
+        }
+    }
+
+    let mut used_names: HashSet<String> = HashSet::new();
+    for owner in owners {
+        let mut allowed = Vec::new();
+        let lost = excluded.get(owner);
+        for (i, (rule, pattern, _)) in rules.iter().enumerate() {
+            if rule.owners.iter().any(|o| o == owner) {
+                allowed.push(pattern.clone());
+            } else if lost.is_some_and(|e| e.contains(&i)) {
+                allowed.extend(exclusions(pattern));
+            }
+        }
+
+        let base = profile_name(owner);
+        let mut profile = base.clone();
+        let mut n = 2;
+        while !used_names.insert(profile.clone()) {
+            profile = format!("{base}-{n}");
+            n += 1;
+        }
+
+        let (files, tokens) = totals.get(owner).copied().unwrap_or_default();
+        suggestions.zones.push(ZoneSuggestion {
+            owner: owner.to_string(),
+            profile,
+            config: ZoneConfig::new(allowed),
+            files,
+            tokens,
+        });
+    }
+    suggestions
+}
+
+/// Quote a string as a TOML basic string.
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+impl Suggestions {
+    /// Render as a zone file. `source` names the CODEOWNERS file in the
+    /// header comment.
+    pub fn to_toml(&self, source: &str) -> String {
+        let mut out = String::new();
+        out.push_str(&format!(
+            "# Zone profiles suggested from {source} by `eisen-core zones suggest`.\n"
+        ));
+        out.push_str("# Review the profiles, then assign agents to them under [agents].\n");
+        out.push_str(&format!(
+            "# Unowned: {} files, {} tokens\n",
+            self.unowned_files, self.unowned_tokens
+        ));
+        for zone in &self.zones {
+            out.push_str(&format!("\n[profiles.{}]\n", toml_string(&zone.profile)));
+            out.push_str(&format!(
+                "# {}: {} files, {} tokens\n",
+                zone.owner, zone.files, zone.tokens
+            ));
+            let allowed: Vec<String> = zone.config.allowed.iter().map(|p| toml_string(p)).collect();
+            out.push_str(&format!("allowed = [{}]\n", allowed.join(", ")));
+        }
+        out.push_str("\n[agents]\n");
+        if let Some(zone) = self.zones.first() {
+            out.push_str(&format!("# opencode = {}\n", toml_string(&zone.profile)));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{NodeData, NodeKind};
+    use crate::zone_file::ZoneFile;
+
+    const CODEOWNERS: &str = "\
+# Default owners
+*                 @org/platform
+/src/ui/          @org/team-ui @alice
+/src/ui/core/     @org/team-core
+docs/             alice@example.com  # docs anywhere
+/vendor/
+[Section]
+";
+
+    fn tree(root: &str, files: &[(&str, u32)]) -> SymbolTree {
+        let mut tree = SymbolTree::new();
+        let root_id = tree.add_node(
+            None,
+            NodeData::new(0, "ws".to_string(), NodeKind::Folder, root.to_string()),
+        );
+        for (path, tokens) in files {
+            let data = NodeData::new(
+                0,
+                path.rsplit('/').next().unwrap().to_string(),
+                NodeKind::File("ts".to_string()),
+                format!("{root}/{path}"),
+            )
+            .with_tokens(*tokens);
+            tree.add_node(Some(root_id), data);
+        }
+        tree
+    }
+
+    #[test]
+    fn parse_skips_comments_and_sections() {
+        let owners = CodeOwners::parse(CODEOWNERS);
+        assert_eq!(owners.rules.len(), 5);
+        assert_eq!(owners.rules[1].owners, vec!["@org/team-ui", "@alice"]);
+        assert_eq!(owners.rules[3].owners, vec!["alice@example.com"]);
+        assert!(owners.rules[4].owners.is_empty());
+    }
+
+    #[test]
+    fn patterns_and_names() {
+        assert_eq!(zone_pattern("/src/ui/"), "src/ui/**");
+        assert_eq!(zone_pattern("src/ui/"), "src/ui/**");
+        assert_eq!(zone_pattern("/src/ui"), "src/ui");
+        assert_eq!(zone_pattern("docs/"), "**/docs/**");
+        assert_eq!(zone_pattern("*.md"), "**/*.md");
+        assert_eq!(exclusions("src/ui"), vec!["!src/ui", "!src/ui/**"]);
+        assert_eq!(profile_name("@org/team-ui"), "team-ui");
+        assert_eq!(profile_name("@alice"), "alice");
+        assert_eq!(profile_name("alice@example.com"), "alice");
+    }
+
+    #[test]
+    fn suggests_profiles_with_token_totals() {
+        let owners = CodeOwners::parse(CODEOWNERS);
+        let tree = tree(
+            "/ws",
+            &[
+                ("src/ui/app.tsx", 100),
+                ("src/ui/core/state.ts", 40),
+                ("src/server.ts", 7),
+                ("pkg/docs/a.md", 5),
+                ("vendor/lib.js", 1000),
+            ],
+        );
+        let suggestions = suggest(&owners, &tree, Path::new("/ws"));
+        assert_eq!(suggestions.unowned_files, 1);
+        assert_eq!(suggestions.unowned_tokens, 1000);
+
+        let by_profile: BTreeMap<&str, &ZoneSuggestion> = suggestions
+            .zones
+            .iter()
+            .map(|z| (z.profile.as_str(), z))
+            .collect();
+        let names: Vec<&str> = by_profile.keys().copied().collect();
+        assert_eq!(
+            names,
+            vec!["alice", "alice-2", "platform", "team-core", "team-ui"]
+        );
+
+        let ui = by_profile["team-ui"];
+        assert_eq!((ui.files, ui.tokens), (1, 100));
+        assert_eq!(ui.config.allowed, vec!["src/ui/**", "!src/ui/core/**"]);
+
+        let platform = by_profile["platform"];
+        assert_eq!((platform.files, platform.tokens), (1, 7));
+        let zone = platform.config.compile().unwrap();
+        assert!(zone.is_allowed("src/server.ts"));
+        assert!(!zone.is_allowed("src/ui/app.tsx"));
+        assert!(!zone.is_allowed("vendor/lib.js"));
+        assert!(!zone.is_allowed("pkg/docs/a.md"));
+
+        let core = by_profile["team-core"];
+        assert_eq!((core.files, core.tokens), (1, 40));
+        assert!(core
+            .config
+            .compile()
+            .unwrap()
+            .is_allowed("src/ui/core/state.ts"));
+    }
+
+    #[test]
+    fn output_is_a_loadable_zone_file() {
+        let owners = CodeOwners::parse(CODEOWNERS);
+        let tree = tree(
+            "/ws",
+            &[("src/ui/app.tsx", 100), ("src/ui/core/state.ts", 40)],
+        );
+        let toml = suggest(&owners, &tree, Path::new("/ws")).to_toml(".github/CODEOWNERS");
+        assert!(toml.contains("# @org/team-ui: 1 files, 100 tokens"));
+
+        let file = ZoneFile::parse(&toml).unwrap();
+        let zone = file.compile_profile("team-ui").unwrap();
+        assert!(zone.is_allowed("src/ui/app.tsx"));
+        assert!(!zone.is_allowed("src/ui/core/state.ts"));
+    }
+}
@@ -10,8 +10,11 @@
 //! | Method                | Direction       | Extraction                          |
 //! |----------------------|-----------------|-------------------------------------|
 //! | `session/prompt`     | Editor > Agent  | Embedded resources & resource links  |
-//! | `session/update`     | Agent > Editor  | Tool call locations & diff paths     |
-//! | `fs/read_text_file`  | Agent > Editor  | File path (Read action)              |
+//! | `session/load`       | Editor > Agent  | Session ID; starts history replay    |
+//! | `session/resume`     | Editor > Agent  | Session ID                           |
+//! | `session/set_model`  | Editor > Agent  | Session model (for cost estimates)   |
+//! | `session/update`     | Agent > Editor  | Tool call locations, diff paths, usage & cost |
+//! | `fs/read_text_file`  | Agent > Editor  | File path and line range (Read)      |
 //! | `fs/write_text_file` | Agent > Editor  | File path (Write action)             |
 //! | `terminal/output`    | Agent > Editor  | File paths in terminal output        |
 //!
@@ -20,16 +23,47 @@
 //! JSON-RPC responses to `session/prompt` carry a `stopReason` field.
 //! We detect these and call `tracker.end_turn()` to advance the turn counter,
 //! which causes files to age out of context after `context_turns` turns.
+//!
+//! ## Load / Resume
+//!
+//! After `session/load` the agent replays the conversation as ordinary
+//! `session/update` notifications before answering the request. Until that
+//! response arrives, the tracker records replayed tool calls without heating
+//! files and rebuilds turn numbers from the replayed user messages. The
+//! response (for load and resume alike) marks the session as resumed.
+//!
+//! ## Usage and Cost
+//!
+//! ACP's `usage_update` session update carries the context window and,
+//! optionally, the cumulative session cost. Agents that predate it attach
+//! the same figures to a notification's `_meta` instead; both are fed to
+//! `usage_update_for_session`, which keeps a running spend per session.
+//! Agents that never report cost may still return token counts in the
+//! `PromptResponse`; those are priced with the session's model (from the
+//! `session/new` response or `session/set_model`) and the local price table.
 
 use agent_client_protocol_schema::{
-    ContentBlock, EmbeddedResourceResource, PromptRequest, ReadTextFileRequest,
-    SessionNotification, SessionUpdate, ToolCall, ToolCallContent, ToolCallUpdate, ToolKind,
-    WriteTextFileRequest, AGENT_METHOD_NAMES, CLIENT_METHOD_NAMES,
+    ContentBlock, Diff, EmbeddedResourceResource, InitializeResponse, PromptRequest,
+    ReadTextFileRequest, SessionNotification, SessionUpdate, ToolCall, ToolCallContent,
+    ToolCallLocation, ToolCallUpdate, ToolKind, Usage, WriteTextFileRequest, AGENT_METHOD_NAMES,
+    CLIENT_METHOD_NAMES,
 };
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
 use tracing::{debug, warn};
 
+use crate::paths::{PathResolver, ResolvedPath};
 use crate::tracker::ContextTracker;
-use crate::types::Action;
+use crate::types::{Action, Cost, LineRange, Source, TokenUsage};
+
+/// `session/resume` is behind the schema's `unstable_session_resume`
+/// feature, so it isn't in `AGENT_METHOD_NAMES` here.
+const SESSION_RESUME_METHOD: &str = "session/resume";
+
+/// Likewise behind `unstable_session_model`.
+const SESSION_SET_MODEL_METHOD: &str = "session/set_model";
 
 // ---------------------------------------------------------------------------
 // Public entry points — called by proxy.rs for each forwarded line
@@ -37,8 +71,8 @@ use crate::types::Action;
 
 /// Extract context from an editor > agent message line.
 ///
-/// Handles `session/prompt` (embedded resources and resource links)
-/// and terminal output responses.
+/// Handles `session/prompt` (embedded resources and resource links),
+/// `session/load` / `session/resume`, and terminal output responses.
 pub fn extract_upstream(line: &str, tracker: &mut ContextTracker) {
     let v: serde_json::Value = match serde_json::from_str(line) {
         Ok(v) => v,
@@ -68,7 +102,18 @@ pub fn extract_upstream(line: &str, tracker: &mut ContextTracker) {
 
     debug!(method, "upstream ACP message");
 
-    if method == AGENT_METHOD_NAMES.session_prompt {
+    if method == AGENT_METHOD_NAMES.session_load || method == SESSION_RESUME_METHOD {
+        if let Some(sid) = v
+            .get("params")
+            .and_then(|p| p.get("sessionId"))
+            .and_then(|s| s.as_str())
+        {
+            let replay = method == AGENT_METHOD_NAMES.session_load;
+            let id = v.get("id").and_then(|i| i.as_u64());
+            tracker.begin_session_load(id, sid, replay);
+            tracing::info!(session_id = sid, method, "ACP session resumed");
+        }
+    } else if method == AGENT_METHOD_NAMES.session_prompt {
         if let Some(params) = v.get("params") {
             let session_id = session_id_from_params(params, tracker);
             if let Some(id) = v.get("id").and_then(|i| i.as_u64()) {
@@ -85,6 +130,14 @@ pub fn extract_upstream(line: &str, tracker: &mut ContextTracker) {
                 Err(e) => warn!(method, error = %e, "failed to deserialize PromptRequest"),
             }
         }
+    } else if method == SESSION_SET_MODEL_METHOD {
+        if let Some(params) = v.get("params") {
+            let session_id = session_id_from_params(params, tracker);
+            if let Some(model) = params.get("modelId").and_then(|m| m.as_str()) {
+                debug!(model, "session/set_model");
+                tracker.set_session_model(&session_id, model);
+            }
+        }
     }
 }
 
@@ -96,7 +149,17 @@ pub fn extract_upstream(line: &str, tracker: &mut ContextTracker) {
 /// - `fs/write_text_file`
 /// - `terminal/output`
 /// - Session ID auto-detection and end-turn detection from responses.
+///
+/// Any session ID seen here is handed to `ContextTracker::track_session`,
+/// so a session resumed after a restart picks up its checkpointed state.
 pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
+    extract_downstream_with_files(line, tracker, &DiffFiles::default());
+}
+
+/// Like `extract_downstream`, locating diff hunks in `files` (see
+/// `diff_files`). Diffs whose file isn't in `files` get hunks numbered
+/// from the start of the snippet.
+pub fn extract_downstream_with_files(line: &str, tracker: &mut ContextTracker, files: &DiffFiles) {
     let v: serde_json::Value = match serde_json::from_str(line) {
         Ok(v) => v,
         Err(_) => return,
@@ -105,6 +168,14 @@ pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
     // --- JSON-RPC responses (no "method", have "result") ---
     // Detect session/new response (sessionId) and PromptResponse (stopReason).
     if v.get("method").is_none() {
+        // Response to session/load or session/resume: the replay (if any)
+        // is over. Errors end it too, but don't count as a resume.
+        if let Some(id) = v.get("id").and_then(|i| i.as_u64()) {
+            if tracker.finish_session_load(id, v.get("error").is_none()) {
+                debug!(id, "session load/resume response");
+                return;
+            }
+        }
         if let Some(result) = v.get("result") {
             // Auto-detect sessionId from session/new response:
             // {"jsonrpc":"2.0","id":1,"result":{"sessionId":"sess_abc123"}}
@@ -114,6 +185,28 @@ pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
                     tracker.set_session_id(sid.to_string());
                     tracing::info!(session_id = sid, "auto-detected ACP session ID");
                 }
+                // session/new also reports the model, if the agent lets
+                // the editor choose one
+                if let Some(model) = result
+                    .get("models")
+                    .and_then(|m| m.get("currentModelId"))
+                    .and_then(|m| m.as_str())
+                {
+                    tracker.set_session_model(sid, model);
+                }
+            }
+            // Detect InitializeResponse by the presence of result.agentCapabilities.
+            // Hot-zone re-injection is only enabled if the agent accepts
+            // embedded resources in prompts.
+            if result.get("agentCapabilities").is_some() {
+                match serde_json::from_value::<InitializeResponse>(result.clone()) {
+                    Ok(resp) => {
+                        let embedded = resp.agent_capabilities.prompt_capabilities.embedded_context;
+                        debug!(embedded_context = embedded, "initialize response");
+                        tracker.set_embedded_context(embedded);
+                    }
+                    Err(e) => warn!(error = %e, "failed to deserialize InitializeResponse"),
+                }
             }
             // Detect PromptResponse by the presence of result.stopReason.
             // This signals end-of-turn so the tracker can advance the turn counter.
@@ -121,6 +214,14 @@ pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
                 debug!(stop_reason, "end-of-turn detected from PromptResponse");
                 let mut handled = false;
                 if let Some(id) = v.get("id").and_then(|i| i.as_u64()) {
+                    if let Some(usage) = result.get("usage") {
+                        match serde_json::from_value::<Usage>(usage.clone()) {
+                            Ok(usage) => {
+                                tracker.token_usage_for_prompt_response(id, token_usage(&usage));
+                            }
+                            Err(e) => warn!(error = %e, "failed to deserialize prompt usage"),
+                        }
+                    }
                     handled = tracker.end_turn_for_prompt_response(id);
                 }
                 if !handled {
@@ -143,14 +244,20 @@ pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
     if method == CLIENT_METHOD_NAMES.session_update {
         if let Some(params) = v.get("params") {
             let session_id = session_id_from_params(params, tracker);
+            tracker.track_session(&session_id);
             match serde_json::from_value::<SessionNotification>(params.clone()) {
                 Ok(notif) => {
+                    let from_user = matches!(notif.update, SessionUpdate::UserMessageChunk(_));
+                    tracker.replay_update(&session_id, from_user);
                     debug!(
                         update_type =
                             format!("{:?}", std::mem::discriminant(&notif.update)).as_str(),
                         "extracting from session/update"
                     );
-                    extract_from_session_update(&notif.update, &session_id, tracker);
+                    extract_from_session_update(&notif.update, &session_id, tracker, files);
+                    if !matches!(notif.update, SessionUpdate::UsageUpdate(_)) {
+                        extract_usage_from_meta(params, &session_id, tracker);
+                    }
                 }
                 Err(e) => warn!(method, error = %e, "failed to deserialize SessionNotification"),
             }
@@ -162,7 +269,13 @@ pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
                 Ok(req) => {
                     let path = req.path.to_string_lossy().to_string();
                     debug!(path = path.as_str(), action = "read", "fs/read_text_file");
-                    tracker.file_access_for_session(&session_id, &path, Action::Read);
+                    tracker.file_access_lines_for_session(
+                        &session_id,
+                        &path,
+                        Action::Read,
+                        Source::Fs,
+                        read_range(req.line, req.limit),
+                    );
                 }
                 Err(e) => warn!(method, error = %e, "failed to deserialize ReadTextFileRequest"),
             }
@@ -187,7 +300,7 @@ pub fn extract_downstream(line: &str, tracker: &mut ContextTracker) {
                 Ok(req) => {
                     let path = req.path.to_string_lossy().to_string();
                     debug!(path = path.as_str(), action = "write", "fs/write_text_file");
-                    tracker.file_access_for_session(&session_id, &path, Action::Write);
+                    tracker.file_access_for_session(&session_id, &path, Action::Write, Source::Fs);
                 }
                 Err(e) => warn!(method, error = %e, "failed to deserialize WriteTextFileRequest"),
             }
@@ -218,7 +331,12 @@ fn extract_from_prompt(req: &PromptRequest, session_id: &str, tracker: &mut Cont
                         action = "user_provided",
                         "prompt: embedded resource"
                     );
-                    tracker.file_access_for_session(session_id, &path, Action::UserProvided);
+                    tracker.file_access_for_session(
+                        session_id,
+                        &path,
+                        Action::UserProvided,
+                        Source::Prompt,
+                    );
                 }
             }
             ContentBlock::ResourceLink(link) => {
@@ -228,7 +346,12 @@ fn extract_from_prompt(req: &PromptRequest, session_id: &str, tracker: &mut Cont
                         action = "user_referenced",
                         "prompt: resource link"
                     );
-                    tracker.file_access_for_session(session_id, &path, Action::UserReferenced);
+                    tracker.file_access_for_session(
+                        session_id,
+                        &path,
+                        Action::UserReferenced,
+                        Source::Prompt,
+                    );
                 }
             }
             _ => {} // Text, Image, Audio — no file paths
@@ -240,24 +363,107 @@ fn extract_from_prompt(req: &PromptRequest, session_id: &str, tracker: &mut Cont
 ///
 /// - `SessionUpdate::ToolCall` > new tool call with locations
 /// - `SessionUpdate::ToolCallUpdate` > update with optional locations
+/// - `SessionUpdate::UsageUpdate` > context window and cumulative cost
 fn extract_from_session_update(
     update: &SessionUpdate,
     session_id: &str,
     tracker: &mut ContextTracker,
+    files: &DiffFiles,
 ) {
     match update {
         SessionUpdate::ToolCall(tc) => {
-            extract_from_tool_call(tc, session_id, tracker);
+            extract_from_tool_call(tc, session_id, tracker, files);
         }
         SessionUpdate::ToolCallUpdate(tcu) => {
-            extract_from_tool_call_update(tcu, session_id, tracker);
+            extract_from_tool_call_update(tcu, session_id, tracker, files);
+        }
+        SessionUpdate::UsageUpdate(usage) => {
+            debug!(used = usage.used, size = usage.size, "usage update");
+            let cost = usage.cost.as_ref().map(|c| Cost {
+                amount: c.amount,
+                currency: c.currency.clone(),
+                estimated: false,
+            });
+            tracker.usage_update_for_session(
+                session_id,
+                saturating_u32(usage.used),
+                saturating_u32(usage.size),
+                cost,
+            );
         }
         _ => {} // AgentMessageChunk, Plan, etc. — no file context
     }
 }
 
+/// Usage some agents attach to a `session/update`'s `_meta` (on `params`
+/// or on the update itself) instead of sending `usage_update`:
+/// `usage: {used, size}` for the context window, and the cumulative cost
+/// as `cost: {amount, currency}` (top level or inside `usage`) or as a
+/// bare USD number under `cost`, `costUsd` or `total_cost_usd`.
+fn extract_usage_from_meta(params: &Value, session_id: &str, tracker: &mut ContextTracker) {
+    let metas = [
+        params.get("_meta"),
+        params.get("update").and_then(|u| u.get("_meta")),
+    ];
+    let Some((tokens, cost)) = metas.into_iter().flatten().find_map(|meta| {
+        let usage = meta.get("usage");
+        let tokens = usage.and_then(|u| Some((u.get("used")?.as_u64()?, u.get("size")?.as_u64()?)));
+        let cost = meta_cost(meta).or_else(|| usage.and_then(meta_cost));
+        (tokens.is_some() || cost.is_some()).then_some((tokens, cost))
+    }) else {
+        return;
+    };
+    debug!(?tokens, ?cost, "usage from _meta");
+    match (tokens, cost) {
+        (Some((used, size)), cost) => tracker.usage_update_for_session(
+            session_id,
+            saturating_u32(used),
+            saturating_u32(size),
+            cost,
+        ),
+        (None, Some(cost)) => tracker.cost_update_for_session(session_id, cost),
+        (None, None) => {}
+    }
+}
+
+fn meta_cost(v: &Value) -> Option<Cost> {
+    let usd = |amount| Cost {
+        amount,
+        currency: "USD".to_string(),
+        estimated: false,
+    };
+    match v.get("cost") {
+        Some(Value::Number(n)) => return n.as_f64().map(usd),
+        Some(cost @ Value::Object(_)) => return serde_json::from_value(cost.clone()).ok(),
+        _ => {}
+    }
+    ["costUsd", "total_cost_usd"]
+        .iter()
+        .find_map(|key| v.get(key)?.as_f64())
+        .map(usd)
+}
+
+fn token_usage(usage: &Usage) -> TokenUsage {
+    TokenUsage {
+        input: usage.input_tokens,
+        output: usage.output_tokens,
+        thought: usage.thought_tokens.unwrap_or(0),
+        cache_read: usage.cached_read_tokens.unwrap_or(0),
+        cache_write: usage.cached_write_tokens.unwrap_or(0),
+    }
+}
+
+fn saturating_u32(n: u64) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
 /// Extract file locations from a new `ToolCall`.
-fn extract_from_tool_call(tc: &ToolCall, session_id: &str, tracker: &mut ContextTracker) {
+fn extract_from_tool_call(
+    tc: &ToolCall,
+    session_id: &str,
+    tracker: &mut ContextTracker,
+    files: &DiffFiles,
+) {
     let action = tool_kind_to_action(&tc.kind);
     debug!(
         tool_call_id = %tc.tool_call_id.0,
@@ -267,16 +473,8 @@ fn extract_from_tool_call(tc: &ToolCall, session_id: &str, tracker: &mut Context
         content_blocks = tc.content.len(),
         "tool_call"
     );
-    for loc in &tc.locations {
-        let path = loc.path.to_string_lossy().to_string();
-        debug!(
-            path = path.as_str(),
-            action = format!("{:?}", action).as_str(),
-            "tool_call location"
-        );
-        tracker.file_access_for_session(session_id, &path, action);
-    }
-    extract_diff_paths(&tc.content, Action::Write, session_id, tracker);
+    extract_locations(&tc.locations, action, session_id, tracker);
+    extract_diff_paths(&tc.content, Action::Write, session_id, tracker, files);
     if matches!(tc.kind, ToolKind::Search | ToolKind::Execute) {
         extract_search_result_paths(&tc.content, session_id, tracker);
     }
@@ -290,6 +488,7 @@ fn extract_from_tool_call_update(
     tcu: &ToolCallUpdate,
     session_id: &str,
     tracker: &mut ContextTracker,
+    files: &DiffFiles,
 ) {
     let action = tcu
         .fields
@@ -313,42 +512,187 @@ fn extract_from_tool_call_update(
         "tool_call_update"
     );
     if let Some(locations) = &tcu.fields.locations {
-        for loc in locations {
-            let path = loc.path.to_string_lossy().to_string();
-            debug!(
-                path = path.as_str(),
-                action = format!("{:?}", action).as_str(),
-                "tool_call_update location"
-            );
-            tracker.file_access_for_session(session_id, &path, action);
-        }
+        extract_locations(locations, action, session_id, tracker);
     }
     if let Some(content) = &tcu.fields.content {
-        extract_diff_paths(content, Action::Write, session_id, tracker);
+        extract_diff_paths(content, Action::Write, session_id, tracker, files);
         if is_search_or_execute {
             extract_search_result_paths(content, session_id, tracker);
         }
     }
 }
 
+/// Record a tool call's locations. A move lists the source first and the
+/// destination last; every other kind applies its action to each location.
+fn extract_locations(
+    locations: &[ToolCallLocation],
+    action: Action,
+    session_id: &str,
+    tracker: &mut ContextTracker,
+) {
+    if let (Action::Move, [from, .., to]) = (action, locations) {
+        let from = from.path.to_string_lossy().to_string();
+        let to = to.path.to_string_lossy().to_string();
+        debug!(from = from.as_str(), to = to.as_str(), "tool_call move");
+        tracker.file_move_for_session(session_id, &from, &to, Source::ToolCall);
+        return;
+    }
+    for loc in locations {
+        let path = loc.path.to_string_lossy().to_string();
+        debug!(
+            path = path.as_str(),
+            action = format!("{:?}", action).as_str(),
+            line = loc.line,
+            "tool_call location"
+        );
+        tracker.file_access_lines_for_session(
+            session_id,
+            &path,
+            action,
+            Source::ToolCall,
+            loc.line.map(LineRange::line),
+        );
+    }
+}
+
+/// Lines covered by an `fs/read_text_file` request. `None` when the whole
+/// file was read; without a `limit` the read runs to the end of the file.
+fn read_range(line: Option<u32>, limit: Option<u32>) -> Option<LineRange> {
+    if line.is_none() && limit.is_none() {
+        return None;
+    }
+    let start = line.unwrap_or(1).max(1);
+    match limit {
+        Some(0) => None,
+        Some(n) => Some(LineRange::new(start, start.saturating_add(n - 1))),
+        None => Some(LineRange::from_line(start)),
+    }
+}
+
 /// Extract file paths from `ToolCallContent::Diff` blocks.
 ///
-/// Diffs always represent file modifications, so action is `Write`.
+/// Diffs always represent file modifications, so action is `Write`. The
+/// changed hunk is recorded as the access's line range.
 fn extract_diff_paths(
     content: &[ToolCallContent],
     action: Action,
     session_id: &str,
     tracker: &mut ContextTracker,
+    files: &DiffFiles,
 ) {
     for item in content {
         if let ToolCallContent::Diff(diff) = item {
             let path = diff.path.to_string_lossy().to_string();
-            debug!(path = path.as_str(), "diff content block");
-            tracker.file_access_for_session(session_id, &path, action);
+            let lines = diff_line_range(diff, files.get(&diff.path));
+            debug!(path = path.as_str(), ?lines, "diff content block");
+            tracker.file_access_lines_for_session(session_id, &path, action, Source::Diff, lines);
         }
     }
 }
 
+/// Lines a diff changed, numbered as in the file after the edit.
+///
+/// The hunk is what's left of `new_text` once the lines it shares with
+/// `old_text` at either end are trimmed; a pure deletion marks the line it
+/// happened at. Agents often send just the edited snippet rather than the
+/// whole file, so the snippet is located in `file`, the start of the file
+/// on disk (after the edit, or before it if the write hasn't landed yet),
+/// to offset the hunk.
+fn diff_line_range(diff: &Diff, file: Option<&str>) -> Option<LineRange> {
+    let new_lines: Vec<&str> = diff.new_text.lines().collect();
+    let old_lines: Vec<&str> = diff
+        .old_text
+        .as_deref()
+        .map(|t| t.lines().collect())
+        .unwrap_or_default();
+    if new_lines.is_empty() && old_lines.is_empty() {
+        return None;
+    }
+    let prefix = new_lines
+        .iter()
+        .zip(&old_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = new_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(old_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let start = prefix as u32 + 1;
+    let end = (new_lines.len() - suffix) as u32;
+
+    let offset = file
+        .and_then(|content| {
+            [Some(diff.new_text.as_str()), diff.old_text.as_deref()]
+                .into_iter()
+                .flatten()
+                .filter(|text| !text.is_empty())
+                .find_map(|text| content.find(text))
+                .map(|at| content[..at].matches('\n').count() as u32)
+        })
+        .unwrap_or(0);
+    Some(LineRange::new(offset + start, offset + end))
+}
+
+/// The start of each file the diffs in a downstream message edit, keyed by
+/// the path the agent sent. Read before the tracker is locked, so locating
+/// a hunk never touches the disk under it.
+#[derive(Debug, Default)]
+pub struct DiffFiles(HashMap<PathBuf, String>);
+
+impl DiffFiles {
+    pub fn insert(&mut self, path: PathBuf, content: String) {
+        self.0.insert(path, content);
+    }
+
+    fn get(&self, path: &Path) -> Option<&str> {
+        self.0.get(path).map(String::as_str)
+    }
+}
+
+/// Files the diffs in a downstream `line` edit: the path as the agent sent
+/// it, and where it is on disk, resolved against the workspace roots in
+/// `paths` like the tracker's own paths.
+pub fn diff_files(line: &str, paths: &PathResolver) -> Vec<(PathBuf, PathBuf)> {
+    // Cheap check before parsing every update
+    if !line.contains("\"diff\"") {
+        return Vec::new();
+    }
+    let Ok(v) = serde_json::from_str::<Value>(line) else {
+        return Vec::new();
+    };
+    if v.get("method").and_then(|m| m.as_str()) != Some(CLIENT_METHOD_NAMES.session_update) {
+        return Vec::new();
+    }
+    let Some(notif) = v
+        .get("params")
+        .and_then(|p| serde_json::from_value::<SessionNotification>(p.clone()).ok())
+    else {
+        return Vec::new();
+    };
+    let content = match &notif.update {
+        SessionUpdate::ToolCall(tc) => tc.content.as_slice(),
+        SessionUpdate::ToolCallUpdate(tcu) => tcu.fields.content.as_deref().unwrap_or_default(),
+        _ => return Vec::new(),
+    };
+    content
+        .iter()
+        .filter_map(|item| match item {
+            ToolCallContent::Diff(diff) => Some(&diff.path),
+            _ => None,
+        })
+        .filter_map(|path| {
+            let fs_path = match paths.resolve(&path.to_string_lossy()) {
+                ResolvedPath::Inside(id) => paths.to_fs_path(&id),
+                ResolvedPath::Outside(full) => PathBuf::from(full),
+                ResolvedPath::Escape => return None,
+            };
+            Some((path.clone(), fs_path))
+        })
+        .collect()
+}
+
 /// Extract file paths from the text content of search tool results.
 ///
 /// Search tools (grep, glob, find, etc.) return results as text where each
@@ -375,7 +719,12 @@ fn extract_search_result_paths(
             if let Some(path) = extract_path_from_line(line) {
                 if std::path::Path::new(&path).extension().is_some() {
                     debug!(path = path.as_str(), "search result file");
-                    tracker.file_access_for_session(session_id, &path, Action::Search);
+                    tracker.file_access_for_session(
+                        session_id,
+                        &path,
+                        Action::Search,
+                        Source::SearchResult,
+                    );
                 }
             }
         }
@@ -387,7 +736,7 @@ fn extract_search_result_paths(
 /// Handles common formats:
 /// - `/path/to/file.rs`           (glob / find output)
 /// - `/path/to/file.rs:42:…`      (grep / ripgrep output)
-fn extract_path_from_line(line: &str) -> Option<String> {
+pub(crate) fn extract_path_from_line(line: &str) -> Option<String> {
     if !line.starts_with('/') {
         return None;
     }
@@ -411,7 +760,12 @@ fn extract_shell_write_paths(title: &str, session_id: &str, tracker: &mut Contex
         let part = part.trim();
         if let Some(path) = extract_redirect_target(part) {
             debug!(path = path.as_str(), "shell write target");
-            tracker.file_access_for_session(session_id, &path, Action::Write);
+            tracker.file_access_for_session(
+                session_id,
+                &path,
+                Action::Write,
+                Source::ShellRedirect,
+            );
         }
     }
 }
@@ -445,7 +799,12 @@ fn extract_paths_from_terminal_output(
         }
         if let Some(path) = extract_path_from_line(line) {
             debug!(path = path.as_str(), "terminal output file");
-            tracker.file_access_for_session(session_id, &path, Action::Search);
+            tracker.file_access_for_session(
+                session_id,
+                &path,
+                Action::Search,
+                Source::TerminalOutput,
+            );
         }
     }
 }
@@ -466,7 +825,9 @@ fn session_id_from_params(params: &serde_json::Value, tracker: &ContextTracker)
 pub fn tool_kind_to_action(kind: &ToolKind) -> Action {
     match kind {
         ToolKind::Read => Action::Read,
-        ToolKind::Edit | ToolKind::Delete | ToolKind::Move => Action::Write,
+        ToolKind::Edit => Action::Write,
+        ToolKind::Delete => Action::Delete,
+        ToolKind::Move => Action::Move,
         ToolKind::Search => Action::Search,
         // Execute, Fetch, Think, SwitchMode, Other — no file-level action
         _ => Action::Read,
@@ -585,6 +946,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn heuristic_hits_carry_their_source_and_lower_confidence() {
+        let mut tracker = make_tracker();
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc3c","title":"Grep","kind":"search","status":"completed","content":[{"type":"content","content":{"type":"text","text":"/home/user/src/main.rs:42:    fn main() {}"}}],"locations":[{"path":"/home/user/src"}]}}}"#;
+        extract_downstream(line, &mut tracker);
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc3d","title":"echo hi > /home/user/out.txt","kind":"execute","status":"pending"}}}"#;
+        extract_downstream(line, &mut tracker);
+
+        let snap = tracker.snapshot();
+        let dir = &snap.nodes["/home/user/src"];
+        assert_eq!(dir.source, Source::ToolCall);
+        assert_eq!(dir.confidence, 1.0);
+        let hit = &snap.nodes["/home/user/src/main.rs"];
+        assert_eq!(hit.source, Source::SearchResult);
+        assert_eq!(hit.confidence, Source::SearchResult.confidence());
+        let redirect = &snap.nodes["/home/user/out.txt"];
+        assert_eq!(redirect.source, Source::ShellRedirect);
+        assert!(redirect.confidence < hit.confidence);
+
+        // A structured access confirms the file; later hits don't demote it
+        let read = r#"{"jsonrpc":"2.0","id":30,"method":"fs/read_text_file","params":{"sessionId":"s1","path":"/home/user/src/main.rs"}}"#;
+        extract_downstream(read, &mut tracker);
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc3e","title":"Grep","kind":"search","status":"completed","content":[{"type":"content","content":{"type":"text","text":"/home/user/src/main.rs:43:    x"}}]}}}"#;
+        extract_downstream(line, &mut tracker);
+        let hit = &tracker.snapshot().nodes["/home/user/src/main.rs"];
+        assert_eq!(hit.source, Source::SearchResult);
+        assert_eq!(hit.confidence, 1.0);
+    }
+
     #[test]
     fn extract_search_result_files_from_tool_call_update() {
         let mut tracker = make_tracker();
@@ -634,6 +1024,29 @@ mod tests {
         assert_eq!(snap.nodes["/home/user/README.md"].last_action, Action::Read);
     }
 
+    #[test]
+    fn delete_tool_call_drops_the_node() {
+        let mut tracker = make_tracker();
+        tracker.file_access_for_session("s1", "/home/user/old.rs", Action::Read, Source::Fs);
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc6","title":"Delete old.rs","kind":"delete","status":"pending","locations":[{"path":"/home/user/old.rs"}]}}}"#;
+        extract_downstream(line, &mut tracker);
+        assert!(tracker.snapshot().nodes.is_empty());
+        let delta = tracker.tick().unwrap();
+        assert_eq!(delta.removed, vec!["/home/user/old.rs".to_string()]);
+    }
+
+    #[test]
+    fn move_tool_call_moves_source_to_destination() {
+        let mut tracker = make_tracker();
+        tracker.file_access_for_session("s1", "/home/user/a.rs", Action::Read, Source::Fs);
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call_update","toolCallId":"tc7","kind":"move","locations":[{"path":"/home/user/a.rs"},{"path":"/home/user/b.rs"}]}}}"#;
+        extract_downstream(line, &mut tracker);
+        let snap = tracker.snapshot();
+        assert!(!snap.nodes.contains_key("/home/user/a.rs"));
+        assert_eq!(snap.nodes["/home/user/b.rs"].last_action, Action::Move);
+        assert_eq!(snap.nodes["/home/user/b.rs"].source, Source::ToolCall);
+    }
+
     // -- fs/read_text_file ------------------------------------------------
 
     #[test]
@@ -644,6 +1057,38 @@ mod tests {
         let snap = tracker.snapshot();
         assert!(snap.nodes.contains_key("/home/user/src/db.ts"));
         assert_eq!(snap.nodes["/home/user/src/db.ts"].last_action, Action::Read);
+        assert_eq!(
+            snap.nodes["/home/user/src/db.ts"].line_ranges,
+            vec![LineRange::new(1, 100)]
+        );
+    }
+
+    #[test]
+    fn read_and_location_lines_merge_into_ranges() {
+        let mut tracker = make_tracker();
+        let reads = [
+            r#"{"jsonrpc":"2.0","id":12,"method":"fs/read_text_file","params":{"sessionId":"s1","path":"/home/user/src/db.ts","line":40,"limit":10}}"#,
+            r#"{"jsonrpc":"2.0","id":13,"method":"fs/read_text_file","params":{"sessionId":"s1","path":"/home/user/src/db.ts","line":10,"limit":5}}"#,
+            r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc20","title":"Read","kind":"read","status":"pending","locations":[{"path":"/home/user/src/db.ts","line":50}]}}}"#,
+            r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call_update","toolCallId":"tc20","locations":[{"path":"/home/user/src/db.ts","line":15}]}}}"#,
+        ];
+        for line in reads {
+            extract_downstream(line, &mut tracker);
+        }
+        let snap = tracker.snapshot();
+        assert_eq!(
+            snap.nodes["/home/user/src/db.ts"].line_ranges,
+            vec![LineRange::new(10, 15), LineRange::new(40, 50)]
+        );
+    }
+
+    #[test]
+    fn read_range_from_line_and_limit() {
+        assert_eq!(read_range(None, None), None);
+        assert_eq!(read_range(Some(5), Some(0)), None);
+        assert_eq!(read_range(None, Some(20)), Some(LineRange::new(1, 20)));
+        assert_eq!(read_range(Some(5), Some(3)), Some(LineRange::new(5, 7)));
+        assert_eq!(read_range(Some(5), None), Some(LineRange::from_line(5)));
     }
 
     // -- fs/write_text_file -----------------------------------------------
@@ -771,6 +1216,67 @@ mod tests {
         assert_eq!(tracker.snapshot().nodes["/c.rs"].turn_accessed, 0);
     }
 
+    // -- Usage and cost ----------------------------------------------------
+
+    #[test]
+    fn usage_update_carries_cost() {
+        let mut tracker = make_tracker();
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"usage_update","used":53000,"size":200000,"cost":{"amount":0.42,"currency":"USD"}}}}"#;
+        extract_downstream(line, &mut tracker);
+
+        let usage = tracker.take_pending_usage();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].used, 53_000);
+        assert_eq!(usage[0].size, 200_000);
+        let cost = usage[0].cost.as_ref().unwrap();
+        assert_eq!((cost.amount, cost.currency.as_str()), (0.42, "USD"));
+        assert_eq!(tracker.session_spend("s1").unwrap().amount, 0.42);
+    }
+
+    #[test]
+    fn usage_and_cost_read_from_meta() {
+        let mut tracker = make_tracker();
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"ok"},"_meta":{"usage":{"used":1000,"size":8000},"total_cost_usd":0.05}}}}"#;
+        extract_downstream(line, &mut tracker);
+        let usage = tracker.take_pending_usage();
+        assert_eq!(usage.len(), 1);
+        assert_eq!((usage[0].used, usage[0].size), (1000, 8000));
+        assert_eq!(usage[0].cost.as_ref().unwrap().amount, 0.05);
+
+        // Cost alone repeats the last known window
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","_meta":{"cost":{"amount":0.08,"currency":"USD"}},"update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"ok"}}}}"#;
+        extract_downstream(line, &mut tracker);
+        let usage = tracker.take_pending_usage();
+        assert_eq!(usage.len(), 1);
+        assert_eq!((usage[0].used, usage[0].size), (1000, 8000));
+        assert_eq!(usage[0].cost.as_ref().unwrap().amount, 0.08);
+
+        // No usage in _meta, nothing reported
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"ok"},"_meta":{"trace":"abc"}}}}"#;
+        extract_downstream(line, &mut tracker);
+        assert!(tracker.take_pending_usage().is_empty());
+    }
+
+    #[test]
+    fn prompt_usage_is_priced_with_the_session_model() {
+        let mut tracker = ContextTracker::new(TrackerConfig::default());
+        let new_session = r#"{"jsonrpc":"2.0","id":1,"result":{"sessionId":"s1","models":{"currentModelId":"gpt-4o","availableModels":[]}}}"#;
+        extract_downstream(new_session, &mut tracker);
+        assert_eq!(tracker.session_model("s1"), Some("gpt-4o"));
+
+        let prompt = r#"{"jsonrpc":"2.0","id":2,"method":"session/prompt","params":{"sessionId":"s1","prompt":[]}}"#;
+        extract_upstream(prompt, &mut tracker);
+        let response = r#"{"jsonrpc":"2.0","id":2,"result":{"stopReason":"end_turn","usage":{"totalTokens":1100000,"inputTokens":1000000,"outputTokens":100000}}}"#;
+        extract_downstream(response, &mut tracker);
+        let spend = tracker.session_spend("s1").unwrap();
+        assert!((spend.amount - 3.5).abs() < 1e-9);
+        assert!(spend.estimated);
+
+        let set_model = r#"{"jsonrpc":"2.0","id":3,"method":"session/set_model","params":{"sessionId":"s1","modelId":"gpt-4o-mini"}}"#;
+        extract_upstream(set_model, &mut tracker);
+        assert_eq!(tracker.session_model("s1"), Some("gpt-4o-mini"));
+    }
+
     // -- Diff content extraction -------------------------------------------
 
     #[test]
@@ -819,8 +1325,8 @@ mod tests {
     fn tool_kind_mapping() {
         assert_eq!(tool_kind_to_action(&ToolKind::Read), Action::Read);
         assert_eq!(tool_kind_to_action(&ToolKind::Edit), Action::Write);
-        assert_eq!(tool_kind_to_action(&ToolKind::Delete), Action::Write);
-        assert_eq!(tool_kind_to_action(&ToolKind::Move), Action::Write);
+        assert_eq!(tool_kind_to_action(&ToolKind::Delete), Action::Delete);
+        assert_eq!(tool_kind_to_action(&ToolKind::Move), Action::Move);
         assert_eq!(tool_kind_to_action(&ToolKind::Search), Action::Search);
         assert_eq!(tool_kind_to_action(&ToolKind::Execute), Action::Read);
         assert_eq!(tool_kind_to_action(&ToolKind::Fetch), Action::Read);
@@ -865,6 +1371,20 @@ mod tests {
         assert_eq!(tracker.session_id(), "cli-provided");
     }
 
+    #[test]
+    fn initialize_response_sets_embedded_context() {
+        let mut tracker = make_tracker();
+        assert!(!tracker.embedded_context());
+
+        let line = r#"{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true,"promptCapabilities":{"image":true,"embeddedContext":true}}}}"#;
+        extract_downstream(line, &mut tracker);
+        assert!(tracker.embedded_context());
+
+        let line = r#"{"jsonrpc":"2.0","id":0,"result":{"protocolVersion":1,"agentCapabilities":{"promptCapabilities":{}}}}"#;
+        extract_downstream(line, &mut tracker);
+        assert!(!tracker.embedded_context());
+    }
+
     #[test]
     fn session_id_not_set_from_non_session_response() {
         let mut tracker = ContextTracker::new(TrackerConfig::default());
@@ -873,4 +1393,174 @@ mod tests {
         extract_downstream(line, &mut tracker);
         assert_eq!(tracker.session_id(), "");
     }
+
+    // -- session/load replay ----------------------------------------------
+
+    fn replay_update(update: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"s1","update":{update}}}}}"#
+        )
+    }
+
+    #[test]
+    fn session_load_replay_rebuilds_turns_without_heat() {
+        let mut tracker = ContextTracker::new(TrackerConfig {
+            context_turns: 1,
+            ..TrackerConfig::default()
+        });
+        let line = r#"{"jsonrpc":"2.0","id":5,"method":"session/load","params":{"sessionId":"s1","cwd":"/home/user","mcpServers":[]}}"#;
+        extract_upstream(line, &mut tracker);
+        assert_eq!(tracker.session_id(), "s1");
+        assert!(tracker.is_replaying("s1"));
+
+        let user =
+            r#"{"sessionUpdate":"user_message_chunk","content":{"type":"text","text":"hi"}}"#;
+        let agent =
+            r#"{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"ok"}}"#;
+        for update in [
+            user,
+            r#"{"sessionUpdate":"tool_call","toolCallId":"tc1","title":"Read","kind":"read","status":"completed","locations":[{"path":"/a.rs"}]}"#,
+            agent,
+            user,
+            user, // second chunk of the same message
+            r#"{"sessionUpdate":"tool_call","toolCallId":"tc2","title":"Edit","kind":"edit","status":"completed","locations":[{"path":"/b.rs"}]}"#,
+        ] {
+            extract_downstream(&replay_update(update), &mut tracker);
+        }
+        // Nothing is broadcast mid-replay
+        assert!(tracker.tick().is_none());
+
+        extract_downstream(r#"{"jsonrpc":"2.0","id":5,"result":{}}"#, &mut tracker);
+        assert!(!tracker.is_replaying("s1"));
+        assert_eq!(tracker.current_turn(), 2);
+
+        let snap = tracker.snapshot();
+        // /a.rs was last touched two turns ago and is out of context
+        assert!(!snap.nodes.contains_key("/a.rs"));
+        let b = &snap.nodes["/b.rs"];
+        assert_eq!(b.heat, 0.0);
+        assert!(b.in_context);
+        assert_eq!(b.turn_accessed, 1);
+        assert_eq!(b.last_action, Action::Write);
+        assert_eq!(tracker.tick().unwrap().updates.len(), 1);
+
+        let resumed = tracker.take_pending_resumed();
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].session_id, "s1");
+
+        // Live accesses after the replay heat files as usual
+        extract_downstream(
+            &replay_update(
+                r#"{"sessionUpdate":"tool_call","toolCallId":"tc3","title":"Read","kind":"read","status":"completed","locations":[{"path":"/c.rs"}]}"#,
+            ),
+            &mut tracker,
+        );
+        let c = &tracker.snapshot().nodes["/c.rs"];
+        assert_eq!(c.heat, 1.0);
+        assert_eq!(c.turn_accessed, 2);
+    }
+
+    #[test]
+    fn session_resume_marks_resumed_only_on_success() {
+        let mut tracker = make_tracker();
+        let line = r#"{"jsonrpc":"2.0","id":7,"method":"session/resume","params":{"sessionId":"s2","cwd":"/home/user"}}"#;
+        extract_upstream(line, &mut tracker);
+        // No replay for resume, and the CLI session stays the default
+        assert!(!tracker.is_replaying("s2"));
+        assert_eq!(tracker.session_id(), "s1");
+
+        extract_downstream(
+            r#"{"jsonrpc":"2.0","id":7,"error":{"code":-32601,"message":"nope"}}"#,
+            &mut tracker,
+        );
+        assert!(tracker.take_pending_resumed().is_empty());
+
+        extract_upstream(line, &mut tracker);
+        extract_downstream(r#"{"jsonrpc":"2.0","id":7,"result":{}}"#, &mut tracker);
+        assert_eq!(tracker.take_pending_resumed()[0].session_id, "s2");
+    }
+
+    #[test]
+    fn prompt_ends_an_unfinished_replay() {
+        let mut tracker = ContextTracker::new(TrackerConfig::default());
+        let line = r#"{"jsonrpc":"2.0","id":5,"method":"session/load","params":{"sessionId":"s1","cwd":"/","mcpServers":[]}}"#;
+        extract_upstream(line, &mut tracker);
+        assert!(tracker.is_replaying("s1"));
+
+        let line = r#"{"jsonrpc":"2.0","id":6,"method":"session/prompt","params":{"sessionId":"s1","prompt":[{"type":"resource_link","uri":"file:///a.ts","name":"a"}]}}"#;
+        extract_upstream(line, &mut tracker);
+        assert!(!tracker.is_replaying("s1"));
+        assert_eq!(tracker.snapshot().nodes["/a.ts"].heat, 1.0);
+    }
+
+    // -- Diff hunks -------------------------------------------------------
+
+    fn diff(path: &std::path::Path, old_text: Option<&str>, new_text: &str) -> Diff {
+        serde_json::from_value(serde_json::json!({
+            "path": path,
+            "oldText": old_text,
+            "newText": new_text,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn diff_range_trims_unchanged_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.rs");
+        // Whole-file diff
+        let d = diff(&path, Some("a\nb\nc\nd\n"), "a\nB\nC\nd\n");
+        assert_eq!(diff_line_range(&d, None), Some(LineRange::new(2, 3)));
+        // New file
+        let d = diff(&path, None, "a\nb\n");
+        assert_eq!(diff_line_range(&d, None), Some(LineRange::new(1, 2)));
+        // Deletion marks the line it happened at
+        let d = diff(&path, Some("a\nb\nc\n"), "a\nc\n");
+        assert_eq!(diff_line_range(&d, None), Some(LineRange::line(2)));
+    }
+
+    #[test]
+    fn diff_snippet_is_located_in_the_file() {
+        let path = Path::new("src/app.rs");
+        let before = "fn a() {}\n\nfn b() {\n    1\n}\n";
+        let after = before.replace("    1", "    2");
+        // Not applied yet: found via old_text
+        let d = diff(path, Some("fn b() {\n    1\n}"), "fn b() {\n    2\n}");
+        assert_eq!(diff_line_range(&d, Some(before)), Some(LineRange::line(4)));
+        // Applied: found via new_text
+        assert_eq!(diff_line_range(&d, Some(&after)), Some(LineRange::line(4)));
+
+        let mut tracker = make_tracker();
+        tracker.set_workspace_root(PathBuf::from("/home/user"));
+        let mut files = DiffFiles::default();
+        files.insert(path.to_path_buf(), after);
+        extract_diff_paths(
+            &[ToolCallContent::Diff(d)],
+            Action::Write,
+            "s1",
+            &mut tracker,
+            &files,
+        );
+        assert_eq!(
+            tracker.snapshot().nodes["src/app.rs"].line_ranges,
+            vec![LineRange::line(4)]
+        );
+    }
+
+    #[test]
+    fn diff_files_resolve_against_the_workspace() {
+        let paths = PathResolver::new(Some(PathBuf::from("/home/user")));
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call_update","toolCallId":"tc1","content":[{"type":"diff","path":"src/app.rs","newText":"x"},{"type":"diff","path":"/tmp/out.rs","newText":"y"},{"type":"diff","path":"../escape.rs","newText":"z"}]}}}"#;
+        assert_eq!(
+            diff_files(line, &paths),
+            vec![
+                (
+                    PathBuf::from("src/app.rs"),
+                    PathBuf::from("/home/user/src/app.rs")
+                ),
+                (PathBuf::from("/tmp/out.rs"), PathBuf::from("/tmp/out.rs")),
+            ]
+        );
+        assert!(diff_files(r#"{"method":"fs/read_text_file"}"#, &paths).is_empty());
+    }
 }
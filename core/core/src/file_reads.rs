@@ -0,0 +1,213 @@
+//! Disk reads the tracker needs, done off its lock.
+//!
+//! Recording an access must not stall ACP traffic, so while locked the
+//! tracker only consults its caches and queues the files it would have to
+//! read (`ContextTracker::take_pending_reads`). `run` does the reads on a
+//! blocking thread and hands the results back through
+//! `ContextTracker::apply_reads`; until they land, a node keeps its last
+//! known token estimate and line-level accesses are attributed with the
+//! symbol spans from the file's last parse.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::parser::walk::count_tokens;
+use crate::symbols::{self, SymbolSpan};
+use crate::tracker::ContextTracker;
+use crate::types::LineRange;
+
+/// Files larger than this are estimated at 4 bytes per token instead of
+/// being tokenized, and only this much of them is read to estimate their
+/// line count.
+pub const MAX_TOKENIZED_BYTES: u64 = 512 * 1024;
+
+/// Token and line counts for a file, valid while its size and mtime match.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenEstimate {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub tokens: u32,
+    pub lines: u32,
+}
+
+impl TokenEstimate {
+    /// Clamp a range to the end of the file. With the length unknown, an
+    /// open-ended range is cut down to its first line.
+    pub fn clamp(&self, range: LineRange) -> LineRange {
+        if self.lines == 0 {
+            return match range.end {
+                u32::MAX => LineRange::line(range.start),
+                _ => range,
+            };
+        }
+        LineRange::new(range.start, range.end.min(self.lines))
+    }
+}
+
+/// Estimate for the file at `path`, reusing `cached` if its size and mtime
+/// still match. `None` if it isn't a readable file.
+///
+/// At most `MAX_TOKENIZED_BYTES` are read; the line count of a larger file
+/// is extrapolated from that prefix. Blocking.
+pub fn estimate_file(path: &Path, cached: Option<TokenEstimate>) -> Option<TokenEstimate> {
+    let meta = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    let modified = meta.modified().ok();
+    if let Some(cached) = cached.filter(|c| c.len == meta.len() && c.modified == modified) {
+        return Some(cached);
+    }
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)
+        .and_then(|file| file.take(MAX_TOKENIZED_BYTES).read_to_end(&mut bytes))
+        .ok()?;
+    let rough = (meta.len() / 4).min(u32::MAX as u64) as u32;
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count() as u64;
+    let (tokens, lines) = if meta.len() > MAX_TOKENIZED_BYTES {
+        let lines = (newlines * meta.len() / bytes.len().max(1) as u64).max(1);
+        (rough, lines)
+    } else {
+        let tokens = match std::str::from_utf8(&bytes) {
+            Ok(content) => count_tokens(content),
+            // Binary: fall back to the byte estimate
+            Err(_) => rough,
+        };
+        let unterminated = bytes.last().is_some_and(|&b| b != b'\n');
+        (tokens, newlines + unterminated as u64)
+    };
+    Some(TokenEstimate {
+        len: meta.len(),
+        modified,
+        tokens,
+        lines: lines.min(u32::MAX as u64) as u32,
+    })
+}
+
+/// Reads the tracker queued, keyed by tracked path.
+#[derive(Debug, Default)]
+pub struct PendingReads {
+    /// Files to (re-)estimate: where they are on disk and the estimate
+    /// cached for them, if any.
+    pub estimates: HashMap<String, (PathBuf, Option<TokenEstimate>)>,
+    /// Files whose symbol spans went stale, to re-parse.
+    pub symbols: HashMap<String, PathBuf>,
+}
+
+impl PendingReads {
+    pub fn is_empty(&self) -> bool {
+        self.estimates.is_empty() && self.symbols.is_empty()
+    }
+
+    /// Do the reads. Blocking.
+    pub fn read(self) -> CompletedReads {
+        let estimates = self
+            .estimates
+            .into_iter()
+            .map(|(path, (fs_path, cached))| {
+                let estimate = estimate_file(&fs_path, cached);
+                (path, fs_path, estimate)
+            })
+            .collect();
+        let symbols = self
+            .symbols
+            .into_iter()
+            .map(|(path, fs_path)| {
+                let spans = symbols::parse_file(&path, &fs_path);
+                (path, spans)
+            })
+            .collect();
+        CompletedReads { estimates, symbols }
+    }
+}
+
+/// Results of `PendingReads::read`, for `ContextTracker::apply_reads`.
+#[derive(Debug, Default)]
+pub struct CompletedReads {
+    /// Tracked path, filesystem path and fresh estimate; `None` for files
+    /// that are gone.
+    pub estimates: Vec<(String, PathBuf, Option<TokenEstimate>)>,
+    /// Tracked path and its re-parsed symbol spans.
+    pub symbols: Vec<(String, Vec<SymbolSpan>)>,
+}
+
+/// Do the tracker's queued reads, if any, and apply the results.
+pub async fn process(tracker: &Arc<Mutex<ContextTracker>>) {
+    let pending = tracker.lock().await.take_pending_reads();
+    if pending.is_empty() {
+        return;
+    }
+    match tokio::task::spawn_blocking(move || pending.read()).await {
+        Ok(done) => tracker.lock().await.apply_reads(done),
+        Err(e) => warn!(error = %e, "file reads failed"),
+    }
+}
+
+/// Process queued reads as the tracker asks for them.
+pub async fn run(tracker: Arc<Mutex<ContextTracker>>) {
+    let wake = tracker.lock().await.reads_wake();
+    loop {
+        wake.notified().await;
+        process(&tracker).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Action, TrackerConfig};
+
+    #[test]
+    fn unchanged_files_keep_their_cached_estimate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}\n").unwrap();
+        let estimate = estimate_file(&path, None).unwrap();
+        assert_eq!(estimate.lines, 1);
+        assert!(estimate.tokens > 0);
+
+        let marked = TokenEstimate {
+            tokens: 999,
+            ..estimate
+        };
+        assert_eq!(estimate_file(&path, Some(marked)), Some(marked));
+        std::fs::write(&path, "fn a() {}\nfn b() {}\n").unwrap();
+        assert_eq!(estimate_file(&path, Some(marked)).unwrap().lines, 2);
+
+        assert!(estimate_file(&dir.path().join("missing.rs"), None).is_none());
+        assert!(estimate_file(dir.path(), None).is_none());
+    }
+
+    #[tokio::test]
+    async fn estimates_land_after_the_access() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.rs"),
+            "fn a() { let x = 1; }\n".repeat(10),
+        )
+        .unwrap();
+        let mut t = ContextTracker::new(TrackerConfig::default());
+        t.set_workspace_root(dir.path().to_path_buf());
+        let tracker = Arc::new(Mutex::new(t));
+
+        tracker.lock().await.file_access("a.rs", Action::Read);
+        // Nothing was read while the access was recorded
+        assert_eq!(
+            tracker.lock().await.snapshot().nodes["a.rs"].estimated_tokens,
+            0
+        );
+
+        process(&tracker).await;
+        let mut t = tracker.lock().await;
+        let tokens = t.snapshot().nodes["a.rs"].estimated_tokens;
+        assert!(tokens > 0);
+        assert!(t.take_pending_reads().is_empty());
+
+        // Later accesses use the cached estimate straight away
+        t.file_access("a.rs", Action::Write);
+        assert_eq!(t.snapshot().nodes["a.rs"].estimated_tokens, tokens);
+    }
+}
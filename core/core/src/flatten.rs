@@ -10,6 +10,7 @@ use indextree::NodeId;
 
 use crate::parser::tree::SymbolTree;
 use crate::parser::types::NodeKind;
+use crate::paths::{self, NamedRoot};
 use crate::types::{UiCallEdge, UiLineRange, UiNode, UiSnapshot};
 
 const SKIP_CALLEE_NAMES: &[&str] = &[
@@ -17,15 +18,44 @@ const SKIP_CALLEE_NAMES: &[&str] = &[
 ];
 
 pub fn flatten(tree: &SymbolTree, root_path: &Path, seq: u64) -> UiSnapshot {
+    flatten_workspace(tree, root_path, &[], seq)
+}
+
+/// Flatten the primary root and each named root into one snapshot. IDs
+/// under a named root are prefixed `name:`, the way the tracker keys those
+/// files; calls resolve across roots.
+pub fn flatten_workspace(
+    tree: &SymbolTree,
+    root_path: &Path,
+    named: &[(NamedRoot, SymbolTree)],
+    seq: u64,
+) -> UiSnapshot {
     let mut nodes = HashMap::new();
     let mut caller_calls: Vec<(String, Vec<String>)> = Vec::new();
-    if let Some(root_id) = tree.root() {
-        walk(tree, root_id, root_path, "", &mut nodes, &mut caller_calls);
+    let roots = std::iter::once((None, tree, root_path)).chain(
+        named
+            .iter()
+            .map(|(root, tree)| (Some(root.name.as_str()), tree, root.path.as_path())),
+    );
+    for (prefix, tree, root_path) in roots {
+        if let Some(root_id) = tree.root() {
+            let root = RootCtx {
+                path: root_path,
+                prefix,
+            };
+            walk(tree, root_id, &root, "", &mut nodes, &mut caller_calls);
+        }
     }
     let calls = resolve_calls(&nodes, &caller_calls);
     UiSnapshot { seq, nodes, calls }
 }
 
+/// The root a walk is under, and the ID prefix of its files.
+struct RootCtx<'a> {
+    path: &'a Path,
+    prefix: Option<&'a str>,
+}
+
 fn resolve_calls(
     nodes: &HashMap<String, UiNode>,
     caller_calls: &[(String, Vec<String>)],
@@ -95,7 +125,7 @@ fn resolve_calls(
 fn walk(
     tree: &SymbolTree,
     node_id: NodeId,
-    root: &Path,
+    root: &RootCtx,
     parent_id: &str,
     nodes: &mut HashMap<String, UiNode>,
     caller_calls: &mut Vec<(String, Vec<String>)>,
@@ -105,10 +135,6 @@ fn walk(
         None => return,
     };
 
-    if data.name.starts_with('.') {
-        return;
-    }
-
     let id = match &data.kind {
         NodeKind::Folder => {
             for child in tree.get_children(node_id) {
@@ -116,15 +142,16 @@ fn walk(
             }
             return;
         }
+        // The walker's ignore policy already decided which files exist
         NodeKind::File(_) => {
             let rel = Path::new(&data.path)
-                .strip_prefix(root)
+                .strip_prefix(root.path)
                 .map(|p| p.to_string_lossy().into_owned())
                 .unwrap_or_else(|_| data.path.clone());
-            if rel.split('/').any(|seg| seg.starts_with('.')) {
-                return;
+            match root.prefix {
+                Some(name) => paths::root_id(name, &rel),
+                None => rel,
             }
-            rel
         }
         _ => format!("{}::{}", parent_id, data.name),
     };
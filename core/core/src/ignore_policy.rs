@@ -0,0 +1,210 @@
+//! Which workspace paths eisen never shows, shared by the tracker (live
+//! graph), the parser's `DirectoryWalker` (symbol graph) and the watcher.
+//!
+//! Rules are gitignore patterns, applied in order so later ones win:
+//!
+//! 1. built-in defaults (VCS metadata, dependencies, build output, caches,
+//!    compiled artifacts)
+//! 2. git's global excludes file and the root's `.git/info/exclude`
+//! 3. the workspace root's `.gitignore`
+//! 4. the workspace root's `.eisenignore`
+//! 5. `TrackerConfig::ignore` / `observe --ignore` patterns
+//!
+//! A `!pattern` re-includes what an earlier rule ignored, so
+//! `.eisenignore` can bring back e.g. `!dist`.
+//!
+//! Ignore files below the root are only honored by the parser's directory
+//! walk, which also reads nested `.gitignore`, `.ignore` and `.eisenignore`
+//! files the way git does. The policy itself matches single paths without
+//! walking, so the tracker and the watcher don't see them.
+
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::{self, Gitignore, GitignoreBuilder};
+use tracing::warn;
+
+/// Ignore file for eisen-only rules, next to `.gitignore`.
+pub const EISENIGNORE_FILE: &str = ".eisenignore";
+
+const DEFAULT_PATTERNS: &[&str] = &[
+    ".git",
+    ".eisen",
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "out",
+    "coverage",
+    "__pycache__",
+    ".venv",
+    "venv",
+    ".pytest_cache",
+    ".mypy_cache",
+    ".tox",
+    "*.egg-info",
+    ".next",
+    ".nuxt",
+    ".turbo",
+    ".cache",
+    ".output",
+    "*.pyc",
+    "*.pyo",
+    "*.so",
+    "*.dylib",
+    "*.dll",
+];
+
+#[derive(Debug, Clone)]
+pub struct IgnorePolicy {
+    root: Option<PathBuf>,
+    rules: Gitignore,
+}
+
+impl Default for IgnorePolicy {
+    /// Built-in defaults only.
+    fn default() -> Self {
+        Self::load(None, &[])
+    }
+}
+
+impl IgnorePolicy {
+    /// Build the policy for a workspace. Without a `root` there are no
+    /// ignore files to read, and absolute paths are matched as if rooted at
+    /// `/`. Unreadable files and invalid patterns are logged and skipped.
+    pub fn load(root: Option<&Path>, patterns: &[String]) -> Self {
+        let mut builder = GitignoreBuilder::new(root.unwrap_or(Path::new("")));
+        for pattern in DEFAULT_PATTERNS {
+            builder
+                .add_line(None, pattern)
+                .expect("built-in ignore patterns are valid");
+        }
+        if let Some(root) = root {
+            let files = gitignore::gitconfig_excludes_path()
+                .into_iter()
+                .chain([".git/info/exclude", ".gitignore", EISENIGNORE_FILE].map(|f| root.join(f)));
+            for path in files {
+                if !path.is_file() {
+                    continue;
+                }
+                if let Some(e) = builder.add(&path) {
+                    warn!(path = %path.display(), error = %e, "bad ignore file rules skipped");
+                }
+            }
+        }
+        for pattern in patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                warn!(pattern = pattern.as_str(), error = %e, "invalid ignore pattern skipped");
+            }
+        }
+        let rules = builder.build().unwrap_or_else(|e| {
+            warn!(error = %e, "failed to build ignore rules, using none");
+            Gitignore::empty()
+        });
+        Self {
+            root: root.map(Path::to_path_buf),
+            rules,
+        }
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Whether `path` (relative to the root, or absolute) or any directory
+    /// above it is ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let path = match &self.root {
+            Some(root) => path.strip_prefix(root).unwrap_or(path),
+            None => path,
+        };
+        // Outside the root, or with no root: match from the filesystem root
+        let path = path.strip_prefix("/").unwrap_or(path);
+        if path.as_os_str().is_empty() {
+            return false;
+        }
+        self.rules
+            .matched_path_or_any_parents(path, is_dir)
+            .is_ignore()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(policy: &IgnorePolicy, path: &str) -> bool {
+        policy.is_ignored(Path::new(path), false)
+    }
+
+    #[test]
+    fn defaults_cover_dependencies_and_build_output() {
+        let policy = IgnorePolicy::default();
+        assert!(ignored(&policy, "node_modules/react/index.js"));
+        assert!(ignored(&policy, "crates/app/target/debug/app"));
+        assert!(ignored(&policy, ".git/HEAD"));
+        assert!(ignored(&policy, "pkg/foo.egg-info/PKG-INFO"));
+        assert!(ignored(&policy, "src/mod.cpython-312.pyc"));
+        assert!(ignored(&policy, "/home/user/proj/dist/app.js"));
+        assert!(!ignored(&policy, "src/main.rs"));
+        // Dotfiles aren't ignored wholesale
+        assert!(!ignored(&policy, ".github/workflows/ci.yml"));
+        assert!(!ignored(&policy, ".env.example"));
+    }
+
+    #[test]
+    fn gitignore_and_eisenignore_apply_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "*.log\ngenerated/\n").unwrap();
+        std::fs::write(dir.path().join(EISENIGNORE_FILE), "!keep.log\nfixtures\n").unwrap();
+        let policy = IgnorePolicy::load(Some(dir.path()), &[]);
+
+        assert!(ignored(&policy, "server.log"));
+        assert!(ignored(&policy, "generated/api.rs"));
+        assert!(!ignored(&policy, "keep.log"));
+        assert!(ignored(&policy, "tests/fixtures/a.json"));
+        // Absolute paths under the root match the same way
+        assert!(ignored(
+            &policy,
+            &dir.path().join("generated/api.rs").to_string_lossy()
+        ));
+        assert!(!ignored(
+            &policy,
+            &dir.path().join("src/lib.rs").to_string_lossy()
+        ));
+    }
+
+    #[test]
+    fn git_info_exclude_is_read() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        std::fs::write(dir.path().join(".git/info/exclude"), "scratch/\n").unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "!scratch/keep.rs\n").unwrap();
+        let policy = IgnorePolicy::load(Some(dir.path()), &[]);
+
+        assert!(ignored(&policy, "scratch/notes.rs"));
+        assert!(!ignored(&policy, "src/lib.rs"));
+    }
+
+    #[test]
+    fn override_patterns_win() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "secrets/\n").unwrap();
+        let patterns = vec!["!dist".to_string(), "*.snap".to_string()];
+        let policy = IgnorePolicy::load(Some(dir.path()), &patterns);
+
+        assert!(!ignored(&policy, "dist/app.js"));
+        assert!(ignored(&policy, "tests/__snapshots__/a.snap"));
+        assert!(ignored(&policy, "secrets/key.pem"));
+    }
+
+    #[test]
+    fn directory_only_patterns_need_is_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "logs/\n").unwrap();
+        let policy = IgnorePolicy::load(Some(dir.path()), &[]);
+
+        assert!(policy.is_ignored(Path::new("logs"), true));
+        assert!(!policy.is_ignored(Path::new("logs"), false));
+        assert!(policy.is_ignored(Path::new("logs/today.txt"), false));
+    }
+}
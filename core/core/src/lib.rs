@@ -1,9 +1,21 @@
+pub mod checkpoint;
+pub mod clock;
+pub mod codeowners;
 pub mod extract;
+pub mod file_reads;
 pub mod flatten;
+pub mod ignore_policy;
 pub mod orchestrator;
 pub mod parser;
+pub mod paths;
+pub mod pricing;
 pub mod proxy;
 pub mod session_registry;
+pub mod session_store;
+pub mod symbols;
 pub mod tcp;
 pub mod tracker;
 pub mod types;
+pub mod watcher;
+pub mod zone_file;
+pub mod zone_registry;
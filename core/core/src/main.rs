@@ -1,8 +1,7 @@
 //! eisen-core binary
 //!
-//! Usage:
-//!   eisen-core snapshot [--root PATH]
-//!   eisen-core observe [--port N] [--agent-id ID] [--session-id ID] -- <agent-command> [agent-args...]
+//! See `USAGE` below for the commands and flags; it is printed whenever the
+//! command line can't be parsed.
 //!
 //! Runs as a transparent ACP proxy between the editor (stdin/stdout) and the
 //! agent process. Simultaneously extracts context from ACP messages to feed
@@ -10,22 +9,48 @@
 
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use tokio::net::TcpListener;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, mpsc, Mutex};
 use tracing_subscriber::EnvFilter;
 
 use tracing::debug;
 
-use eisen_core::flatten::flatten;
+use eisen_core::checkpoint::{self, CheckpointStore};
+use eisen_core::clock;
+use eisen_core::codeowners::{self, CodeOwners};
+use eisen_core::file_reads;
+use eisen_core::flatten::flatten_workspace;
 use eisen_core::orchestrator::OrchestratorAggregator;
 use eisen_core::parser::tree::SymbolTree;
+use eisen_core::paths::{NamedRoot, PathResolver};
+use eisen_core::pricing::PriceTable;
 use eisen_core::proxy;
-use eisen_core::session_registry::SessionRegistry;
+use eisen_core::session_registry::{self, SessionRegistry};
+use eisen_core::symbols::SymbolIndex;
 use eisen_core::tcp::{self, WireLine};
 use eisen_core::tracker::ContextTracker;
-use eisen_core::types::{TrackerConfig, ZoneConfig};
+use eisen_core::types::{ToolCallPolicy, TrackerConfig, ZoneConfig, ZoneMode};
+use eisen_core::watcher::{self, FileWatcher};
+use eisen_core::zone_file::{self, ProfileOverrides, ZoneFile};
+use eisen_core::zone_registry::ZoneRegistry;
+
+/// The one usage string, for every command.
+const USAGE: &str = "\
+Usage:
+  eisen-core snapshot [--root PATH] [--root NAME=PATH]...
+  eisen-core observe [--port N] [--agent-id ID] [--session-id ID] [--cwd PATH]
+      [--zone PATTERN]... [--deny PATTERN]... [--zone-mode enforce|warn|off]
+      [--zone-case-insensitive] [--zone-resolve-symlinks] [--zones-file PATH]
+      [--tool-calls deny|report] [--redact-search] [--no-checkpoints]
+      [--symbols] [--watch] [--ignore PATTERN]... [--root NAME=PATH]...
+      [--pricing PATH] -- <agent-command> [agent-args...]
+  eisen-core zones check <path> [--root PATH] [--file PATH] [--agent-id ID | --profile NAME]...
+  eisen-core zones suggest [--root PATH] [--codeowners PATH] [--output PATH]
+
+--symbols needs --cwd; --watch watches the current directory without it.";
 
 /// Parsed CLI arguments.
 struct Args {
@@ -35,26 +60,55 @@ struct Args {
     cwd: Option<PathBuf>,
     zone_patterns: Vec<String>,
     deny_patterns: Vec<String>,
+    zone_overrides: ProfileOverrides,
+    zone_resolve_symlinks: bool,
+    zones_file: Option<PathBuf>,
+    checkpoints: bool,
+    symbols: bool,
+    watch: bool,
+    ignore_patterns: Vec<String>,
+    roots: Vec<NamedRoot>,
+    pricing: Option<PathBuf>,
     agent_command: String,
     agent_args: Vec<String>,
 }
 
 enum Command {
     Observe(Args),
-    Snapshot { root_path: PathBuf },
+    Snapshot {
+        root_path: PathBuf,
+        roots: Vec<NamedRoot>,
+    },
+    ZonesCheck(ZonesCheckArgs),
+    ZonesSuggest(ZonesSuggestArgs),
+}
+
+/// Arguments for `zones check`.
+struct ZonesCheckArgs {
+    path: String,
+    root: PathBuf,
+    file: Option<PathBuf>,
+    agent_id: Option<String>,
+    profiles: Vec<String>,
+}
+
+/// Arguments for `zones suggest`.
+struct ZonesSuggestArgs {
+    root: PathBuf,
+    codeowners: Option<PathBuf>,
+    output: Option<PathBuf>,
 }
 
 fn parse_command() -> Result<Command> {
     let raw: Vec<String> = std::env::args().skip(1).collect();
     if raw.is_empty() {
-        bail!(
-            "Usage: eisen-core snapshot [--root PATH] | eisen-core observe [--port N] [--agent-id ID] [--session-id ID] -- <command> [args...]"
-        );
+        bail!(USAGE);
     }
 
     match raw[0].as_str() {
         "snapshot" => {
             let mut root_path: Option<PathBuf> = None;
+            let mut roots: Vec<NamedRoot> = Vec::new();
             let mut i = 1;
             while i < raw.len() {
                 match raw[i].as_str() {
@@ -63,7 +117,13 @@ fn parse_command() -> Result<Command> {
                         let Some(root) = raw.get(i) else {
                             bail!("Missing value after --root");
                         };
-                        root_path = Some(PathBuf::from(root));
+                        // NAME=PATH adds a named root, a bare path sets the
+                        // primary one
+                        if root.contains('=') {
+                            roots.push(NamedRoot::parse(root)?);
+                        } else {
+                            root_path = Some(PathBuf::from(root));
+                        }
                     }
                     other => bail!("Unknown flag for snapshot: {other}"),
                 }
@@ -72,17 +132,23 @@ fn parse_command() -> Result<Command> {
 
             Ok(Command::Snapshot {
                 root_path: root_path.unwrap_or(std::env::current_dir()?),
+                roots,
             })
         }
         "observe" => parse_observe_args(&raw).map(Command::Observe),
-        other => bail!("Unknown command: {other}"),
+        "zones" => match raw.get(1).map(|s| s.as_str()) {
+            Some("check") => parse_zones_check_args(&raw).map(Command::ZonesCheck),
+            Some("suggest") => parse_zones_suggest_args(&raw).map(Command::ZonesSuggest),
+            _ => bail!(USAGE),
+        },
+        other => bail!("Unknown command: {other}\n\n{USAGE}"),
     }
 }
 
 fn parse_observe_args(raw: &[String]) -> Result<Args> {
     // Find the "observe" subcommand
     if raw.is_empty() || raw[0] != "observe" {
-        bail!("Usage: eisen-core observe [--port N] [--agent-id ID] [--session-id ID] [--zone PATTERN]... [--deny PATTERN]... -- <command> [args...]");
+        bail!(USAGE);
     }
 
     let mut port: u16 = tcp::DEFAULT_PORT;
@@ -91,6 +157,15 @@ fn parse_observe_args(raw: &[String]) -> Result<Args> {
     let mut cwd: Option<PathBuf> = None;
     let mut zone_patterns: Vec<String> = Vec::new();
     let mut deny_patterns: Vec<String> = Vec::new();
+    let mut zone_overrides = ProfileOverrides::default();
+    let mut zone_resolve_symlinks = false;
+    let mut zones_file: Option<PathBuf> = None;
+    let mut checkpoints = true;
+    let mut symbols = false;
+    let mut watch = false;
+    let mut ignore_patterns: Vec<String> = Vec::new();
+    let mut roots: Vec<NamedRoot> = Vec::new();
+    let mut pricing: Option<PathBuf> = None;
     let mut i = 1; // skip "observe"
 
     // Parse flags before "--"
@@ -128,6 +203,75 @@ fn parse_observe_args(raw: &[String]) -> Result<Args> {
                     bail!("Missing value after --deny");
                 }
             }
+            "--zone-mode" => {
+                i += 1;
+                zone_overrides.mode = match raw.get(i).map(|s| s.as_str()) {
+                    Some("enforce") => Some(ZoneMode::Enforce),
+                    Some("warn") => Some(ZoneMode::Warn),
+                    Some("off") => Some(ZoneMode::Off),
+                    Some(other) => {
+                        bail!("Invalid --zone-mode value: {other} (expected enforce, warn or off)")
+                    }
+                    None => bail!("Missing value after --zone-mode"),
+                };
+            }
+            "--zone-case-insensitive" => {
+                zone_overrides.case_insensitive = true;
+            }
+            "--zone-resolve-symlinks" => {
+                zone_resolve_symlinks = true;
+            }
+            "--zones-file" => {
+                i += 1;
+                let Some(path) = raw.get(i) else {
+                    bail!("Missing value after --zones-file");
+                };
+                zones_file = Some(PathBuf::from(path));
+            }
+            "--tool-calls" => {
+                i += 1;
+                zone_overrides.tool_calls = match raw.get(i).map(|s| s.as_str()) {
+                    Some("deny") => Some(ToolCallPolicy::Deny),
+                    Some("report") => Some(ToolCallPolicy::Report),
+                    Some(other) => {
+                        bail!("Invalid --tool-calls value: {other} (expected deny or report)")
+                    }
+                    None => bail!("Missing value after --tool-calls"),
+                };
+            }
+            "--redact-search" => {
+                zone_overrides.redact_search_results = true;
+            }
+            "--no-checkpoints" => {
+                checkpoints = false;
+            }
+            "--symbols" => {
+                symbols = true;
+            }
+            "--watch" => {
+                watch = true;
+            }
+            "--ignore" => {
+                i += 1;
+                let Some(pattern) = raw.get(i) else {
+                    bail!("Missing value after --ignore");
+                };
+                ignore_patterns.push(pattern.clone());
+            }
+            "--root" => {
+                i += 1;
+                let Some(root) = raw.get(i) else {
+                    bail!("Missing value after --root");
+                };
+                roots.push(NamedRoot::parse(root)?);
+            }
+            "--pricing" => {
+                i += 1;
+                let Some(path) = raw.get(i) else {
+                    bail!("Missing value after --pricing");
+                };
+                pricing = Some(PathBuf::from(path));
+            }
             other => bail!("Unknown flag: {other}"),
         }
         i += 1;
@@ -141,6 +285,10 @@ fn parse_observe_args(raw: &[String]) -> Result<Args> {
     if i >= raw.len() {
         bail!("Missing agent command after '--'");
     }
+    // Symbol IDs are relative to the workspace root, so it must be explicit
+    if symbols && cwd.is_none() {
+        bail!("--symbols requires --cwd\n\n{USAGE}");
+    }
 
     let agent_command = raw[i].clone();
     let agent_args = raw[i + 1..].to_vec();
@@ -152,11 +300,163 @@ fn parse_observe_args(raw: &[String]) -> Result<Args> {
         cwd,
         zone_patterns,
         deny_patterns,
+        zone_overrides,
+        zone_resolve_symlinks,
+        zones_file,
+        checkpoints,
+        symbols,
+        watch,
+        ignore_patterns,
+        roots,
+        pricing,
         agent_command,
         agent_args,
     })
 }
 
+fn parse_zones_check_args(raw: &[String]) -> Result<ZonesCheckArgs> {
+    let mut path: Option<String> = None;
+    let mut root: Option<PathBuf> = None;
+    let mut file: Option<PathBuf> = None;
+    let mut agent_id: Option<String> = None;
+    let mut profiles: Vec<String> = Vec::new();
+    let mut i = 2; // skip "zones check"
+    while i < raw.len() {
+        match raw[i].as_str() {
+            "--root" => {
+                i += 1;
+                let Some(value) = raw.get(i) else {
+                    bail!("Missing value after --root");
+                };
+                root = Some(PathBuf::from(value));
+            }
+            "--file" => {
+                i += 1;
+                let Some(value) = raw.get(i) else {
+                    bail!("Missing value after --file");
+                };
+                file = Some(PathBuf::from(value));
+            }
+            "--agent-id" => {
+                i += 1;
+                let Some(value) = raw.get(i) else {
+                    bail!("Missing value after --agent-id");
+                };
+                agent_id = Some(value.clone());
+            }
+            "--profile" => {
+                i += 1;
+                let Some(value) = raw.get(i) else {
+                    bail!("Missing value after --profile");
+                };
+                profiles.push(value.clone());
+            }
+            other if other.starts_with("--") => bail!("Unknown flag for zones check: {other}"),
+            other => {
+                if path.replace(other.to_string()).is_some() {
+                    bail!(USAGE);
+                }
+            }
+        }
+        i += 1;
+    }
+
+    let Some(path) = path else {
+        bail!(USAGE);
+    };
+    Ok(ZonesCheckArgs {
+        path,
+        root: root.unwrap_or(std::env::current_dir()?),
+        file,
+        agent_id,
+        profiles,
+    })
+}
+
+fn parse_zones_suggest_args(raw: &[String]) -> Result<ZonesSuggestArgs> {
+    let mut root: Option<PathBuf> = None;
+    let mut codeowners: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut i = 2; // skip "zones suggest"
+    while i < raw.len() {
+        let flag = raw[i].as_str();
+        let target = match flag {
+            "--root" => &mut root,
+            "--codeowners" => &mut codeowners,
+            "--output" => &mut output,
+            other => bail!("Unknown flag for zones suggest: {other}"),
+        };
+        i += 1;
+        let Some(value) = raw.get(i) else {
+            bail!("Missing value after {flag}");
+        };
+        *target = Some(PathBuf::from(value));
+        i += 1;
+    }
+
+    Ok(ZonesSuggestArgs {
+        root: root.unwrap_or(std::env::current_dir()?),
+        codeowners,
+        output,
+    })
+}
+
+/// `zones suggest`: propose zone profiles from CODEOWNERS and write them
+/// as a zone file (stdout unless --output is given).
+fn zones_suggest(args: ZonesSuggestArgs) -> Result<()> {
+    let Some(path) = args.codeowners.or_else(|| CodeOwners::find(&args.root)) else {
+        bail!("no CODEOWNERS file found under {}", args.root.display());
+    };
+    let codeowners = CodeOwners::load(&path)?;
+    let tree = SymbolTree::init_tree(&args.root)?;
+    let suggestions = codeowners::suggest(&codeowners, &tree, &args.root);
+
+    let source = path
+        .strip_prefix(&args.root)
+        .unwrap_or(&path)
+        .display()
+        .to_string();
+    let toml = suggestions.to_toml(&source);
+    match args.output {
+        Some(output) => {
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&output, toml)
+                .with_context(|| format!("failed to write {}", output.display()))?;
+            eprintln!(
+                "wrote {} zone profiles to {}",
+                suggestions.zones.len(),
+                output.display()
+            );
+        }
+        None => print!("{toml}"),
+    }
+    Ok(())
+}
+
+/// `zones check`: print which profiles allow or deny a path, and why.
+fn zones_check(args: ZonesCheckArgs) -> Result<()> {
+    let path = args
+        .file
+        .unwrap_or_else(|| zone_file::default_path(&args.root));
+    let file = ZoneFile::load(&path)?;
+
+    let mut profiles = args.profiles;
+    if let Some(agent_id) = &args.agent_id {
+        match file.profile_name_for(agent_id) {
+            Some(name) => profiles.push(name.to_string()),
+            None => bail!("no profile assigned to agent {agent_id:?} and no default profile"),
+        }
+    }
+
+    let resolver = PathResolver::new(Some(args.root));
+    for check in file.check(&args.path, &resolver, &profiles)? {
+        println!("{check}");
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing (respects RUST_LOG env var)
@@ -166,36 +466,149 @@ async fn main() -> Result<()> {
         .init();
 
     match parse_command()? {
-        Command::Snapshot { root_path } => {
+        Command::Snapshot { root_path, roots } => {
             let tree = SymbolTree::init_tree(&root_path)?;
-            let snapshot = flatten(&tree, &root_path, 0);
+            let mut named = Vec::new();
+            for root in roots {
+                let tree = SymbolTree::init_tree(&root.path)?;
+                named.push((root, tree));
+            }
+            let snapshot = flatten_workspace(&tree, &root_path, &named, 0);
             println!("{}", serde_json::to_string(&snapshot)?);
             return Ok(());
         }
+        Command::ZonesCheck(args) => zones_check(args),
+        Command::ZonesSuggest(args) => zones_suggest(args),
         Command::Observe(args) => {
+            // One clock for everything that stamps or ages state
+            let clock = clock::system();
+
             // Create the context tracker
-            let mut tracker = ContextTracker::new(TrackerConfig::default());
+            let mut tracker = ContextTracker::new(TrackerConfig {
+                ignore: args.ignore_patterns.clone(),
+                ..TrackerConfig::default()
+            });
+            tracker.set_clock(clock.clone());
+            // Prices for estimating cost when the agent reports only tokens
+            tracker.set_price_table(match &args.pricing {
+                Some(path) => PriceTable::load(path)?,
+                None => PriceTable::load_default(),
+            });
             if let Some(aid) = &args.agent_id {
                 tracker.set_agent_id(aid.clone());
             }
+            // Checkpoint session state so a restarted proxy can pick up a
+            // resumed session where it left off
+            if args.checkpoints {
+                let store = CheckpointStore::open_default();
+                match store.prune(SystemTime::now()) {
+                    Ok(0) => {}
+                    Ok(n) => debug!(removed = n, "pruned expired tracker checkpoints"),
+                    Err(e) => debug!(error = %e, "failed to prune tracker checkpoints"),
+                }
+                tracker.set_checkpoint_store(store);
+            }
             if let Some(sid) = &args.session_id {
                 tracker.set_session_id(sid.clone());
             }
-            if let Some(root) = args.cwd {
-                tracker.set_workspace_root(root);
+            if let Some(root) = &args.cwd {
+                tracker.set_workspace_root(root.clone());
+            }
+            for root in &args.roots {
+                tracker.add_workspace_root(root.clone());
+            }
+            // Attribute line-level accesses to symbols. IDs are relative to
+            // the root the tree was parsed from, which is why --symbols
+            // requires --cwd. Files are re-parsed as they change.
+            if let Some(root) = args.cwd.as_ref().filter(|_| args.symbols) {
+                let tree = SymbolTree::init_tree_with(root, tracker.ignore_policy())?;
+                let mut named = Vec::new();
+                for named_root in &args.roots {
+                    let policy = tracker.root_ignore_policy(&named_root.name);
+                    let tree = SymbolTree::init_tree_with(&named_root.path, policy)?;
+                    named.push((named_root.clone(), tree));
+                }
+                let index = SymbolIndex::build_workspace(&tree, root, &named);
+                debug!(symbols = index.len(), "built symbol index");
+                tracker.set_symbol_index(index);
             }
             let tracker = Arc::new(Mutex::new(tracker));
 
-            // Build zone config if --zone flags were provided
+            // Build the default zone from --zone flags, or else from the
+            // zone file; the other zone flags apply either way. Sessions can
+            // override it at runtime via the set_zone RPC.
+            let zones_path = args.zones_file.clone().or_else(|| {
+                let root = args.cwd.clone().or_else(|| std::env::current_dir().ok())?;
+                Some(zone_file::default_path(&root)).filter(|p| p.exists())
+            });
+            let mut watch_zones_file = None;
             let zone_config = if !args.zone_patterns.is_empty() {
                 let mut config = ZoneConfig::new(args.zone_patterns);
                 config.denied = args.deny_patterns;
+                let config = args.zone_overrides.apply(config);
                 debug!(
                     allowed = ?config.allowed,
                     denied = ?config.denied,
+                    mode = ?config.mode,
+                    tool_calls = ?config.tool_calls,
                     "zone enforcement enabled"
                 );
-                Some(Arc::new(config))
+                Some(config.compile().context("invalid --zone/--deny pattern")?)
+            } else if let Some(path) = zones_path {
+                let agent_id = args.agent_id.as_deref().unwrap_or_default();
+                let zone = ZoneFile::load(&path)?.zone_for_agent(agent_id, &args.zone_overrides)?;
+                debug!(
+                    path = %path.display(),
+                    allowed = ?zone.as_ref().map(|z| &z.allowed),
+                    "zone loaded from zone file"
+                );
+                watch_zones_file = Some(path);
+                zone
+            } else {
+                None
+            };
+            // Zone checks resolve paths against --cwd, like the tracker does
+            let mut zones = ZoneRegistry::new(zone_config);
+            zones.set_clock(clock.clone());
+            zones.set_path_resolver(
+                PathResolver::new(args.cwd.clone())
+                    .with_roots(args.roots.clone())
+                    .with_symlinks(args.zone_resolve_symlinks),
+            );
+            let zones = Arc::new(Mutex::new(zones));
+
+            // Hot-reload the zone file
+            let zone_watcher = watch_zones_file.map(|path| {
+                tokio::spawn(zone_file::watch(
+                    path,
+                    tracker.clone(),
+                    zones.clone(),
+                    args.zone_overrides.clone(),
+                ))
+            });
+
+            // Catch writes the agent makes outside ACP (its own shell,
+            // codegen) while a turn is in flight
+            let file_watcher = if args.watch {
+                let root = match &args.cwd {
+                    Some(root) => root.clone(),
+                    None => std::env::current_dir()?,
+                };
+                let (tx, rx) = mpsc::unbounded_channel();
+                let t = tracker.lock().await;
+                let mut watchers = vec![FileWatcher::start(
+                    &root,
+                    t.ignore_policy().clone(),
+                    tx.clone(),
+                )?];
+                debug!(root = %root.display(), "watching workspace for out-of-band edits");
+                for named in &args.roots {
+                    let ignore = t.root_ignore_policy(&named.name).clone();
+                    watchers.push(FileWatcher::start(&named.path, ignore, tx.clone())?);
+                    debug!(root = %named.path.display(), name = %named.name, "watching named root");
+                }
+                drop(t);
+                Some((watchers, tokio::spawn(watcher::run(tracker.clone(), rx))))
             } else {
                 None
             };
@@ -210,7 +623,14 @@ async fn main() -> Result<()> {
             let (delta_tx, _) = broadcast::channel::<WireLine>(256);
 
             // Create session registry
-            let registry = Arc::new(Mutex::new(SessionRegistry::load_default()));
+            let mut registry = SessionRegistry::load_default()?;
+            registry.set_clock(clock.clone());
+            let registry = Arc::new(Mutex::new(registry));
+            // Pick up sessions written by other eisen-core processes
+            let registry_watcher = tokio::spawn(session_registry::watch(registry.clone()));
+            let checkpointer = tokio::spawn(checkpoint::run(tracker.clone()));
+            // Token estimates and symbol re-parses run off the tracker lock
+            let file_reader = tokio::spawn(file_reads::run(tracker.clone()));
             let orchestrator = Arc::new(Mutex::new(OrchestratorAggregator::new()));
 
             // Spawn TCP server
@@ -218,6 +638,7 @@ async fn main() -> Result<()> {
             let tcp_delta_tx = delta_tx.clone();
             let tcp_registry = registry.clone();
             let tcp_orchestrator = orchestrator.clone();
+            let tcp_zones = zones.clone();
             tokio::spawn(async move {
                 if let Err(e) = tcp::serve(
                     listener,
@@ -225,6 +646,7 @@ async fn main() -> Result<()> {
                     tcp_delta_tx,
                     tcp_registry,
                     tcp_orchestrator,
+                    tcp_zones,
                 )
                 .await
                 {
@@ -234,24 +656,35 @@ async fn main() -> Result<()> {
 
             // Spawn the agent process
             let mut child = proxy::spawn_agent(&args.agent_command, &args.agent_args)?;
-            let agent_stdin = child.stdin.take().expect("agent stdin should be piped");
+            let agent_stdin = Arc::new(Mutex::new(
+                child.stdin.take().expect("agent stdin should be piped"),
+            ));
             let agent_stdout = child.stdout.take().expect("agent stdout should be piped");
 
             // Spawn upstream proxy (editor stdin -> agent stdin)
             let up_tracker = tracker.clone();
+            let up_stdin = agent_stdin.clone();
+            let up_zones = zones.clone();
             let upstream = tokio::spawn(async move {
-                if let Err(e) = proxy::upstream_task(up_tracker, agent_stdin).await {
+                if let Err(e) = proxy::upstream_task(up_tracker, up_stdin, up_zones).await {
                     eprintln!("eisen-core upstream error: {e}");
                 }
             });
 
             // Spawn downstream proxy (agent stdout -> editor stdout)
             let down_tracker = tracker.clone();
-            let down_zone = zone_config.clone();
+            let down_stdin = agent_stdin.clone();
+            let down_zones = zones.clone();
             let down_tx = delta_tx.clone();
             let downstream = tokio::spawn(async move {
-                if let Err(e) =
-                    proxy::downstream_task(down_tracker, agent_stdout, down_zone, down_tx).await
+                if let Err(e) = proxy::downstream_task(
+                    down_tracker,
+                    agent_stdout,
+                    down_stdin,
+                    down_zones,
+                    down_tx,
+                )
+                .await
                 {
                     eprintln!("eisen-core downstream error: {e}");
                 }
@@ -265,6 +698,7 @@ async fn main() -> Result<()> {
             let tick_tx = delta_tx.clone();
             let tick_registry = registry.clone();
             let tick_orchestrator = orchestrator.clone();
+            let tick_zones = zones.clone();
             let tick_loop = tokio::spawn(async move {
                 const ACTIVE_INTERVAL_MS: u64 = 100;
                 const IDLE_INTERVAL_MS: u64 = 500;
@@ -301,6 +735,26 @@ async fn main() -> Result<()> {
                         tcp::broadcast_line(&tick_tx, &usage);
                     }
 
+                    // Mark sessions reattached via session/load or session/resume
+                    let resumed = t.take_pending_resumed();
+                    if !resumed.is_empty() {
+                        let mut registry = tick_registry.lock().await;
+                        for key in &resumed {
+                            if let Err(e) = registry.mark_resumed(key) {
+                                debug!(error = %e, session_id = key.session_id.as_str(), "failed to mark session resumed");
+                            }
+                        }
+                    }
+
+                    // Broadcast zone changes made over RPC
+                    let zone_changes = tick_zones.lock().await.take_pending_changes();
+                    if !zone_changes.is_empty() {
+                        had_activity = true;
+                    }
+                    for change in &zone_changes {
+                        tcp::broadcast_line(&tick_tx, change);
+                    }
+
                     // Broadcast delta if anything changed
                     let deltas = t.tick_all();
                     if !deltas.is_empty() {
@@ -367,6 +821,18 @@ async fn main() -> Result<()> {
 
             // Clean up
             tick_loop.abort();
+            registry_watcher.abort();
+            checkpointer.abort();
+            file_reader.abort();
+            if let Some(watcher) = zone_watcher {
+                watcher.abort();
+            }
+            if let Some((_, task)) = file_watcher {
+                task.abort();
+            }
+            if let Err(e) = tracker.lock().await.save_checkpoints() {
+                eprintln!("eisen-core checkpoint error: {e:#}");
+            }
             let _ = child.kill().await;
 
             Ok(())
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::pricing::PriceTable;
 use crate::session_registry::SessionRegistry;
 use crate::tracker::ContextTracker;
 use crate::types::{
@@ -117,6 +118,7 @@ impl OrchestratorAggregator {
                     &session.session_id,
                     &session.providers,
                     &state.provider_usage,
+                    tracker.price_table(),
                 ) {
                     outputs.push(usage_msg);
                 }
@@ -156,12 +158,18 @@ fn merge_node(target: &mut HashMap<String, FileNode>, node: &FileNode) {
             existing.heat = existing.heat.max(node.heat);
             existing.in_context = existing.in_context || node.in_context;
             existing.turn_accessed = existing.turn_accessed.max(node.turn_accessed);
+            existing.estimated_tokens = existing.estimated_tokens.max(node.estimated_tokens);
+            for range in &node.line_ranges {
+                existing.add_line_range(*range);
+            }
+            existing.confidence = existing.confidence.max(node.confidence);
 
             let should_replace = node.timestamp_ms > existing.timestamp_ms
                 || (node.timestamp_ms == existing.timestamp_ms
                     && action_priority(node.last_action) > action_priority(existing.last_action));
             if should_replace {
                 existing.last_action = node.last_action;
+                existing.source = node.source;
                 existing.timestamp_ms = node.timestamp_ms;
             }
         }
@@ -170,7 +178,7 @@ fn merge_node(target: &mut HashMap<String, FileNode>, node: &FileNode) {
 
 fn action_priority(action: Action) -> u8 {
     match action {
-        Action::Write => 3,
+        Action::Write | Action::Move => 3,
         Action::Search => 2,
         _ => 1,
     }
@@ -197,6 +205,10 @@ fn nodes_equal(a: &FileNode, b: &FileNode) -> bool {
         && a.last_action == b.last_action
         && a.turn_accessed == b.turn_accessed
         && a.timestamp_ms == b.timestamp_ms
+        && a.estimated_tokens == b.estimated_tokens
+        && a.line_ranges == b.line_ranges
+        && a.source == b.source
+        && a.confidence == b.confidence
 }
 
 fn diff_nodes(
@@ -231,6 +243,7 @@ fn aggregate_usage_for_session(
     session_id: &str,
     providers: &[SessionKey],
     provider_usage: &HashMap<SessionKey, UsageMessage>,
+    prices: &PriceTable,
 ) -> Option<UsageMessage> {
     if providers.is_empty() {
         return None;
@@ -249,20 +262,16 @@ fn aggregate_usage_for_session(
 
         match (&cost_total, &usage.cost) {
             (None, Some(cost)) => {
-                cost_total = Some(Cost {
-                    amount: cost.amount,
-                    currency: cost.currency.clone(),
-                });
+                cost_total = Some(cost.clone());
             }
             (Some(existing), Some(cost)) => {
-                if existing.currency == cost.currency {
-                    cost_total = Some(Cost {
-                        amount: existing.amount + cost.amount,
-                        currency: existing.currency.clone(),
-                    });
-                } else {
-                    cost_total = None;
-                }
+                // Other currencies are converted with the price table's
+                // rates; without a rate the total is unknown
+                cost_total = prices.convert(cost, &existing.currency).map(|cost| Cost {
+                    amount: existing.amount + cost.amount,
+                    currency: existing.currency.clone(),
+                    estimated: existing.estimated || cost.estimated,
+                });
             }
             (_, None) => {
                 cost_total = None;
@@ -279,3 +288,71 @@ fn aggregate_usage_for_session(
         cost_total,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::types::{Source, TrackerConfig};
+
+    #[test]
+    fn merge_keeps_the_latest_action() {
+        let clock = ManualClock::new(1_000);
+        let mut tracker = ContextTracker::new(TrackerConfig::default());
+        tracker.set_clock(clock.shared());
+        tracker.set_agent_id("agent-a".to_string());
+
+        tracker.file_access_for_session("s1", "/a.rs", Action::Write, Source::Api);
+        clock.advance(Duration::from_millis(5));
+        tracker.file_access_for_session("s2", "/a.rs", Action::Read, Source::Api);
+        // Same timestamp: the stronger action wins
+        tracker.file_access_for_session("s1", "/b.rs", Action::Read, Source::Api);
+        tracker.file_access_for_session("s2", "/b.rs", Action::Search, Source::Api);
+
+        let providers = vec![
+            SessionKey::new("agent-a", "s1"),
+            SessionKey::new("agent-a", "s2"),
+        ];
+        let nodes = compute_aggregate_nodes(&providers, &tracker);
+        assert_eq!(nodes["/a.rs"].last_action, Action::Read);
+        assert_eq!(nodes["/a.rs"].timestamp_ms, 1_005);
+        assert_eq!(nodes["/b.rs"].last_action, Action::Search);
+    }
+
+    #[test]
+    fn provider_costs_in_other_currencies_are_converted() {
+        let usage = |session: &str, amount: f64, currency: &str, estimated: bool| {
+            let cost = Cost {
+                amount,
+                currency: currency.to_string(),
+                estimated,
+            };
+            let mode = SessionMode::SingleAgent;
+            UsageMessage::new("agent-a", session, mode, 100, 1_000, Some(cost))
+        };
+        let providers = vec![
+            SessionKey::new("agent-a", "s1"),
+            SessionKey::new("agent-a", "s2"),
+        ];
+        let provider_usage: HashMap<SessionKey, UsageMessage> = HashMap::from([
+            (providers[0].clone(), usage("s1", 1.0, "USD", false)),
+            (providers[1].clone(), usage("s2", 2.0, "EUR", true)),
+        ]);
+
+        // Without a rate the total is unknown
+        let mut prices = PriceTable::default();
+        let total = aggregate_usage_for_session("o", "orch", &providers, &provider_usage, &prices);
+        assert!(total.unwrap().cost.is_none());
+
+        prices.rates.insert("EUR".to_string(), 1.1);
+        let total =
+            aggregate_usage_for_session("o", "orch", &providers, &provider_usage, &prices).unwrap();
+        assert_eq!((total.used, total.size), (200, 2_000));
+        let cost = total.cost.unwrap();
+        assert!((cost.amount - 3.2).abs() < 1e-9);
+        assert_eq!(cost.currency, "USD");
+        assert!(cost.estimated);
+    }
+}
@@ -1,5 +1,7 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
+    use crate::ignore_policy::IgnorePolicy;
     use crate::parser::languages::python::PythonParser;
     use crate::parser::languages::LanguageParser;
     use crate::parser::tree::SymbolTree;
@@ -380,6 +382,60 @@ class MyClass:
         assert!(tree.find_by_path(&main_path).is_some());
     }
 
+    #[test]
+    fn test_directory_walker_uses_ignore_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir_all(root.join(".github/workflows")).unwrap();
+        fs::create_dir_all(root.join("generated")).unwrap();
+        fs::create_dir_all(root.join("dist")).unwrap();
+        fs::write(root.join(".github/workflows/ci.yml"), "on: push").unwrap();
+        fs::write(root.join("generated/api.py"), "def api(): pass").unwrap();
+        fs::write(root.join("dist/app.js"), "").unwrap();
+        fs::write(root.join("main.py"), "def main(): pass").unwrap();
+        fs::write(root.join(".gitignore"), "generated/\n").unwrap();
+        fs::write(root.join(".eisenignore"), "!dist\n").unwrap();
+
+        let policy = IgnorePolicy::load(Some(root), &["main.py".to_string()]);
+        let tree = SymbolTree::init_tree_with(root, &policy).unwrap();
+        let has = |rel: &str| {
+            tree.find_by_path(&root.join(rel).to_string_lossy())
+                .is_some()
+        };
+
+        assert!(has(".github/workflows/ci.yml"));
+        assert!(has("dist/app.js"));
+        assert!(!has("generated"));
+        assert!(!has("generated/api.py"));
+        assert!(!has("main.py"));
+    }
+
+    #[test]
+    fn test_directory_walker_honors_nested_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::create_dir_all(root.join("pkg/fixtures")).unwrap();
+        fs::create_dir_all(root.join("pkg/snapshots")).unwrap();
+        fs::write(root.join("pkg/fixtures/data.py"), "").unwrap();
+        fs::write(root.join("pkg/snapshots/a.py"), "").unwrap();
+        fs::write(root.join("pkg/lib.py"), "def lib(): pass").unwrap();
+        fs::write(root.join("pkg/.gitignore"), "fixtures/\nsnapshots/\n").unwrap();
+        // .eisenignore re-includes win over .gitignore
+        fs::write(root.join(".eisenignore"), "!snapshots\n").unwrap();
+
+        let tree = SymbolTree::init_tree(root).unwrap();
+        let has = |rel: &str| {
+            tree.find_by_path(&root.join(rel).to_string_lossy())
+                .is_some()
+        };
+
+        assert!(has("pkg/lib.py"));
+        assert!(!has("pkg/fixtures/data.py"));
+        assert!(has("pkg/snapshots/a.py"));
+    }
+
     #[test]
     fn test_directory_walker_python_parsing() {
         let temp_dir = TempDir::new().unwrap();
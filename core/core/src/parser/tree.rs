@@ -1,6 +1,7 @@
 use indextree::{Arena, NodeId};
 use std::path::Path;
 
+use crate::ignore_policy::IgnorePolicy;
 use crate::parser::types::NodeData;
 use crate::parser::walk::DirectoryWalker;
 
@@ -24,6 +25,24 @@ impl SymbolTree {
         Ok(tree)
     }
 
+    /// Like `init_tree`, skipping the paths `ignore` excludes.
+    pub fn init_tree_with(root_path: &Path, ignore: &IgnorePolicy) -> anyhow::Result<Self> {
+        let mut tree = Self::new();
+        let walker = DirectoryWalker::with_policy(root_path, ignore.clone());
+        walker.walk_and_build(&mut tree)?;
+        Ok(tree)
+    }
+
+    /// A tree with just `path` and its symbols, under a folder node for
+    /// the directory it is in.
+    pub fn init_file(path: &Path) -> anyhow::Result<Self> {
+        let mut tree = Self::new();
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let walker = DirectoryWalker::with_policy(parent, IgnorePolicy::default());
+        walker.build_file(&mut tree, path)?;
+        Ok(tree)
+    }
+
     pub fn add_node(&mut self, parent_id: Option<NodeId>, data: NodeData) -> NodeId {
         let node_id = self.arena.new_node(data);
 
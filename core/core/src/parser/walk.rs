@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tiktoken_rs::CoreBPE;
 
+use crate::ignore_policy::{IgnorePolicy, EISENIGNORE_FILE};
 use crate::parser::languages::{
     python::PythonParser, rust::RustParser, typescript::TypeScriptParser, LanguageParser,
 };
@@ -18,55 +19,51 @@ static BPE: Lazy<CoreBPE> =
     Lazy::new(|| tiktoken_rs::o200k_base().expect("failed to initialise o200k_base tokeniser"));
 
 /// Count the number of tokens in `content` using the o200k_base encoding.
-fn count_tokens(content: &str) -> u32 {
+pub fn count_tokens(content: &str) -> u32 {
     BPE.encode_ordinary(content).len() as u32
 }
 
-/// Directory/file names to skip in addition to .gitignore rules.
-const IGNORED_NAMES: &[&str] = &[
-    ".git",
-    "target",
-    "node_modules",
-    "__pycache__",
-    ".venv",
-    "venv",
-    ".pytest_cache",
-    ".mypy_cache",
-    ".tox",
-    "dist",
-    "build",
-    ".egg-info",
-];
-
 pub struct DirectoryWalker<'a> {
     root_path: &'a Path,
+    ignore: IgnorePolicy,
 }
 
 impl<'a> DirectoryWalker<'a> {
+    /// Walk with the built-in defaults and the workspace's ignore files.
     pub fn new(root_path: &'a Path) -> Self {
-        Self { root_path }
+        Self::with_policy(root_path, IgnorePolicy::load(Some(root_path), &[]))
     }
 
-    pub fn walk_and_build(&self, tree: &mut SymbolTree) -> anyhow::Result<()> {
-        let root_path_str = self.root_path.to_string_lossy().to_string();
-        let root_name = self
-            .root_path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "root".to_string());
+    /// Walk skipping whatever `ignore` excludes, e.g. the tracker's policy
+    /// so the symbol graph matches the live graph. Nested ignore files are
+    /// honored on top of it.
+    pub fn with_policy(root_path: &'a Path, ignore: IgnorePolicy) -> Self {
+        Self { root_path, ignore }
+    }
 
-        let root_data = NodeData::new(0, root_name, NodeKind::Folder, root_path_str);
-        let root_id = tree.add_node(None, root_data);
+    pub fn walk_and_build(&self, tree: &mut SymbolTree) -> anyhow::Result<()> {
+        let root_id = self.add_root(tree);
 
         let mut path_to_node: HashMap<PathBuf, NodeId> = HashMap::new();
         path_to_node.insert(self.root_path.to_path_buf(), root_id);
 
+        // Git's own rules first, so nested ignore files are honored, then
+        // the policy. `.eisenignore` files are read as custom ignore files,
+        // which take precedence over `.gitignore`, so their `!pattern`s
+        // still re-include.
+        let ignore = self.ignore.clone();
         let walker = WalkBuilder::new(self.root_path)
             .hidden(false)
             .git_ignore(true)
             .git_global(true)
             .git_exclude(true)
             .ignore(true)
+            .require_git(false)
+            .add_custom_ignore_filename(EISENIGNORE_FILE)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                !ignore.is_ignored(entry.path(), is_dir)
+            })
             .build();
 
         for entry in walker {
@@ -87,10 +84,6 @@ impl<'a> DirectoryWalker<'a> {
                 continue;
             };
 
-            if self.should_ignore(path, file_type.is_file()) {
-                continue;
-            }
-
             let parent_path = path.parent().unwrap_or(self.root_path);
 
             if let Some(&parent_id) = path_to_node.get(parent_path) {
@@ -116,35 +109,29 @@ impl<'a> DirectoryWalker<'a> {
         Ok(())
     }
 
-    fn should_ignore(&self, path: &Path, is_file: bool) -> bool {
-        let file_name = path
+    /// Parse a single file into `tree`, directly under the root folder,
+    /// without walking the rest of the directory. The ignore policy is not
+    /// consulted.
+    pub fn build_file(&self, tree: &mut SymbolTree, path: &Path) -> anyhow::Result<()> {
+        let root_id = self.add_root(tree);
+        let name = path
             .file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_default();
+        let mut path_to_node = HashMap::new();
+        self.process_file(tree, path, &name, root_id, &mut path_to_node)
+    }
 
-        if file_name.starts_with('.')
-            && file_name != "."
-            && file_name != ".."
-            && IGNORED_NAMES.iter().any(|p| file_name.starts_with(*p))
-        {
-            return true;
-        }
-
-        if IGNORED_NAMES.iter().any(|name| file_name == *name) {
-            return true;
-        }
-
-        if is_file
-            && (file_name.ends_with(".pyc")
-                || file_name.ends_with(".pyo")
-                || file_name.ends_with(".so")
-                || file_name.ends_with(".dylib")
-                || file_name.ends_with(".dll"))
-        {
-            return true;
-        }
+    fn add_root(&self, tree: &mut SymbolTree) -> NodeId {
+        let root_path_str = self.root_path.to_string_lossy().to_string();
+        let root_name = self
+            .root_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "root".to_string());
 
-        false
+        let root_data = NodeData::new(0, root_name, NodeKind::Folder, root_path_str);
+        tree.add_node(None, root_data)
     }
 
     fn process_file(
@@ -0,0 +1,428 @@
+//! Workspace path resolution shared by the tracker and zone checks.
+//!
+//! Agents report paths in whatever form they like: absolute, relative to the
+//! workspace, with `./` prefixes or `..` segments. Everything that compares
+//! paths (tracked file keys, zone patterns) works on the normalized
+//! workspace-relative form produced here, so a path can't get past a zone
+//! just by being spelled differently.
+//!
+//! Besides the primary root, a workspace can have named roots (sibling
+//! repos, a shared `libs/` checkout). Paths under a named root are keyed
+//! `name:relative`; paths under the primary root stay plain relative paths.
+
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+/// Separates a named root from the path below it in a path ID.
+pub const ROOT_SEPARATOR: char = ':';
+
+/// Group for paths outside every root, keyed `external:/absolute/path`.
+pub const EXTERNAL_ROOT: &str = "external";
+
+/// An extra workspace root. Paths under it are keyed `name:relative`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedRoot {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl NamedRoot {
+    /// Names become ID prefixes, so they can't contain `/` or `:`, and
+    /// `external` is reserved for paths outside every root.
+    pub fn new(name: &str, path: PathBuf) -> Result<Self> {
+        if name.is_empty() || name.contains(['/', '\\', ROOT_SEPARATOR]) {
+            bail!("invalid root name {name:?}: must be non-empty without '/', '\\' or ':'");
+        }
+        if name == EXTERNAL_ROOT {
+            bail!("root name {EXTERNAL_ROOT:?} is reserved");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            path,
+        })
+    }
+
+    /// Parse a `NAME=PATH` CLI value.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let Some((name, path)) = spec.split_once('=') else {
+            bail!("expected NAME=PATH, got {spec:?}");
+        };
+        Self::new(name, PathBuf::from(path))
+    }
+}
+
+/// ID of `path` under the root called `root`.
+pub fn root_id(root: &str, path: &str) -> String {
+    format!("{root}{ROOT_SEPARATOR}{path}")
+}
+
+/// ID for a path outside every root.
+pub fn external_id(path: &str) -> String {
+    root_id(EXTERNAL_ROOT, path)
+}
+
+/// Outcome of resolving a raw path against the workspace root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedPath {
+    /// Inside the workspace. Workspace-relative and `/`-separated when a
+    /// root is known, otherwise the normalized path as given.
+    Inside(String),
+    /// Absolute path that lies outside the workspace root.
+    Outside(String),
+    /// Path that only leaves the workspace through `..` segments or a
+    /// symlink.
+    Escape,
+}
+
+/// Resolves agent-reported paths against the workspace roots.
+#[derive(Debug, Clone, Default)]
+pub struct PathResolver {
+    root: Option<PathBuf>,
+    /// Named roots, deepest path first so nested roots win.
+    roots: Vec<NamedRoot>,
+    /// Follow symlinks on disk, so a link inside the workspace that points
+    /// outside of it counts as an escape.
+    resolve_symlinks: bool,
+}
+
+impl PathResolver {
+    pub fn new(root: Option<PathBuf>) -> Self {
+        Self {
+            root,
+            roots: Vec::new(),
+            resolve_symlinks: false,
+        }
+    }
+
+    pub fn with_roots(mut self, roots: impl IntoIterator<Item = NamedRoot>) -> Self {
+        for root in roots {
+            self.add_root(root);
+        }
+        self
+    }
+
+    /// Add a named root, replacing any root with the same name.
+    pub fn add_root(&mut self, root: NamedRoot) {
+        self.roots.retain(|r| r.name != root.name);
+        self.roots.push(root);
+        self.roots
+            .sort_by_key(|r| std::cmp::Reverse(r.path.components().count()));
+    }
+
+    pub fn set_root(&mut self, root: Option<PathBuf>) {
+        self.root = root;
+    }
+
+    pub fn roots(&self) -> &[NamedRoot] {
+        &self.roots
+    }
+
+    pub fn with_symlinks(mut self, resolve_symlinks: bool) -> Self {
+        self.resolve_symlinks = resolve_symlinks;
+        self
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    /// Resolve against the named roots first, for absolute paths, then the
+    /// primary root. A path under a named root comes back `Inside` as
+    /// `name:relative`.
+    pub fn resolve(&self, raw: &str) -> ResolvedPath {
+        if Path::new(&raw.replace('\\', "/")).is_absolute() {
+            for named in &self.roots {
+                match resolve(raw, Some(&named.path), self.resolve_symlinks) {
+                    ResolvedPath::Inside(relative) => {
+                        return ResolvedPath::Inside(root_id(&named.name, &relative))
+                    }
+                    ResolvedPath::Escape => return ResolvedPath::Escape,
+                    ResolvedPath::Outside(_) => {}
+                }
+            }
+        }
+        resolve(raw, self.root.as_deref(), self.resolve_symlinks)
+    }
+
+    /// Split a path ID into the named root it belongs to (or
+    /// `EXTERNAL_ROOT`) and the path below it. IDs under the primary root
+    /// have no root.
+    pub fn split_id<'a>(&self, id: &'a str) -> (Option<&'a str>, &'a str) {
+        match id.split_once(ROOT_SEPARATOR) {
+            Some((root, rest))
+                if root == EXTERNAL_ROOT || self.roots.iter().any(|r| r.name == root) =>
+            {
+                (Some(root), rest)
+            }
+            _ => (None, id),
+        }
+    }
+
+    /// Filesystem path for a path ID, the inverse of `resolve`.
+    pub fn to_fs_path(&self, id: &str) -> PathBuf {
+        match self.split_id(id) {
+            (Some(EXTERNAL_ROOT), path) => PathBuf::from(path),
+            (Some(name), path) => self
+                .roots
+                .iter()
+                .find(|r| r.name == name)
+                .map(|r| r.path.join(path))
+                .unwrap_or_else(|| PathBuf::from(id)),
+            (None, path) => match &self.root {
+                Some(root) => root.join(path),
+                None => PathBuf::from(path),
+            },
+        }
+    }
+}
+
+/// Resolve `raw` against `root`.
+///
+/// Relative paths are taken relative to the root. `.` and `..` segments are
+/// collapsed lexically; with `resolve_symlinks` the result is additionally
+/// canonicalized on disk (the longest existing ancestor, so paths that are
+/// about to be created still resolve).
+pub fn resolve(raw: &str, root: Option<&Path>, resolve_symlinks: bool) -> ResolvedPath {
+    let raw = raw.replace('\\', "/");
+    let absolute = Path::new(&raw).is_absolute();
+    let Some(normalized) = normalize_lexically(&raw, absolute) else {
+        return ResolvedPath::Escape;
+    };
+
+    let Some(root) = root else {
+        return ResolvedPath::Inside(normalized);
+    };
+    let root_str = root.to_string_lossy().replace('\\', "/");
+    let root_norm = normalize_lexically(&root_str, true).unwrap_or(root_str);
+    let full = if absolute {
+        normalized
+    } else {
+        format!("{}/{}", root_norm.trim_end_matches('/'), normalized)
+    };
+
+    let Some(relative) = strip_root(&full, &root_norm) else {
+        // Only absolute paths can land outside the root lexically.
+        return ResolvedPath::Outside(full);
+    };
+    if !resolve_symlinks {
+        return ResolvedPath::Inside(relative);
+    }
+
+    let real_root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let real_root = real_root.to_string_lossy().replace('\\', "/");
+    let real = canonicalize_existing(Path::new(&full));
+    match strip_root(&real.to_string_lossy().replace('\\', "/"), &real_root) {
+        Some(relative) => ResolvedPath::Inside(relative),
+        None => ResolvedPath::Escape,
+    }
+}
+
+/// Collapse `.` and `..` segments and duplicate separators. Returns `None`
+/// if a relative path climbs above its starting point; absolute paths stop
+/// at `/`.
+fn normalize_lexically(path: &str, absolute: bool) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for seg in path.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() && !absolute {
+                    return None;
+                }
+            }
+            seg => parts.push(seg),
+        }
+    }
+    let joined = parts.join("/");
+    Some(if absolute && !path.starts_with('/') {
+        // Windows drive paths (`C:/...`) keep their prefix as the first part
+        joined
+    } else if absolute {
+        format!("/{joined}")
+    } else {
+        joined
+    })
+}
+
+/// Workspace-relative form of `path` if it lies under `root`.
+fn strip_root(path: &str, root: &str) -> Option<String> {
+    let root = root.trim_end_matches('/');
+    if path == root {
+        return Some(String::new());
+    }
+    path.strip_prefix(root)?
+        .strip_prefix('/')
+        .map(|rest| rest.to_string())
+}
+
+/// Canonicalize the longest existing ancestor of `path` and re-append the
+/// components that don't exist yet.
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut missing: Vec<Component> = Vec::new();
+    loop {
+        if let Ok(real) = std::fs::canonicalize(existing) {
+            return missing.iter().rev().fold(real, |acc, c| acc.join(c));
+        }
+        let mut components = existing.components();
+        match components.next_back() {
+            Some(last) => {
+                missing.push(last);
+                existing = components.as_path();
+            }
+            None => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_are_normalized() {
+        let root = Path::new("/workspace");
+        assert_eq!(
+            resolve("./src/../src/ui/a.ts", Some(root), false),
+            ResolvedPath::Inside("src/ui/a.ts".to_string())
+        );
+        assert_eq!(
+            resolve("src\\ui\\a.ts", Some(root), false),
+            ResolvedPath::Inside("src/ui/a.ts".to_string())
+        );
+    }
+
+    #[test]
+    fn absolute_paths_are_made_relative() {
+        let root = Path::new("/workspace/");
+        assert_eq!(
+            resolve("/workspace/src/ui/a.ts", Some(root), false),
+            ResolvedPath::Inside("src/ui/a.ts".to_string())
+        );
+        assert_eq!(
+            resolve("/workspace-other/a.ts", Some(root), false),
+            ResolvedPath::Outside("/workspace-other/a.ts".to_string())
+        );
+        assert_eq!(
+            resolve("/workspace/src/../../etc/passwd", Some(root), false),
+            ResolvedPath::Outside("/etc/passwd".to_string())
+        );
+    }
+
+    #[test]
+    fn traversal_above_root_is_an_escape() {
+        assert_eq!(
+            resolve("src/../../etc/passwd", Some(Path::new("/workspace")), false),
+            ResolvedPath::Escape
+        );
+        assert_eq!(resolve("../secrets", None, false), ResolvedPath::Escape);
+    }
+
+    #[test]
+    fn without_root_paths_are_only_normalized() {
+        assert_eq!(
+            resolve("/src/./main.rs", None, false),
+            ResolvedPath::Inside("/src/main.rs".to_string())
+        );
+        assert_eq!(
+            resolve("src//lib/../main.rs", None, false),
+            ResolvedPath::Inside("src/main.rs".to_string())
+        );
+    }
+
+    fn multi_root() -> PathResolver {
+        PathResolver::new(Some(PathBuf::from("/ws/app"))).with_roots([
+            NamedRoot::new("libs", PathBuf::from("/ws/libs")).unwrap(),
+            NamedRoot::new("vendored", PathBuf::from("/ws/app/vendor")).unwrap(),
+        ])
+    }
+
+    #[test]
+    fn named_roots_prefix_their_paths() {
+        let resolver = multi_root();
+        assert_eq!(
+            resolver.resolve("/ws/libs/util/src/lib.rs"),
+            ResolvedPath::Inside("libs:util/src/lib.rs".to_string())
+        );
+        // Relative paths stay under the primary root
+        assert_eq!(
+            resolver.resolve("src/main.rs"),
+            ResolvedPath::Inside("src/main.rs".to_string())
+        );
+        // The deepest root wins
+        assert_eq!(
+            resolver.resolve("/ws/app/vendor/zlib/zlib.h"),
+            ResolvedPath::Inside("vendored:zlib/zlib.h".to_string())
+        );
+        assert_eq!(
+            resolver.resolve("/ws/other/a.rs"),
+            ResolvedPath::Outside("/ws/other/a.rs".to_string())
+        );
+    }
+
+    #[test]
+    fn ids_map_back_to_filesystem_paths() {
+        let resolver = multi_root();
+        assert_eq!(resolver.split_id("libs:a.rs"), (Some("libs"), "a.rs"));
+        assert_eq!(
+            resolver.split_id("external:/tmp/x.rs"),
+            (Some(EXTERNAL_ROOT), "/tmp/x.rs")
+        );
+        assert_eq!(resolver.split_id("src/a.rs::Foo"), (None, "src/a.rs::Foo"));
+        assert_eq!(resolver.split_id("unknown:a.rs"), (None, "unknown:a.rs"));
+
+        assert_eq!(
+            resolver.to_fs_path("libs:util/lib.rs"),
+            PathBuf::from("/ws/libs/util/lib.rs")
+        );
+        assert_eq!(
+            resolver.to_fs_path("external:/tmp/x.rs"),
+            PathBuf::from("/tmp/x.rs")
+        );
+        assert_eq!(
+            resolver.to_fs_path("src/main.rs"),
+            PathBuf::from("/ws/app/src/main.rs")
+        );
+    }
+
+    #[test]
+    fn root_names_are_validated() {
+        assert!(NamedRoot::parse("libs=/ws/libs").is_ok());
+        assert!(NamedRoot::parse("/ws/libs").is_err());
+        assert!(NamedRoot::parse("=/ws/libs").is_err());
+        assert!(NamedRoot::parse("a:b=/ws/libs").is_err());
+        assert!(NamedRoot::parse("external=/ws/libs").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_workspace_are_escapes() {
+        let outside = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::create_dir(workspace.path().join("src")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), workspace.path().join("src/link")).unwrap();
+
+        let resolver = PathResolver::new(Some(workspace.path().to_path_buf()));
+        assert_eq!(
+            resolver.resolve("src/link/secret.txt"),
+            ResolvedPath::Inside("src/link/secret.txt".to_string())
+        );
+
+        let resolver = resolver.with_symlinks(true);
+        assert_eq!(
+            resolver.resolve("src/link/secret.txt"),
+            ResolvedPath::Escape
+        );
+        // Files that don't exist yet still resolve through their parent
+        assert_eq!(
+            resolver.resolve("src/new/file.rs"),
+            ResolvedPath::Inside("src/new/file.rs".to_string())
+        );
+        let absolute = workspace.path().join("src/link/secret.txt");
+        assert_eq!(
+            resolver.resolve(&absolute.to_string_lossy()),
+            ResolvedPath::Escape
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Model price table for estimating cost when agents report tokens but not
+//! cost, loaded from `~/.eisen/pricing.toml` (or `observe --pricing PATH`):
+//!
+//! ```toml
+//! currency = "USD"                  # currency of every price below
+//!
+//! [models."claude-sonnet-4"]        # per million tokens
+//! input = 3.0
+//! output = 15.0
+//! cache_read = 0.3                  # optional, defaults to `input`
+//! cache_write = 3.75                # optional, defaults to `input`
+//!
+//! [rates]                           # value of one unit in `currency`
+//! EUR = 1.08
+//! ```
+//!
+//! A `.json` file with the same shape works too. The file's models are
+//! added to (and replace) the built-in list of approximate list prices; a
+//! file that sets a currency other than USD drops the built-ins instead.
+//! Model IDs match case-insensitively: exactly, then by their last `/`
+//! segment, then by the longest entry they start with
+//! (`claude-sonnet-4-20250514` uses `claude-sonnet-4`).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::session_store::default_eisen_dir;
+use crate::types::{Cost, TokenUsage};
+
+/// Price file name, relative to the eisen dir.
+pub const PRICING_FILE: &str = "pricing.toml";
+
+const DEFAULT_CURRENCY: &str = "USD";
+
+/// Built-in USD prices per million tokens: model, input, output, cache
+/// read, cache write.
+const DEFAULT_PRICES: &[(&str, f64, f64, f64, f64)] = &[
+    ("claude-opus-4", 15.0, 75.0, 1.5, 18.75),
+    ("claude-opus-4-5", 5.0, 25.0, 0.5, 6.25),
+    ("claude-sonnet-4", 3.0, 15.0, 0.3, 3.75),
+    ("claude-3-7-sonnet", 3.0, 15.0, 0.3, 3.75),
+    ("claude-haiku-4-5", 1.0, 5.0, 0.1, 1.25),
+    ("claude-3-5-haiku", 0.8, 4.0, 0.08, 1.0),
+    ("gpt-4o", 2.5, 10.0, 1.25, 2.5),
+    ("gpt-4o-mini", 0.15, 0.6, 0.075, 0.15),
+    ("gpt-4.1", 2.0, 8.0, 0.5, 2.0),
+    ("gpt-4.1-mini", 0.4, 1.6, 0.1, 0.4),
+    ("gpt-4.1-nano", 0.1, 0.4, 0.025, 0.1),
+    ("o3", 2.0, 8.0, 0.5, 2.0),
+    ("o3-mini", 1.1, 4.4, 0.55, 1.1),
+    ("gemini-2.5-pro", 1.25, 10.0, 0.31, 1.25),
+    ("gemini-2.5-flash", 0.3, 2.5, 0.075, 0.3),
+];
+
+/// Prices per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+}
+
+impl ModelPrice {
+    /// Cost of `usage`, in the table's currency. Thought tokens are billed
+    /// as output.
+    pub fn cost_of(&self, usage: &TokenUsage) -> f64 {
+        let per_token = |tokens: u64, rate: f64| tokens as f64 * rate / 1_000_000.0;
+        per_token(usage.input, self.input)
+            + per_token(usage.output + usage.thought, self.output)
+            + per_token(usage.cache_read, self.cache_read.unwrap_or(self.input))
+            + per_token(usage.cache_write, self.cache_write.unwrap_or(self.input))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PriceFile {
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    models: HashMap<String, ModelPrice>,
+    #[serde(default)]
+    rates: HashMap<String, f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    /// Currency of every model price.
+    pub currency: String,
+    pub models: HashMap<String, ModelPrice>,
+    /// Value of one unit of each currency in `currency`, for converting
+    /// reported costs.
+    pub rates: HashMap<String, f64>,
+}
+
+impl Default for PriceTable {
+    /// Built-in prices only.
+    fn default() -> Self {
+        let models = DEFAULT_PRICES
+            .iter()
+            .map(|&(model, input, output, cache_read, cache_write)| {
+                let price = ModelPrice {
+                    input,
+                    output,
+                    cache_read: Some(cache_read),
+                    cache_write: Some(cache_write),
+                };
+                (model.to_string(), price)
+            })
+            .collect();
+        Self {
+            currency: DEFAULT_CURRENCY.to_string(),
+            models,
+            rates: HashMap::new(),
+        }
+    }
+}
+
+impl PriceTable {
+    /// `~/.eisen/pricing.toml` (or `$EISEN_DIR/pricing.toml`).
+    pub fn default_path() -> PathBuf {
+        default_eisen_dir().join(PRICING_FILE)
+    }
+
+    /// The built-in table with `path` applied on top.
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let json = path.extension().is_some_and(|ext| ext == "json");
+        Self::parse(&raw, json).with_context(|| format!("invalid price file {}", path.display()))
+    }
+
+    /// The table from the default path if it exists, else the built-ins.
+    /// A broken file is logged and skipped.
+    pub fn load_default() -> Self {
+        let path = Self::default_path();
+        if !path.is_file() {
+            return Self::default();
+        }
+        Self::load(&path).unwrap_or_else(|e| {
+            warn!(error = format!("{e:#}"), "using built-in model prices");
+            Self::default()
+        })
+    }
+
+    pub fn parse(raw: &str, json: bool) -> Result<Self> {
+        let file: PriceFile = if json {
+            serde_json::from_str(raw)?
+        } else {
+            toml::from_str(raw)?
+        };
+        let mut table = Self::default();
+        if let Some(currency) = file.currency {
+            if currency != table.currency {
+                table.models.clear();
+                table.currency = currency;
+            }
+        }
+        // Lookups lowercase the model ID, so the keys must be lowercase too
+        table.models.extend(
+            file.models
+                .into_iter()
+                .map(|(model, price)| (model.to_ascii_lowercase(), price)),
+        );
+        table.rates.extend(file.rates);
+        Ok(table)
+    }
+
+    /// Price for `model_id`, or `None` if no entry matches.
+    pub fn price(&self, model_id: &str) -> Option<&ModelPrice> {
+        let id = model_id.to_ascii_lowercase();
+        let bare = id.rsplit('/').next().unwrap_or(&id);
+        if let Some(price) = self.models.get(&id).or_else(|| self.models.get(bare)) {
+            return Some(price);
+        }
+        // Longest entry the ID extends at a word boundary, so dated or
+        // suffixed IDs pick up their family's price
+        self.models
+            .iter()
+            .filter(|(model, _)| {
+                bare.strip_prefix(model.as_str())
+                    .is_some_and(|rest| rest.starts_with(['-', '@', ':']))
+            })
+            .max_by_key(|(model, _)| model.len())
+            .map(|(_, price)| price)
+    }
+
+    /// Estimated cost of `usage` on `model_id`, marked as estimated.
+    pub fn estimate(&self, model_id: &str, usage: &TokenUsage) -> Option<Cost> {
+        let price = self.price(model_id)?;
+        Some(Cost {
+            amount: price.cost_of(usage),
+            currency: self.currency.clone(),
+            estimated: true,
+        })
+    }
+
+    /// `cost` in `currency`, via the table's rates. `None` if either
+    /// currency has no rate.
+    pub fn convert(&self, cost: &Cost, currency: &str) -> Option<Cost> {
+        if cost.currency == currency {
+            return Some(cost.clone());
+        }
+        let rate = |c: &str| {
+            if c == self.currency {
+                Some(1.0)
+            } else {
+                self.rates.get(c).copied()
+            }
+        };
+        Some(Cost {
+            amount: cost.amount * rate(&cost.currency)? / rate(currency)?,
+            currency: currency.to_string(),
+            estimated: cost.estimated,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u64, output: u64) -> TokenUsage {
+        TokenUsage {
+            input,
+            output,
+            ..TokenUsage::default()
+        }
+    }
+
+    #[test]
+    fn model_ids_match_exactly_then_by_family() {
+        let table = PriceTable::default();
+        assert_eq!(table.price("gpt-4o").unwrap().input, 2.5);
+        assert_eq!(table.price("gpt-4o-mini").unwrap().input, 0.15);
+        assert_eq!(table.price("claude-sonnet-4-20250514").unwrap().input, 3.0);
+        assert_eq!(table.price("claude-opus-4-5-20251101").unwrap().input, 5.0);
+        assert_eq!(table.price("anthropic/Claude-Sonnet-4").unwrap().input, 3.0);
+        // A shared prefix isn't enough
+        assert!(table.price("o3x").is_none());
+        assert!(table.price("llama-3").is_none());
+    }
+
+    #[test]
+    fn estimates_are_per_million_tokens() {
+        let table = PriceTable::default();
+        let cost = table
+            .estimate("claude-sonnet-4", &usage(1_000_000, 100_000))
+            .unwrap();
+        assert!((cost.amount - 4.5).abs() < 1e-9);
+        assert_eq!(cost.currency, "USD");
+        assert!(cost.estimated);
+
+        let cached = TokenUsage {
+            cache_read: 1_000_000,
+            thought: 1_000_000,
+            ..TokenUsage::default()
+        };
+        let cost = table.estimate("claude-sonnet-4", &cached).unwrap();
+        assert!((cost.amount - 15.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn files_extend_and_override_the_builtins() {
+        let table = PriceTable::parse(
+            r#"
+[models."gpt-4o"]
+input = 2.0
+output = 8.0
+
+[models.local-llama]
+input = 0.0
+output = 0.0
+
+[rates]
+EUR = 1.1
+"#,
+            false,
+        )
+        .unwrap();
+        assert_eq!(table.price("gpt-4o").unwrap().input, 2.0);
+        assert!(table.price("local-llama").is_some());
+        assert!(table.price("claude-sonnet-4").is_some());
+        assert_eq!(table.rates["EUR"], 1.1);
+
+        // Prices in another currency replace the built-ins
+        let table = PriceTable::parse(
+            r#"{"currency": "EUR", "models": {"mistral-large": {"input": 2.0, "output": 6.0}}}"#,
+            true,
+        )
+        .unwrap();
+        assert_eq!(table.currency, "EUR");
+        assert!(table.price("claude-sonnet-4").is_none());
+        assert!(table.price("mistral-large").is_some());
+
+        assert!(PriceTable::parse("[models.x]\ninput = 1.0\n", false).is_err());
+    }
+
+    #[test]
+    fn file_model_ids_match_case_insensitively() {
+        let table = PriceTable::parse(
+            "[models.\"Local-Llama\"]\ninput = 1.0\noutput = 2.0\n",
+            false,
+        )
+        .unwrap();
+        assert_eq!(table.price("local-llama").unwrap().input, 1.0);
+        assert_eq!(table.price("LOCAL-LLAMA-3b").unwrap().input, 1.0);
+    }
+
+    #[test]
+    fn costs_convert_through_rates() {
+        let mut table = PriceTable::default();
+        table.rates.insert("EUR".to_string(), 1.1);
+        table.rates.insert("GBP".to_string(), 1.25);
+        let eur = Cost {
+            amount: 10.0,
+            currency: "EUR".to_string(),
+            estimated: false,
+        };
+        let usd = table.convert(&eur, "USD").unwrap();
+        assert!((usd.amount - 11.0).abs() < 1e-9);
+        let gbp = table.convert(&eur, "GBP").unwrap();
+        assert!((gbp.amount - 8.8).abs() < 1e-9);
+        assert_eq!(table.convert(&eur, "EUR").unwrap(), eur);
+        assert!(table.convert(&eur, "JPY").is_none());
+    }
+}
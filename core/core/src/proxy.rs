@@ -4,24 +4,42 @@
 //! Reads lines from agent stdout, inspects for context, forwards to editor stdout.
 //! Agent stderr is inherited (passes through to the editor's stderr).
 //!
-//! Phase 3 addition: Zone enforcement. When a ZoneConfig is provided, the proxy
-//! intercepts `fs/read_text_file` and `fs/write_text_file` requests from the
-//! agent and blocks access to paths outside the allowed zone. Blocked requests
-//! receive a JSON-RPC error response directly from the proxy (not forwarded to
-//! the editor).
+//! Phase 3 addition: Zone enforcement. When the requesting session has a zone
+//! (see `ZoneRegistry`), the proxy intercepts `fs/read_text_file` and
+//! `fs/write_text_file` requests from the agent and blocks access to paths
+//! outside the allowed zone. Blocked requests receive a JSON-RPC error response
+//! directly from the proxy (not forwarded to the editor). Zones are looked up
+//! per message, so changes made over RPC apply to the very next request.
+//!
+//! Hot zone: files pinned with the `set_hot_zone` RPC are re-appended to the
+//! next `session/prompt` as embedded resources once they fall out of the
+//! agent's context (compaction or turn expiry). This is the only case where
+//! the upstream direction rewrites a message instead of passing it through.
 
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use agent_client_protocol_schema::{
+    PermissionOption, PermissionOptionKind, RequestPermissionOutcome, RequestPermissionRequest,
+    RequestPermissionResponse, SelectedPermissionOutcome, SessionNotification, SessionUpdate,
+    ToolCallContent, ToolCallLocation, ToolKind, AGENT_METHOD_NAMES, CLIENT_METHOD_NAMES,
+};
 use anyhow::Result;
-use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::{broadcast, Mutex};
 use tracing::{debug, warn};
 
-use crate::extract;
+use crate::extract::{self, DiffFiles};
+use crate::file_reads::MAX_TOKENIZED_BYTES;
+use crate::paths::PathResolver;
 use crate::tcp::WireLine;
 use crate::tracker::ContextTracker;
-use crate::types::{Action, BlockedAccess, ZoneConfig};
+use crate::types::{
+    Action, BlockedAccess, CompiledZone, SessionKey, Source, ToolCallPolicy, ZoneMode,
+};
+use crate::zone_registry::ZoneRegistry;
 
 /// JSON-RPC error code for zone violation.
 const ZONE_VIOLATION_CODE: i64 = -32001;
@@ -41,10 +59,17 @@ pub fn spawn_agent(command: &str, args: &[String]) -> Result<Child> {
 /// Task 1: Read from editor stdin, extract context, forward to agent stdin.
 ///
 /// Returns when editor closes stdin (EOF).
-pub async fn upstream_task(
+///
+/// `agent_stdin` is shared with the downstream task, which writes responses
+/// to agent requests the proxy answers itself (zone violations).
+pub async fn upstream_task<W>(
     tracker: Arc<Mutex<ContextTracker>>,
-    mut agent_stdin: impl io::AsyncWrite + Unpin,
-) -> Result<()> {
+    agent_stdin: Arc<Mutex<W>>,
+    zones: Arc<Mutex<ZoneRegistry>>,
+) -> Result<()>
+where
+    W: io::AsyncWrite + Unpin,
+{
     let mut reader = BufReader::new(io::stdin());
     let mut line = String::new();
     while reader.read_line(&mut line).await? > 0 {
@@ -63,38 +88,143 @@ pub async fn upstream_task(
                 "editor -> agent"
             );
         }
+        extract::extract_upstream(&line, &mut *tracker.lock().await);
+        let rewritten = inject_hot_zone(&line, &tracker, &zones).await;
+        let out = rewritten.as_deref().unwrap_or(&line);
         {
-            let mut t = tracker.lock().await;
-            extract::extract_upstream(&line, &mut t);
+            let mut w = agent_stdin.lock().await;
+            w.write_all(out.as_bytes()).await?;
+            w.flush().await?;
         }
-        agent_stdin.write_all(line.as_bytes()).await?;
-        agent_stdin.flush().await?;
         line.clear();
     }
     Ok(())
 }
 
+/// Append pending hot-zone files to a `session/prompt` as
+/// `ContentBlock::Resource` blocks.
+///
+/// Returns the rewritten line (with trailing newline), or `None` if the line
+/// should be forwarded unchanged: not a prompt, nothing pending, or the
+/// agent didn't advertise `embeddedContext`. Each injected file is recorded
+/// as `Action::Refreshed`. Files are read without holding the tracker lock;
+/// ones that can't be read, or that the session's enforced zone doesn't
+/// allow, stay queued for the next prompt.
+async fn inject_hot_zone(
+    line: &str,
+    tracker: &Arc<Mutex<ContextTracker>>,
+    zones: &Arc<Mutex<ZoneRegistry>>,
+) -> Option<String> {
+    let mut v: serde_json::Value = serde_json::from_str(line).ok()?;
+    if v.get("method").and_then(|m| m.as_str()) != Some(AGENT_METHOD_NAMES.session_prompt) {
+        return None;
+    }
+    v.get("params")?.get("prompt")?.as_array()?;
+    let (key, pending) = {
+        let mut t = tracker.lock().await;
+        if !t.embedded_context() {
+            return None;
+        }
+        let session_id = v
+            .get("params")
+            .and_then(|p| p.get("sessionId"))
+            .and_then(|s| s.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| t.session_id().to_string());
+        let pending: Vec<(String, PathBuf)> = t
+            .take_pending_refresh(&session_id)
+            .into_iter()
+            .map(|path| {
+                let file_path = t.resolve_path(&path);
+                let file_path = std::path::absolute(&file_path).unwrap_or(file_path);
+                (path, file_path)
+            })
+            .collect();
+        (SessionKey::new(t.agent_id(), &session_id), pending)
+    };
+    if pending.is_empty() {
+        return None;
+    }
+    let zone = zones.lock().await.enforced_zone(&key);
+
+    let mut injected = Vec::new();
+    let mut failed = Vec::new();
+    let mut resources = Vec::new();
+    for (path, file_path) in pending {
+        if let Some((zone, paths)) = &zone {
+            if !zone.is_path_allowed(&file_path.to_string_lossy(), paths) {
+                debug!(path = path.as_str(), "hot zone: skipped out-of-zone file");
+                failed.push(path);
+                continue;
+            }
+        }
+        match tokio::fs::read_to_string(&file_path).await {
+            Ok(text) => {
+                resources.push(serde_json::json!({
+                    "type": "resource",
+                    "resource": {
+                        "uri": format!("file://{}", file_path.to_string_lossy()),
+                        "text": text,
+                    }
+                }));
+                injected.push(path);
+            }
+            Err(e) => {
+                warn!(path = path.as_str(), error = %e, "hot zone: failed to read file");
+                failed.push(path);
+            }
+        }
+    }
+
+    let session_id = key.session_id;
+    let mut t = tracker.lock().await;
+    t.requeue_refresh(&session_id, &failed);
+    if injected.is_empty() {
+        return None;
+    }
+    v["params"]["prompt"].as_array_mut()?.extend(resources);
+    for path in &injected {
+        debug!(path = path.as_str(), "hot zone: re-injected into prompt");
+        t.file_access_for_session(&session_id, path, Action::Refreshed, Source::Proxy);
+    }
+    serde_json::to_string(&v).ok().map(|s| s + "\n")
+}
+
 /// Task 2: Read from agent stdout, extract context, forward to editor stdout.
 ///
-/// When zone enforcement is active (`zone_config` is `Some`), intercepts
-/// `fs/read_text_file` and `fs/write_text_file` requests. If the path is
-/// outside the allowed zone:
-///   - Returns a JSON-RPC error to the agent (via agent stdin, not shown here
-///     -- the error is written directly to editor stdout for the agent to receive)
-///   - Broadcasts a `BlockedAccess` message to TCP listeners
-///   - Records the blocked access in the tracker
-///   - Does NOT forward the request to the editor
+/// When the requesting session has a zone in `zones` in `ZoneMode::Enforce`:
+///   - `fs/read_text_file` / `fs/write_text_file` outside the zone are answered
+///     with a JSON-RPC error on agent stdin, recorded as `Action::Blocked`,
+///     broadcast as `BlockedAccess`, and NOT forwarded to the editor.
+///   - `session/request_permission` for an out-of-zone tool call is answered
+///     with a reject option when the zone's `ToolCallPolicy` is `Deny`.
+///   - `session/update` tool calls touching out-of-zone paths are broadcast
+///     as `BlockedAccess` after the fact (once per tool call and path).
+///   - Search results listing out-of-zone paths are redacted if the zone
+///     has `redact_search_results` set.
+///
+/// In `ZoneMode::Warn` the same violations are broadcast with
+/// `enforced: false` and recorded as `Action::Warned`, but every message is
+/// forwarded unchanged. `ZoneMode::Off` skips the checks entirely. All
+/// violations are added to the zone audit log.
 ///
 /// Returns when agent closes stdout (EOF / exit).
-pub async fn downstream_task(
+pub async fn downstream_task<W>(
     tracker: Arc<Mutex<ContextTracker>>,
     agent_stdout: impl io::AsyncRead + Unpin,
-    zone_config: Option<Arc<ZoneConfig>>,
+    agent_stdin: Arc<Mutex<W>>,
+    zones: Arc<Mutex<ZoneRegistry>>,
     blocked_tx: broadcast::Sender<WireLine>,
-) -> Result<()> {
+) -> Result<()>
+where
+    W: io::AsyncWrite + Unpin,
+{
     let mut reader = BufReader::new(agent_stdout);
     let mut writer = io::stdout();
     let mut line = String::new();
+    let mut tool_calls = ToolCalls::default();
+    // Workspace roots are fixed at startup, so one copy serves every diff
+    let paths = tracker.lock().await.path_resolver().clone();
     while reader.read_line(&mut line).await? > 0 {
         // Log the method (if JSON-RPC) for downstream messages
         let parsed = serde_json::from_str::<serde_json::Value>(&line).ok();
@@ -113,84 +243,355 @@ pub async fn downstream_task(
             );
         }
 
-        // Zone enforcement check
-        if let (Some(ref zone), Some(ref v)) = (&zone_config, &parsed) {
-            if let Some(block_result) = check_zone_violation(v, zone) {
-                // Blocked! Don't forward to editor.
-                let id = v.get("id");
-                let (agent_id, session_id) = {
+        let tool_call = parsed.as_ref().and_then(tool_call_event);
+        if let Some(ref event) = tool_call {
+            let session_id = match event.session_id {
+                Some(session_id) => session_id.to_string(),
+                None => tracker.lock().await.session_id().to_string(),
+            };
+            let state = tool_calls.state(event.id, &session_id);
+            if let Some(kind) = event.kind {
+                state.kind = Some(kind.to_string());
+            }
+        }
+
+        // Zone of the requesting session, for the messages that can leave it
+        let zone = match parsed {
+            Some(ref v) if tool_call.is_some() || is_zone_checked_method(v) => {
+                let key = {
                     let t = tracker.lock().await;
-                    let sid = block_result
-                        .session_id
-                        .as_deref()
-                        .unwrap_or_else(|| t.session_id())
-                        .to_string();
-                    (t.agent_id().to_string(), sid)
+                    let sid = v
+                        .get("params")
+                        .and_then(|p| p.get("sessionId"))
+                        .and_then(|s| s.as_str())
+                        .unwrap_or_else(|| t.session_id());
+                    SessionKey::new(t.agent_id(), sid)
                 };
+                let zones = zones.lock().await;
+                zones
+                    .shared_zone_for(&key)
+                    .filter(|zone| zone.mode != ZoneMode::Off)
+                    .map(|zone| (v, key, zone, zones.shared_path_resolver()))
+            }
+            _ => None,
+        };
+
+        let mut redacted = None;
+        // Out-of-zone paths that were let through, recorded as
+        // Action::Warned once the line has been extracted.
+        let mut warned: Vec<(String, String)> = Vec::new();
+        if let Some((v, key, zone, paths)) = zone {
+            let enforce = zone.mode == ZoneMode::Enforce;
+
+            if let Some(block_result) = check_zone_violation(v, &zone, &paths) {
+                report_violation(&zones, &blocked_tx, &key, &block_result, enforce).await;
+                if enforce {
+                    // Blocked! Don't forward to editor.
+                    warn!(
+                        path = block_result.path.as_str(),
+                        action = block_result.action.as_str(),
+                        "zone violation: blocked out-of-zone access"
+                    );
 
+                    // Record in tracker as Blocked action
+                    {
+                        let mut t = tracker.lock().await;
+                        t.file_access_for_session(
+                            &key.session_id,
+                            &block_result.path,
+                            Action::Blocked,
+                            Source::Proxy,
+                        );
+                    }
+
+                    // Answer the agent's request with a JSON-RPC error
+                    if let Some(id) = v.get("id") {
+                        let error_response = serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": {
+                                "code": ZONE_VIOLATION_CODE,
+                                "message": format!(
+                                    "Outside agent zone: {}. Request cross-region info through the orchestrator.",
+                                    block_result.path
+                                )
+                            }
+                        });
+                        reply_to_agent(&agent_stdin, &error_response).await?;
+                    }
+
+                    line.clear();
+                    continue; // Do NOT forward to editor
+                }
                 warn!(
                     path = block_result.path.as_str(),
                     action = block_result.action.as_str(),
-                    "zone violation: blocked out-of-zone access"
+                    "zone violation (warn mode): out-of-zone access forwarded"
                 );
+                warned.push((key.session_id.clone(), block_result.path));
+            }
 
-                // Record in tracker as Blocked action
-                {
-                    let mut t = tracker.lock().await;
-                    t.file_access_for_session(&session_id, &block_result.path, Action::Blocked);
-                }
+            if zone.tool_calls == ToolCallPolicy::Deny {
+                if let Some(denied) = check_permission_violation(v) {
+                    let violations = tool_call_violations(
+                        denied.request.tool_call.fields.kind.as_ref(),
+                        denied.request.tool_call.fields.locations.as_deref(),
+                        denied.request.tool_call.fields.content.as_deref(),
+                        &zone,
+                        &paths,
+                    );
+                    if !violations.is_empty() {
+                        let tool_call_id = denied.request.tool_call.tool_call_id.0.to_string();
+                        let state = tool_calls.state(&tool_call_id, &key.session_id);
+                        for violation in &violations {
+                            report_violation(&zones, &blocked_tx, &key, violation, enforce).await;
+                            state.reported.insert(violation.path.clone());
+                        }
+                        if enforce {
+                            warn!(
+                                tool_call_id = tool_call_id.as_str(),
+                                paths = violations.len(),
+                                "zone violation: rejected out-of-zone tool call"
+                            );
+                            {
+                                let mut t = tracker.lock().await;
+                                for violation in &violations {
+                                    t.file_access_for_session(
+                                        &key.session_id,
+                                        &violation.path,
+                                        Action::Blocked,
+                                        Source::Proxy,
+                                    );
+                                }
+                            }
+                            let response = serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": denied.id,
+                                "result": RequestPermissionResponse::new(
+                                    reject_outcome(&denied.request.options)
+                                ),
+                            });
+                            reply_to_agent(&agent_stdin, &response).await?;
 
-                // Build JSON-RPC error response for the agent
-                if let Some(id) = id {
-                    let error_response = serde_json::json!({
-                        "jsonrpc": "2.0",
-                        "id": id,
-                        "error": {
-                            "code": ZONE_VIOLATION_CODE,
-                            "message": format!(
-                                "Outside agent zone: {}. Request cross-region info through the orchestrator.",
-                                block_result.path
-                            )
+                            line.clear();
+                            continue; // Do NOT forward to editor
                         }
-                    });
-                    let error_line = serde_json::to_string(&error_response)? + "\n";
-                    // Write the error response back to editor stdout so the
-                    // ACP connection delivers it to the agent as a response
-                    writer.write_all(error_line.as_bytes()).await?;
-                    writer.flush().await?;
+                        warn!(
+                            tool_call_id = tool_call_id.as_str(),
+                            paths = violations.len(),
+                            "zone violation (warn mode): out-of-zone tool call forwarded"
+                        );
+                        warned.extend(
+                            violations
+                                .into_iter()
+                                .map(|violation| (key.session_id.clone(), violation.path)),
+                        );
+                    }
                 }
+            }
 
-                // Broadcast BlockedAccess message to TCP listeners
-                let blocked_msg = BlockedAccess::new(
-                    &agent_id,
-                    &session_id,
-                    &block_result.path,
-                    &block_result.action,
-                );
-                crate::tcp::broadcast_line(&blocked_tx, &blocked_msg);
+            // Tool calls have already run by the time they are reported, so
+            // out-of-zone paths can only be flagged after the fact.
+            if let Some((tool_call_id, violations)) = check_tool_call_violations(v, &zone, &paths) {
+                let state = tool_calls.state(&tool_call_id, &key.session_id);
+                for violation in violations {
+                    if state.reported.insert(violation.path.clone()) {
+                        warn!(
+                            tool_call_id = tool_call_id.as_str(),
+                            path = violation.path.as_str(),
+                            "zone violation: out-of-zone tool call"
+                        );
+                        report_violation(&zones, &blocked_tx, &key, &violation, false).await;
+                        warned.push((key.session_id.clone(), violation.path));
+                    }
+                }
+            }
 
-                line.clear();
-                continue; // Do NOT forward to editor
+            if enforce && zone.redact_search_results {
+                if let Some(ref event) = tool_call {
+                    let kind = tool_calls.kind(event.id);
+                    redacted = redact_search_results(v, kind, &zone, &paths);
+                }
             }
         }
 
         // Normal path: extract context and forward
+        let diff_files = read_diff_files(&line, &paths).await;
         {
             let mut t = tracker.lock().await;
-            extract::extract_downstream(&line, &mut t);
+            if let Some(session_id) = parsed.as_ref().and_then(|v| prompt_response_session(v, &t)) {
+                tool_calls.end_turn(&session_id);
+            }
+            extract::extract_downstream_with_files(&line, &mut t, &diff_files);
+            for (session_id, path) in &warned {
+                t.file_access_for_session(session_id, path, Action::Warned, Source::Proxy);
+            }
         }
-        writer.write_all(line.as_bytes()).await?;
+        let out = redacted.as_deref().unwrap_or(&line);
+        writer.write_all(out.as_bytes()).await?;
         writer.flush().await?;
+
+        if let Some(event) = tool_call.filter(|e| matches!(e.status, Some("completed" | "failed")))
+        {
+            tool_calls.finish(event.id);
+        }
         line.clear();
     }
     Ok(())
 }
 
+/// Read the start of each file a diff in `line` edits, for locating its
+/// hunk, before the tracker is locked. Files that can't be read are left
+/// out.
+async fn read_diff_files(line: &str, paths: &PathResolver) -> DiffFiles {
+    let mut files = DiffFiles::default();
+    for (path, fs_path) in extract::diff_files(line, paths) {
+        let Ok(file) = tokio::fs::File::open(&fs_path).await else {
+            continue;
+        };
+        let mut bytes = Vec::new();
+        if file
+            .take(MAX_TOKENIZED_BYTES)
+            .read_to_end(&mut bytes)
+            .await
+            .is_ok()
+        {
+            files.insert(path, String::from_utf8_lossy(&bytes).into_owned());
+        }
+    }
+    files
+}
+
+/// Tool calls that haven't finished yet, by tool call ID.
+#[derive(Debug, Default)]
+struct ToolCalls(HashMap<String, ToolCallState>);
+
+impl ToolCalls {
+    fn state(&mut self, id: &str, session_id: &str) -> &mut ToolCallState {
+        self.0
+            .entry(id.to_string())
+            .or_insert_with(|| ToolCallState {
+                session_id: session_id.to_string(),
+                ..Default::default()
+            })
+    }
+
+    fn kind(&self, id: &str) -> Option<&str> {
+        self.0.get(id).and_then(|s| s.kind.as_deref())
+    }
+
+    fn finish(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+
+    /// Forget a session's tool calls when its prompt is answered. Tool
+    /// calls cut short (e.g. by `session/cancel`) may never report a final
+    /// status, but the prompt always gets a response.
+    fn end_turn(&mut self, session_id: &str) {
+        self.0.retain(|_, state| state.session_id != session_id);
+    }
+}
+
+/// Session whose turn a `session/prompt` response ends, or `None` if `v`
+/// isn't one. Must be called before the tracker sees the response, which
+/// forgets the request.
+fn prompt_response_session(v: &serde_json::Value, t: &ContextTracker) -> Option<String> {
+    v.get("result")?.get("stopReason")?;
+    let session_id = v
+        .get("id")
+        .and_then(|id| id.as_u64())
+        .and_then(|id| t.prompt_session(id))
+        .unwrap_or_else(|| t.session_id());
+    Some(session_id.to_string())
+}
+
+/// What the downstream task remembers about a tool call until it finishes.
+#[derive(Debug, Default)]
+struct ToolCallState {
+    session_id: String,
+    /// Kind from the initial `tool_call`; updates usually omit it.
+    kind: Option<String>,
+    /// Out-of-zone paths already reported, so repeated tool call updates
+    /// don't re-broadcast the same violation.
+    reported: HashSet<String>,
+}
+
+/// The fields of a `session/update` tool call (or tool call update) the
+/// downstream task tracks between messages.
+struct ToolCallEvent<'a> {
+    session_id: Option<&'a str>,
+    id: &'a str,
+    kind: Option<&'a str>,
+    status: Option<&'a str>,
+}
+
+fn tool_call_event(v: &serde_json::Value) -> Option<ToolCallEvent<'_>> {
+    if v.get("method")?.as_str()? != CLIENT_METHOD_NAMES.session_update {
+        return None;
+    }
+    let params = v.get("params")?;
+    let update = params.get("update")?;
+    let session_update = update.get("sessionUpdate")?.as_str()?;
+    if session_update != "tool_call" && session_update != "tool_call_update" {
+        return None;
+    }
+    Some(ToolCallEvent {
+        session_id: params.get("sessionId").and_then(|s| s.as_str()),
+        id: update.get("toolCallId")?.as_str()?,
+        kind: update.get("kind").and_then(|k| k.as_str()),
+        status: update.get("status").and_then(|s| s.as_str()),
+    })
+}
+
+/// Write a proxy-generated JSON-RPC response to the agent.
+async fn reply_to_agent<W>(agent_stdin: &Arc<Mutex<W>>, response: &serde_json::Value) -> Result<()>
+where
+    W: io::AsyncWrite + Unpin,
+{
+    let out = serde_json::to_string(response)? + "\n";
+    let mut w = agent_stdin.lock().await;
+    w.write_all(out.as_bytes()).await?;
+    w.flush().await?;
+    Ok(())
+}
+
+/// Broadcast a `BlockedAccess` message to TCP listeners and add it to the
+/// zone audit log.
+async fn report_violation(
+    zones: &Arc<Mutex<ZoneRegistry>>,
+    blocked_tx: &broadcast::Sender<WireLine>,
+    key: &SessionKey,
+    violation: &ZoneViolation,
+    enforced: bool,
+) {
+    let mut zones = zones.lock().await;
+    let blocked_msg = BlockedAccess::new(
+        &key.agent_id,
+        &key.session_id,
+        &violation.path,
+        &violation.action,
+        enforced,
+        zones.clock().now_ms(),
+    );
+    crate::tcp::broadcast_line(blocked_tx, &blocked_msg);
+    zones.record_violation(blocked_msg);
+}
+
+/// Whether a request from the agent is one the zone checks look at: file
+/// system access or a permission request. Tool call updates are detected
+/// separately.
+fn is_zone_checked_method(v: &serde_json::Value) -> bool {
+    v.get("method")
+        .and_then(|m| m.as_str())
+        .is_some_and(|method| {
+            method.starts_with("fs/") || method == CLIENT_METHOD_NAMES.session_request_permission
+        })
+}
+
 /// Result of a zone violation check.
 struct ZoneViolation {
     path: String,
-    action: String, // "read" or "write"
-    session_id: Option<String>,
+    action: String, // "read", "write" or "search"
 }
 
 /// Check if a JSON-RPC message from the agent is a zone violation.
@@ -198,7 +599,11 @@ struct ZoneViolation {
 /// Returns `Some(ZoneViolation)` if the message is an `fs/read_text_file` or
 /// `fs/write_text_file` request with a path outside the allowed zone.
 /// Returns `None` if the message is allowed or not a file access method.
-fn check_zone_violation(v: &serde_json::Value, zone: &ZoneConfig) -> Option<ZoneViolation> {
+fn check_zone_violation(
+    v: &serde_json::Value,
+    zone: &CompiledZone,
+    paths: &PathResolver,
+) -> Option<ZoneViolation> {
     let method = v.get("method")?.as_str()?;
 
     let (action_str, path) = match method {
@@ -219,37 +624,241 @@ fn check_zone_violation(v: &serde_json::Value, zone: &ZoneConfig) -> Option<Zone
         _ => return None, // Not a file access method — allow through
     };
 
-    if zone.is_allowed(&path) {
+    if zone.is_path_allowed(&path, paths) {
         None // Path is within the zone — allow
     } else {
-        let session_id = v
-            .get("params")
-            .and_then(|p| p.get("sessionId"))
-            .and_then(|p| p.as_str())
-            .map(|s| s.to_string());
         Some(ZoneViolation {
             path,
             action: action_str.to_string(),
-            session_id,
         })
     }
 }
 
+/// A parsed `session/request_permission` request.
+struct PermissionRequest<'a> {
+    id: &'a serde_json::Value,
+    request: RequestPermissionRequest,
+}
+
+/// Parse a `session/request_permission` request from the agent.
+fn check_permission_violation(v: &serde_json::Value) -> Option<PermissionRequest<'_>> {
+    if v.get("method")?.as_str()? != CLIENT_METHOD_NAMES.session_request_permission {
+        return None;
+    }
+    let id = v.get("id")?;
+    match serde_json::from_value::<RequestPermissionRequest>(v.get("params")?.clone()) {
+        Ok(request) => Some(PermissionRequest { id, request }),
+        Err(e) => {
+            warn!(error = %e, "failed to deserialize RequestPermissionRequest");
+            None
+        }
+    }
+}
+
+/// Pick the outcome used to deny a permission request: the "reject once"
+/// option if offered, then "reject always", otherwise cancel.
+fn reject_outcome(options: &[PermissionOption]) -> RequestPermissionOutcome {
+    [
+        PermissionOptionKind::RejectOnce,
+        PermissionOptionKind::RejectAlways,
+    ]
+    .iter()
+    .find_map(|kind| options.iter().find(|o| o.kind == *kind))
+    .map(|o| {
+        RequestPermissionOutcome::Selected(SelectedPermissionOutcome::new(o.option_id.clone()))
+    })
+    .unwrap_or(RequestPermissionOutcome::Cancelled)
+}
+
+/// Check a `session/update` tool call (or tool call update) for out-of-zone
+/// locations and diff targets.
+///
+/// Returns the tool call ID and its violations, or `None` if the message is
+/// not a tool call or touches only in-zone paths.
+fn check_tool_call_violations(
+    v: &serde_json::Value,
+    zone: &CompiledZone,
+    paths: &PathResolver,
+) -> Option<(String, Vec<ZoneViolation>)> {
+    if v.get("method")?.as_str()? != CLIENT_METHOD_NAMES.session_update {
+        return None;
+    }
+    let update = v.get("params")?.get("update")?;
+    let kind = update.get("sessionUpdate")?.as_str()?;
+    if kind != "tool_call" && kind != "tool_call_update" {
+        return None;
+    }
+    let notif = serde_json::from_value::<SessionNotification>(v.get("params")?.clone()).ok()?;
+    let (tool_call_id, violations) = match &notif.update {
+        SessionUpdate::ToolCall(tc) => (
+            tc.tool_call_id.0.to_string(),
+            tool_call_violations(
+                Some(&tc.kind),
+                Some(&tc.locations),
+                Some(&tc.content),
+                zone,
+                paths,
+            ),
+        ),
+        SessionUpdate::ToolCallUpdate(tcu) => (
+            tcu.tool_call_id.0.to_string(),
+            tool_call_violations(
+                tcu.fields.kind.as_ref(),
+                tcu.fields.locations.as_deref(),
+                tcu.fields.content.as_deref(),
+                zone,
+                paths,
+            ),
+        ),
+        _ => return None,
+    };
+    if violations.is_empty() {
+        None
+    } else {
+        Some((tool_call_id, violations))
+    }
+}
+
+/// Collect out-of-zone paths from a tool call's locations and diffs.
+///
+/// Locations take their action from the tool kind; diffs are always writes.
+fn tool_call_violations(
+    kind: Option<&ToolKind>,
+    locations: Option<&[ToolCallLocation]>,
+    content: Option<&[ToolCallContent]>,
+    zone: &CompiledZone,
+    paths: &PathResolver,
+) -> Vec<ZoneViolation> {
+    let action = match kind.map(extract::tool_kind_to_action) {
+        Some(Action::Write | Action::Delete | Action::Move) => "write",
+        Some(Action::Search) => "search",
+        _ => "read",
+    };
+    let mut violations = Vec::new();
+    for loc in locations.unwrap_or_default() {
+        let path = loc.path.to_string_lossy().to_string();
+        if !zone.is_path_allowed(&path, paths) {
+            violations.push(ZoneViolation {
+                path,
+                action: action.to_string(),
+            });
+        }
+    }
+    for item in content.unwrap_or_default() {
+        if let ToolCallContent::Diff(diff) = item {
+            let path = diff.path.to_string_lossy().to_string();
+            if !zone.is_path_allowed(&path, paths) {
+                violations.push(ZoneViolation {
+                    path,
+                    action: "write".to_string(),
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Remove out-of-zone paths from a search (or execute) tool call before it
+/// reaches the editor: drops out-of-zone `locations` and result lines that
+/// start with an out-of-zone path.
+///
+/// `kind` is the tool call's kind as recorded from its initial `tool_call`,
+/// since later updates usually omit it. Text blocks that lost no lines are
+/// left untouched; the others keep their original line endings.
+///
+/// Returns the rewritten line (with trailing newline), or `None` if nothing
+/// was redacted.
+fn redact_search_results(
+    v: &serde_json::Value,
+    kind: Option<&str>,
+    zone: &CompiledZone,
+    paths: &PathResolver,
+) -> Option<String> {
+    if v.get("method")?.as_str()? != CLIENT_METHOD_NAMES.session_update {
+        return None;
+    }
+    if !matches!(kind, Some("search" | "execute")) {
+        return None;
+    }
+
+    let mut v = v.clone();
+    let update = v.get_mut("params")?.get_mut("update")?;
+    let mut redacted = 0;
+
+    if let Some(locations) = update.get_mut("locations").and_then(|l| l.as_array_mut()) {
+        let before = locations.len();
+        locations.retain(|loc| {
+            loc.get("path")
+                .and_then(|p| p.as_str())
+                .map(|p| zone.is_path_allowed(p, paths))
+                .unwrap_or(true)
+        });
+        redacted += before - locations.len();
+    }
+
+    if let Some(content) = update.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for item in content.iter_mut() {
+            if item.get("type").and_then(|t| t.as_str()) != Some("content") {
+                continue;
+            }
+            let Some(block) = item.get_mut("content") else {
+                continue;
+            };
+            if block.get("type").and_then(|t| t.as_str()) != Some("text") {
+                continue;
+            }
+            let Some(text) = block.get("text").and_then(|t| t.as_str()) else {
+                continue;
+            };
+            let mut kept = String::with_capacity(text.len());
+            let mut dropped = 0;
+            for text_line in text.split_inclusive('\n') {
+                match extract::extract_path_from_line(text_line.trim()) {
+                    Some(path) if !zone.is_path_allowed(&path, paths) => dropped += 1,
+                    _ => kept.push_str(text_line),
+                }
+            }
+            if dropped == 0 {
+                continue;
+            }
+            // Dropping an unterminated last line leaves the new last line
+            // with a terminator the text didn't end with.
+            if !text.ends_with('\n') {
+                if let Some(rest) = kept.strip_suffix('\n') {
+                    let len = rest.strip_suffix('\r').unwrap_or(rest).len();
+                    kept.truncate(len);
+                }
+            }
+            redacted += dropped;
+            block["text"] = serde_json::Value::String(kept);
+        }
+    }
+
+    if redacted == 0 {
+        return None;
+    }
+    debug!(redacted, "redacted out-of-zone search results");
+    serde_json::to_string(&v).ok().map(|s| s + "\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ZoneConfig;
 
     /// Test that check_zone_violation blocks reads outside zone.
     #[test]
     fn test_zone_blocks_read_outside() {
-        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()]);
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
         let msg = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 1,
             "method": "fs/read_text_file",
             "params": {"path": "/workspace/core/auth.rs", "sessionId": "s1"}
         });
-        let result = check_zone_violation(&msg, &zone);
+        let result = check_zone_violation(&msg, &zone, &PathResolver::default());
         assert!(result.is_some());
         let v = result.unwrap();
         assert_eq!(v.action, "read");
@@ -259,53 +868,373 @@ mod tests {
     /// Test that check_zone_violation allows reads inside zone.
     #[test]
     fn test_zone_allows_read_inside() {
-        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()]);
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
         let msg = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 2,
             "method": "fs/read_text_file",
             "params": {"path": "src/ui/components/button.tsx", "sessionId": "s1"}
         });
-        assert!(check_zone_violation(&msg, &zone).is_none());
+        assert!(check_zone_violation(&msg, &zone, &PathResolver::default()).is_none());
     }
 
     /// Test that check_zone_violation blocks writes outside zone.
     #[test]
     fn test_zone_blocks_write_outside() {
-        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()]);
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
         let msg = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 3,
             "method": "fs/write_text_file",
             "params": {"path": "core/src/proxy.rs", "content": "hello", "sessionId": "s1"}
         });
-        let result = check_zone_violation(&msg, &zone);
+        let result = check_zone_violation(&msg, &zone, &PathResolver::default());
         assert!(result.is_some());
         assert_eq!(result.unwrap().action, "write");
     }
 
+    /// Absolute paths are resolved against the workspace root; `..`
+    /// traversal out of the zone is blocked.
+    #[test]
+    fn test_zone_resolves_paths_against_workspace_root() {
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
+        let paths = PathResolver::new(Some(std::path::PathBuf::from("/workspace")));
+        let read = |path: &str| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "fs/read_text_file",
+                "params": {"path": path, "sessionId": "s1"}
+            })
+        };
+        assert!(check_zone_violation(&read("/workspace/src/ui/a.ts"), &zone, &paths).is_none());
+        assert!(check_zone_violation(&read("./src/ui/a.ts"), &zone, &paths).is_none());
+        for escape in [
+            "/workspace/src/ui/../../core/auth.rs",
+            "src/ui/../../../etc/passwd",
+            "/elsewhere/src/ui/a.ts",
+        ] {
+            let result = check_zone_violation(&read(escape), &zone, &paths);
+            assert_eq!(result.expect(escape).path, escape);
+        }
+    }
+
     /// Test that non-file methods are not blocked.
     #[test]
     fn test_zone_ignores_non_file_methods() {
-        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()]);
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
         let msg = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 4,
             "method": "session/update",
             "params": {"sessionId": "s1"}
         });
-        assert!(check_zone_violation(&msg, &zone).is_none());
+        assert!(check_zone_violation(&msg, &zone, &PathResolver::default()).is_none());
+    }
+
+    /// Only file system and permission requests (plus tool call updates)
+    /// are looked up against the zone.
+    #[test]
+    fn test_zone_checked_methods() {
+        let msg = |method: &str| serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": method});
+        assert!(is_zone_checked_method(&msg("fs/read_text_file")));
+        assert!(is_zone_checked_method(&msg("session/request_permission")));
+        assert!(!is_zone_checked_method(&msg("session/update")));
+        assert!(!is_zone_checked_method(&msg("terminal/create")));
+        assert!(!is_zone_checked_method(
+            &serde_json::json!({"id": 1, "result": {}})
+        ));
+    }
+
+    fn hot_zone_tracker(root: &std::path::Path) -> Arc<Mutex<ContextTracker>> {
+        let mut tracker = ContextTracker::new(crate::types::TrackerConfig::default());
+        tracker.set_session_id("s1".to_string());
+        tracker.set_workspace_root(root.to_path_buf());
+        tracker.set_hot_zone("s1", &["src/pinned.rs".to_string()]);
+        Arc::new(Mutex::new(tracker))
+    }
+
+    const PROMPT_LINE: &str = r#"{"jsonrpc":"2.0","id":7,"method":"session/prompt","params":{"sessionId":"s1","prompt":[{"type":"text","text":"go"}]}}"#;
+
+    /// Pending hot-zone files are appended as embedded resources.
+    #[tokio::test]
+    async fn test_hot_zone_injected_into_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/pinned.rs"), "fn pinned() {}").unwrap();
+        let tracker = hot_zone_tracker(dir.path());
+        tracker.lock().await.set_embedded_context(true);
+
+        let out = inject_hot_zone(PROMPT_LINE, &tracker, &Arc::default())
+            .await
+            .expect("prompt rewritten");
+        assert!(out.ends_with('\n'));
+        let v: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+        let prompt = v["params"]["prompt"].as_array().unwrap();
+        assert_eq!(prompt.len(), 2);
+        assert_eq!(prompt[1]["type"], "resource");
+        assert_eq!(prompt[1]["resource"]["text"], "fn pinned() {}");
+        assert!(prompt[1]["resource"]["uri"]
+            .as_str()
+            .unwrap()
+            .ends_with("src/pinned.rs"));
+
+        let node = &tracker.lock().await.snapshot().nodes["src/pinned.rs"];
+        assert_eq!(node.last_action, Action::Refreshed);
+        assert!(node.in_context);
+
+        // Nothing left to inject on the following prompt
+        assert!(inject_hot_zone(PROMPT_LINE, &tracker, &Arc::default())
+            .await
+            .is_none());
+    }
+
+    /// Without `embeddedContext` the prompt passes through and files stay queued.
+    #[tokio::test]
+    async fn test_hot_zone_requires_embedded_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracker = hot_zone_tracker(dir.path());
+
+        assert!(inject_hot_zone(PROMPT_LINE, &tracker, &Arc::default())
+            .await
+            .is_none());
+        assert_eq!(
+            tracker.lock().await.take_pending_refresh("s1"),
+            vec!["src/pinned.rs".to_string()]
+        );
+    }
+
+    /// Pinned files outside the session's enforced zone are skipped but
+    /// stay queued.
+    #[tokio::test]
+    async fn test_hot_zone_skips_out_of_zone_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/pinned.rs"), "fn pinned() {}").unwrap();
+        let tracker = hot_zone_tracker(dir.path());
+        let key = {
+            let mut t = tracker.lock().await;
+            t.set_embedded_context(true);
+            SessionKey::new(t.agent_id(), "s1")
+        };
+        let mut registry = ZoneRegistry::default();
+        registry.set_path_resolver(PathResolver::new(Some(dir.path().to_path_buf())));
+        let docs_only = ZoneConfig::new(vec!["docs/**".to_string()]);
+        registry.set_zone(key.clone(), docs_only.compile().unwrap());
+        let zones = Arc::new(Mutex::new(registry));
+
+        assert!(inject_hot_zone(PROMPT_LINE, &tracker, &zones)
+            .await
+            .is_none());
+
+        // Warn mode lets the file through
+        zones.lock().await.set_mode(&key, ZoneMode::Warn);
+        assert!(inject_hot_zone(PROMPT_LINE, &tracker, &zones)
+            .await
+            .is_some());
+    }
+
+    /// A pinned file that can't be read stays queued for the next prompt.
+    #[tokio::test]
+    async fn test_hot_zone_requeues_unreadable_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracker = hot_zone_tracker(dir.path());
+        tracker.lock().await.set_embedded_context(true);
+
+        assert!(inject_hot_zone(PROMPT_LINE, &tracker, &Arc::default())
+            .await
+            .is_none());
+
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/pinned.rs"), "fn pinned() {}").unwrap();
+        let out = inject_hot_zone(PROMPT_LINE, &tracker, &Arc::default())
+            .await
+            .expect("prompt rewritten");
+        assert!(out.contains("fn pinned() {}"));
     }
 
     /// Test that JSON-RPC responses (no method) are not blocked.
     #[test]
     fn test_zone_ignores_responses() {
-        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()]);
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
         let msg = serde_json::json!({
             "jsonrpc": "2.0",
             "id": 5,
             "result": {"content": "hello"}
         });
-        assert!(check_zone_violation(&msg, &zone).is_none());
+        assert!(check_zone_violation(&msg, &zone, &PathResolver::default()).is_none());
+    }
+
+    /// Out-of-zone diffs in a permission request are rejected with the
+    /// "reject once" option.
+    #[test]
+    fn test_permission_request_rejected_outside_zone() {
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
+        let msg = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "session/request_permission",
+            "params": {
+                "sessionId": "s1",
+                "toolCall": {
+                    "toolCallId": "tc1",
+                    "kind": "edit",
+                    "content": [{"type": "diff", "path": "core/auth.rs", "newText": "x"}]
+                },
+                "options": [
+                    {"optionId": "allow", "name": "Allow", "kind": "allow_once"},
+                    {"optionId": "reject", "name": "Reject", "kind": "reject_once"}
+                ]
+            }
+        });
+        let denied = check_permission_violation(&msg).expect("permission request");
+        assert_eq!(denied.id, &serde_json::json!(7));
+        let fields = &denied.request.tool_call.fields;
+        let violations = tool_call_violations(
+            fields.kind.as_ref(),
+            fields.locations.as_deref(),
+            fields.content.as_deref(),
+            &zone,
+            &PathResolver::default(),
+        );
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "core/auth.rs");
+        assert_eq!(violations[0].action, "write");
+
+        let outcome = serde_json::to_value(RequestPermissionResponse::new(reject_outcome(
+            &denied.request.options,
+        )))
+        .unwrap();
+        assert_eq!(outcome["outcome"]["outcome"], "selected");
+        assert_eq!(outcome["outcome"]["optionId"], "reject");
+    }
+
+    /// Without a reject option the permission request is cancelled.
+    #[test]
+    fn test_reject_outcome_falls_back_to_cancelled() {
+        let options = vec![PermissionOption::new(
+            "allow",
+            "Allow",
+            PermissionOptionKind::AllowOnce,
+        )];
+        assert_eq!(
+            reject_outcome(&options),
+            RequestPermissionOutcome::Cancelled
+        );
+    }
+
+    /// A prompt response drops its session's unfinished tool calls.
+    #[test]
+    fn test_prompt_response_ends_pending_tool_calls() {
+        let mut t = ContextTracker::new(crate::types::TrackerConfig::default());
+        t.set_session_id("s1".to_string());
+        t.record_prompt_request(7, "s2");
+        let mut tool_calls = ToolCalls::default();
+        tool_calls.state("tc1", "s1");
+        tool_calls.state("tc2", "s2").kind = Some("search".to_string());
+
+        let cancelled =
+            serde_json::json!({"jsonrpc": "2.0", "id": 7, "result": {"stopReason": "cancelled"}});
+        let session_id = prompt_response_session(&cancelled, &t).unwrap();
+        assert_eq!(session_id, "s2");
+        tool_calls.end_turn(&session_id);
+        assert!(tool_calls.kind("tc2").is_none());
+        assert!(tool_calls.0.contains_key("tc1"));
+
+        // Unknown prompts end the default session's turn
+        let unknown =
+            serde_json::json!({"jsonrpc": "2.0", "id": 8, "result": {"stopReason": "end_turn"}});
+        assert_eq!(prompt_response_session(&unknown, &t).as_deref(), Some("s1"));
+        let other = serde_json::json!({"jsonrpc": "2.0", "id": 9, "result": {}});
+        assert!(prompt_response_session(&other, &t).is_none());
+    }
+
+    /// Tool call locations outside the zone are reported after the fact.
+    #[test]
+    fn test_tool_call_locations_outside_zone() {
+        let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+            .compile()
+            .unwrap();
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc2","title":"Edit","kind":"edit","status":"in_progress","content":[],"locations":[{"path":"src/ui/app.tsx"},{"path":"core/db.rs"}]}}}"#;
+        let msg: serde_json::Value = serde_json::from_str(line).unwrap();
+        let (tool_call_id, violations) =
+            check_tool_call_violations(&msg, &zone, &PathResolver::default()).expect("violation");
+        assert_eq!(tool_call_id, "tc2");
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "core/db.rs");
+        assert_eq!(violations[0].action, "write");
+
+        let inside = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call_update","toolCallId":"tc2","locations":[{"path":"src/ui/app.tsx"}]}}}"#;
+        let msg: serde_json::Value = serde_json::from_str(inside).unwrap();
+        assert!(check_tool_call_violations(&msg, &zone, &PathResolver::default()).is_none());
+    }
+
+    /// Out-of-zone locations and result lines are stripped from search output.
+    #[test]
+    fn test_redact_search_results() {
+        let zone = ZoneConfig::new(vec!["src/**".to_string()])
+            .compile()
+            .unwrap();
+        let line = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc3","title":"Grep","kind":"search","status":"completed","content":[{"type":"content","content":{"type":"text","text":"Results:\n/src/a.rs:1: foo\n/secret/keys.rs:2: foo"}}],"locations":[{"path":"/src/a.rs"},{"path":"/secret/keys.rs"}]}}}"#;
+        let msg: serde_json::Value = serde_json::from_str(line).unwrap();
+        let out = redact_search_results(&msg, Some("search"), &zone, &PathResolver::default())
+            .expect("redacted");
+        let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+        let update = &v["params"]["update"];
+        assert_eq!(update["locations"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            update["content"][0]["content"]["text"],
+            "Results:\n/src/a.rs:1: foo"
+        );
+
+        // Nothing to redact: the line passes through unchanged
+        let clean = ZoneConfig::new(vec!["**".to_string()]).compile().unwrap();
+        assert!(
+            redact_search_results(&msg, Some("search"), &clean, &PathResolver::default()).is_none()
+        );
+    }
+
+    /// Updates without a `kind` are redacted using the kind recorded from
+    /// the initial tool call; untouched blocks and line endings are kept.
+    #[test]
+    fn test_redact_tool_call_update_without_kind() {
+        let zone = ZoneConfig::new(vec!["src/**".to_string()])
+            .compile()
+            .unwrap();
+        let start = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call","toolCallId":"tc4","title":"Grep","kind":"search","status":"pending"}}}"#;
+        let start: serde_json::Value = serde_json::from_str(start).unwrap();
+        let event = tool_call_event(&start).expect("tool call");
+        assert_eq!((event.id, event.kind), ("tc4", Some("search")));
+
+        let update = r#"{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s1","update":{"sessionUpdate":"tool_call_update","toolCallId":"tc4","status":"completed","content":[{"type":"content","content":{"type":"text","text":"/src/a.rs:1: foo\r\n/secret/keys.rs:2: foo\r\n/src/b.rs:3: foo\r\n"}},{"type":"content","content":{"type":"text","text":"done\n"}}]}}}"#;
+        let msg: serde_json::Value = serde_json::from_str(update).unwrap();
+        let event = tool_call_event(&msg).expect("tool call update");
+        assert_eq!(event.kind, None);
+        assert_eq!(event.status, Some("completed"));
+
+        // Without the recorded kind nothing is redacted
+        assert!(redact_search_results(&msg, None, &zone, &PathResolver::default()).is_none());
+
+        let out = redact_search_results(&msg, Some("search"), &zone, &PathResolver::default())
+            .expect("redacted");
+        let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+        let content = &v["params"]["update"]["content"];
+        assert_eq!(
+            content[0]["content"]["text"],
+            "/src/a.rs:1: foo\r\n/src/b.rs:3: foo\r\n"
+        );
+        assert_eq!(content[1]["content"]["text"], "done\n");
     }
 }
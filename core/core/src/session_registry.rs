@@ -1,127 +1,49 @@
 use std::collections::HashMap;
-use std::fs;
 use std::path::PathBuf;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
 use tracing::{debug, warn};
 
+use crate::clock::{self, SharedClock};
+use crate::session_store::{self, SessionStore, StoredRegistry};
 use crate::types::{SessionKey, SessionMode, SessionModel, SessionState, SessionSummary};
 
-const DEFAULT_DIR_NAME: &str = ".eisen";
-const DEFAULT_FILE_NAME: &str = "core_sessions.json";
-
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
-}
-
-fn default_eisen_dir() -> PathBuf {
-    if let Ok(dir) = std::env::var("EISEN_DIR") {
-        return PathBuf::from(dir);
-    }
-    if let Ok(home) = std::env::var("HOME") {
-        return PathBuf::from(home).join(DEFAULT_DIR_NAME);
-    }
-    if let Ok(home) = std::env::var("USERPROFILE") {
-        return PathBuf::from(home).join(DEFAULT_DIR_NAME);
-    }
-    std::env::current_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join(DEFAULT_DIR_NAME)
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-struct StoredRegistry {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    active: Option<SessionKey>,
-    #[serde(default)]
-    sessions: Vec<SessionState>,
-}
-
-#[derive(Debug, Clone)]
-struct SessionStore {
-    path: PathBuf,
-}
-
-impl SessionStore {
-    fn new(path: PathBuf) -> Self {
-        Self { path }
-    }
-
-    fn default_path() -> PathBuf {
-        default_eisen_dir().join(DEFAULT_FILE_NAME)
-    }
-
-    fn load(&self) -> Result<StoredRegistry> {
-        if !self.path.exists() {
-            return Ok(StoredRegistry::default());
-        }
-        let start = Instant::now();
-        let raw = fs::read_to_string(&self.path)
-            .with_context(|| format!("failed to read session store {}", self.path.display()))?;
-        let parsed = serde_json::from_str(&raw)
-            .with_context(|| format!("failed to parse session store {}", self.path.display()))?;
-        debug!(
-            path = %self.path.display(),
-            elapsed_ms = start.elapsed().as_millis(),
-            "loaded session registry"
-        );
-        Ok(parsed)
-    }
-
-    fn save(&self, data: &StoredRegistry) -> Result<()> {
-        let start = Instant::now();
-        if let Some(parent) = self.path.parent() {
-            fs::create_dir_all(parent).with_context(|| {
-                format!("failed to create session store dir {}", parent.display())
-            })?;
-        }
-        let serialized =
-            serde_json::to_string_pretty(data).context("failed to serialize session registry")?;
-        let tmp_path = self.path.with_extension("json.tmp");
-        fs::write(&tmp_path, serialized).with_context(|| {
-            format!("failed to write temp session store {}", tmp_path.display())
-        })?;
-        if self.path.exists() {
-            let _ = fs::remove_file(&self.path);
-        }
-        fs::rename(&tmp_path, &self.path).with_context(|| {
-            format!(
-                "failed to move session store {} -> {}",
-                tmp_path.display(),
-                self.path.display()
-            )
-        })?;
-        debug!(
-            path = %self.path.display(),
-            elapsed_ms = start.elapsed().as_millis(),
-            "saved session registry"
-        );
-        Ok(())
-    }
-}
+/// How often `watch` polls the store for changes from other processes.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct SessionRegistry {
     sessions: HashMap<SessionKey, SessionState>,
     active: Option<SessionKey>,
-    store: SessionStore,
+    store: Box<dyn SessionStore>,
+    /// Source of `created_at_ms` / `updated_at_ms` / `resumed_at_ms`.
+    clock: SharedClock,
 }
 
 impl SessionRegistry {
-    pub fn load_default() -> Self {
-        Self::load(SessionStore::new(SessionStore::default_path()))
+    /// Open the default store (SQLite unless `EISEN_SESSION_STORE=json`).
+    ///
+    /// A database that can't be opened (newer schema, locked, corrupt) is
+    /// an error rather than a reason to fall back to the JSON file: the
+    /// JSON file may already have been migrated away, and other processes
+    /// would never see sessions written to it.
+    pub fn load_default() -> Result<Self> {
+        let store = session_store::open_default().context("failed to open session store")?;
+        Ok(Self::with_store(store))
     }
 
-    pub fn load_from_path(path: PathBuf) -> Self {
-        Self::load(SessionStore::new(path))
+    /// Open the store at `path`: JSON for `.json` files, SQLite otherwise.
+    /// Fails like `load_default` if it can't be opened.
+    pub fn load_from_path(path: PathBuf) -> Result<Self> {
+        let store = session_store::open_path(path.clone())
+            .with_context(|| format!("failed to open session store {}", path.display()))?;
+        Ok(Self::with_store(store))
     }
 
-    fn load(store: SessionStore) -> Self {
+    pub fn with_store(store: Box<dyn SessionStore>) -> Self {
         let stored = match store.load() {
             Ok(data) => data,
             Err(err) => {
@@ -137,15 +59,30 @@ impl SessionRegistry {
             sessions,
             active: stored.active,
             store,
+            clock: clock::system(),
         }
     }
 
-    fn persist(&self) -> Result<()> {
-        let stored = StoredRegistry {
-            active: self.active.clone(),
-            sessions: self.sessions.values().cloned().collect(),
-        };
-        self.store.save(&stored)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Reload from the store if another process changed it. Every local
+    /// change is written through immediately, so nothing is lost by
+    /// replacing the in-memory state. Returns true if a reload happened.
+    pub fn refresh(&mut self) -> Result<bool> {
+        if !self.store.changed_externally()? {
+            return Ok(false);
+        }
+        let stored = self.store.load()?;
+        self.sessions = stored
+            .sessions
+            .into_iter()
+            .map(|session| (session.key(), session))
+            .collect();
+        self.active = stored.active;
+        debug!(sessions = self.sessions.len(), "session registry refreshed");
+        Ok(true)
     }
 
     pub fn list_sessions(&self, agent_id: Option<&str>) -> Vec<SessionSummary> {
@@ -159,6 +96,7 @@ impl SessionRegistry {
                 mode: session.mode,
                 model: session.model.clone(),
                 updated_at_ms: session.updated_at_ms,
+                resumed_at_ms: session.resumed_at_ms,
                 is_active: self
                     .active
                     .as_ref()
@@ -166,7 +104,7 @@ impl SessionRegistry {
                     .unwrap_or(false),
             })
             .collect();
-        sessions.sort_by(|a, b| b.updated_at_ms.cmp(&a.updated_at_ms));
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.updated_at_ms));
         sessions
     }
 
@@ -183,7 +121,7 @@ impl SessionRegistry {
         providers: Option<Vec<SessionKey>>,
     ) -> Result<SessionState> {
         let key = SessionKey::new(&agent_id, &session_id);
-        let now = now_ms();
+        let now = self.clock.now_ms();
         let entry = self
             .sessions
             .entry(key.clone())
@@ -198,6 +136,8 @@ impl SessionRegistry {
                 providers: providers.clone().unwrap_or_default(),
                 created_at_ms: now,
                 updated_at_ms: now,
+                resumed_at_ms: None,
+                spend: None,
             });
 
         entry.mode = mode;
@@ -222,7 +162,36 @@ impl SessionRegistry {
         entry.updated_at_ms = now;
 
         let result = entry.clone();
-        self.persist()?;
+        self.store.put_session(&result)?;
+        Ok(result)
+    }
+
+    /// Record that the agent reattached to a session (`session/load` or
+    /// `session/resume`). Sessions the registry hasn't seen yet are added as
+    /// single-agent sessions.
+    pub fn mark_resumed(&mut self, key: &SessionKey) -> Result<SessionState> {
+        let now = self.clock.now_ms();
+        let session = self
+            .sessions
+            .entry(key.clone())
+            .or_insert_with(|| SessionState {
+                agent_id: key.agent_id.clone(),
+                session_id: key.session_id.clone(),
+                mode: SessionMode::SingleAgent,
+                model: None,
+                history: Vec::new(),
+                summary: None,
+                context: Vec::new(),
+                providers: Vec::new(),
+                created_at_ms: now,
+                updated_at_ms: now,
+                resumed_at_ms: None,
+                spend: None,
+            });
+        session.resumed_at_ms = Some(now);
+        session.updated_at_ms = now;
+        let result = session.clone();
+        self.store.put_session(&result)?;
         Ok(result)
     }
 
@@ -232,7 +201,7 @@ impl SessionRegistry {
             self.active = None;
         }
         if removed {
-            self.persist()?;
+            self.store.delete_session(key)?;
         }
         Ok(removed)
     }
@@ -241,8 +210,8 @@ impl SessionRegistry {
         if !self.sessions.contains_key(&key) {
             return Ok(false);
         }
+        self.store.set_active(Some(&key))?;
         self.active = Some(key);
-        self.persist()?;
         Ok(true)
     }
 
@@ -267,7 +236,7 @@ impl SessionRegistry {
         key: &SessionKey,
         providers: Vec<SessionKey>,
     ) -> Result<Option<SessionState>> {
-        let now = now_ms();
+        let now = self.clock.now_ms();
         let Some(session) = self.sessions.get_mut(key) else {
             return Ok(None);
         };
@@ -275,7 +244,7 @@ impl SessionRegistry {
         session.mode = SessionMode::Orchestrator;
         session.updated_at_ms = now;
         let result = session.clone();
-        self.persist()?;
+        self.store.put_session(&result)?;
         Ok(Some(result))
     }
 
@@ -284,7 +253,7 @@ impl SessionRegistry {
         key: &SessionKey,
         items: Vec<serde_json::Value>,
     ) -> Result<Option<SessionState>> {
-        let now = now_ms();
+        let now = self.clock.now_ms();
         let Some(session) = self.sessions.get_mut(key) else {
             return Ok(None);
         };
@@ -293,11 +262,22 @@ impl SessionRegistry {
         }
         session.updated_at_ms = now;
         let result = session.clone();
-        self.persist()?;
+        self.store.put_session(&result)?;
         Ok(Some(result))
     }
 }
 
+/// Keep a shared registry in sync with changes made by other processes.
+pub async fn watch(registry: Arc<Mutex<SessionRegistry>>) {
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = registry.lock().await.refresh() {
+            warn!(error = %err, "failed to refresh session registry");
+        }
+    }
+}
+
 impl SessionKey {
     fn matches(&self, session: &SessionState) -> bool {
         self.agent_id == session.agent_id && self.session_id == session.session_id
@@ -307,13 +287,14 @@ impl SessionKey {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::ManualClock;
     use crate::types::{SessionKey, SessionMode};
     use tempfile::tempdir;
 
     fn test_registry() -> (SessionRegistry, tempfile::TempDir) {
         let dir = tempdir().unwrap();
         let path = dir.path().join("core_sessions.json");
-        (SessionRegistry::load_from_path(path), dir)
+        (SessionRegistry::load_from_path(path).unwrap(), dir)
     }
 
     #[test]
@@ -359,4 +340,90 @@ mod tests {
         let sessions = registry.list_sessions(None);
         assert!(sessions[0].is_active);
     }
+
+    #[test]
+    fn mark_resumed_records_timestamp() {
+        let (mut registry, dir) = test_registry();
+        let clock = ManualClock::new(42);
+        registry.set_clock(clock.shared());
+        let key = SessionKey::new("agent-a", "sess-1");
+        assert!(registry.list_sessions(None).is_empty());
+
+        // Unknown sessions are registered on resume
+        let session = registry.mark_resumed(&key).unwrap();
+        assert_eq!(session.mode, SessionMode::SingleAgent);
+        assert_eq!(session.created_at_ms, 42);
+        let resumed_at = session.resumed_at_ms.unwrap();
+        assert_eq!(resumed_at, 42);
+        assert_eq!(
+            registry.list_sessions(None)[0].resumed_at_ms,
+            Some(resumed_at)
+        );
+
+        let reloaded =
+            SessionRegistry::load_from_path(dir.path().join("core_sessions.json")).unwrap();
+        assert_eq!(
+            reloaded.get_session_state(&key).unwrap().resumed_at_ms,
+            Some(resumed_at)
+        );
+    }
+
+    fn create(registry: &mut SessionRegistry, agent_id: &str) {
+        registry
+            .create_session(
+                agent_id.to_string(),
+                "sess-1".to_string(),
+                SessionMode::SingleAgent,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn registries_sharing_a_store_see_each_other() {
+        for file in ["core_sessions.json", "core_sessions.db"] {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join(file);
+            let mut a = SessionRegistry::load_from_path(path.clone()).unwrap();
+            let mut b = SessionRegistry::load_from_path(path).unwrap();
+
+            create(&mut a, "agent-a");
+            create(&mut b, "agent-b");
+            assert!(a.refresh().unwrap(), "{file}");
+            assert_eq!(a.list_sessions(None).len(), 2, "{file}");
+            assert!(!a.refresh().unwrap(), "{file}");
+
+            assert!(b.refresh().unwrap(), "{file}");
+            assert!(b
+                .close_session(&SessionKey::new("agent-a", "sess-1"))
+                .unwrap());
+            assert!(a.refresh().unwrap(), "{file}");
+            assert_eq!(a.list_sessions(None).len(), 1, "{file}");
+        }
+    }
+
+    #[test]
+    fn newer_schema_fails_instead_of_falling_back() {
+        let dir = tempdir().unwrap();
+        let db = dir.path().join("core_sessions.db");
+        let json = dir.path().join("core_sessions.json");
+        std::fs::write(&json, r#"{"sessions":[]}"#).unwrap();
+        rusqlite::Connection::open(&db)
+            .unwrap()
+            .pragma_update(None, "user_version", 99)
+            .unwrap();
+
+        let err = SessionRegistry::load_from_path(db).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("newer than supported"),
+            "{err:#}"
+        );
+        // Nothing was migrated away or written to a second store
+        assert!(json.exists());
+        assert!(!dir.path().join("core_sessions.json.migrated").exists());
+    }
 }
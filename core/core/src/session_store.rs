@@ -0,0 +1,589 @@
+//! Storage backends for `SessionRegistry`.
+//!
+//! The registry keeps every session in memory and tells its store about
+//! each change as it happens, so backends can write incrementally instead of
+//! rewriting everything:
+//!
+//! - `SqliteSessionStore` (default, `~/.eisen/core_sessions.db`): one row
+//!   per session in WAL mode. Each change is a single-statement write, and
+//!   SQLite's file locking (with a busy timeout) serializes writers, so
+//!   several `eisen-core` processes can share the database. On first open
+//!   it imports an existing `core_sessions.json`.
+//! - `JsonSessionStore` (`~/.eisen/core_sessions.json`): the original
+//!   single-file format, selected with `EISEN_SESSION_STORE=json`. Each
+//!   change is a read-merge-write cycle under an advisory lock on
+//!   `core_sessions.json.lock`, so concurrent processes only ever replace
+//!   the sessions they changed.
+//!
+//! Both backends report changes made by other processes through
+//! `changed_externally`, which `SessionRegistry::refresh` polls.
+
+use std::cell::Cell;
+use std::fs::{self, File, OpenOptions};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::types::{SessionKey, SessionState};
+
+pub(crate) const DEFAULT_DIR_NAME: &str = ".eisen";
+pub(crate) const JSON_FILE_NAME: &str = "core_sessions.json";
+pub(crate) const SQLITE_FILE_NAME: &str = "core_sessions.db";
+
+/// How long a writer waits for another process holding the database lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Current SQLite schema version (`PRAGMA user_version`).
+const SCHEMA_VERSION: i64 = 1;
+
+pub(crate) fn default_eisen_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("EISEN_DIR") {
+        return PathBuf::from(dir);
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return PathBuf::from(home).join(DEFAULT_DIR_NAME);
+    }
+    if let Ok(home) = std::env::var("USERPROFILE") {
+        return PathBuf::from(home).join(DEFAULT_DIR_NAME);
+    }
+    std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join(DEFAULT_DIR_NAME)
+}
+
+/// Everything a store holds: the sessions and the active session.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct StoredRegistry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<SessionKey>,
+    #[serde(default)]
+    pub sessions: Vec<SessionState>,
+}
+
+/// Persistence backend for `SessionRegistry`.
+pub trait SessionStore: std::fmt::Debug + Send {
+    /// Load everything; called once when the registry is created.
+    fn load(&self) -> Result<StoredRegistry>;
+    /// Insert or replace one session.
+    fn put_session(&self, session: &SessionState) -> Result<()>;
+    /// Remove one session (and clear it as active if it was).
+    fn delete_session(&self, key: &SessionKey) -> Result<()>;
+    /// Record the active session.
+    fn set_active(&self, key: Option<&SessionKey>) -> Result<()>;
+    /// Whether another process changed the store since this handle last
+    /// loaded or wrote it.
+    fn changed_externally(&self) -> Result<bool>;
+}
+
+// ---------------------------------------------------------------------------
+// JSON file
+// ---------------------------------------------------------------------------
+
+/// Whole registry in one pretty-printed JSON file. Every change locks the
+/// store, re-reads the file, applies the change and atomically replaces it.
+#[derive(Debug, Clone)]
+pub struct JsonSessionStore {
+    path: PathBuf,
+    /// Modification time of the file as last read or written through this
+    /// handle, for `changed_externally`.
+    seen: Cell<Option<SystemTime>>,
+}
+
+impl JsonSessionStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            seen: Cell::new(None),
+        }
+    }
+
+    pub fn default_path() -> PathBuf {
+        default_eisen_dir().join(JSON_FILE_NAME)
+    }
+
+    fn save(&self, data: &StoredRegistry) -> Result<()> {
+        let start = Instant::now();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create session store dir {}", parent.display())
+            })?;
+        }
+        let serialized =
+            serde_json::to_string_pretty(data).context("failed to serialize session registry")?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serialized).with_context(|| {
+            format!("failed to write temp session store {}", tmp_path.display())
+        })?;
+        if self.path.exists() {
+            let _ = fs::remove_file(&self.path);
+        }
+        fs::rename(&tmp_path, &self.path).with_context(|| {
+            format!(
+                "failed to move session store {} -> {}",
+                tmp_path.display(),
+                self.path.display()
+            )
+        })?;
+        debug!(
+            path = %self.path.display(),
+            elapsed_ms = start.elapsed().as_millis(),
+            "saved session registry"
+        );
+        Ok(())
+    }
+
+    /// Take the advisory lock guarding the file; released when the
+    /// returned handle is dropped. The data file itself can't be locked
+    /// since `save` replaces it.
+    fn lock(&self, exclusive: bool) -> Result<File> {
+        let lock_path = self.path.with_extension("json.lock");
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create session store dir {}", parent.display())
+            })?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("failed to open lock file {}", lock_path.display()))?;
+        if exclusive {
+            file.lock()
+        } else {
+            file.lock_shared()
+        }
+        .with_context(|| format!("failed to lock {}", lock_path.display()))?;
+        Ok(file)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    fn read(&self) -> Result<StoredRegistry> {
+        if !self.path.exists() {
+            return Ok(StoredRegistry::default());
+        }
+        let start = Instant::now();
+        let raw = fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read session store {}", self.path.display()))?;
+        let parsed = serde_json::from_str(&raw)
+            .with_context(|| format!("failed to parse session store {}", self.path.display()))?;
+        debug!(
+            path = %self.path.display(),
+            elapsed_ms = start.elapsed().as_millis(),
+            "loaded session registry"
+        );
+        Ok(parsed)
+    }
+
+    /// Read-merge-write: apply `f` to the current file contents, keeping
+    /// whatever other processes wrote in the meantime.
+    fn update(&self, f: impl FnOnce(&mut StoredRegistry)) -> Result<()> {
+        let _lock = self.lock(true)?;
+        // If another process wrote since we last looked, the merged file
+        // holds changes we haven't loaded; leave them flagged for refresh.
+        let stale = self.modified() != self.seen.get();
+        let mut data = self.read()?;
+        f(&mut data);
+        self.save(&data)?;
+        if !stale {
+            self.seen.set(self.modified());
+        }
+        Ok(())
+    }
+}
+
+impl SessionStore for JsonSessionStore {
+    fn load(&self) -> Result<StoredRegistry> {
+        let _lock = self.lock(false)?;
+        let data = self.read()?;
+        self.seen.set(self.modified());
+        Ok(data)
+    }
+
+    fn put_session(&self, session: &SessionState) -> Result<()> {
+        self.update(|data| {
+            let key = session.key();
+            match data.sessions.iter_mut().find(|s| s.key() == key) {
+                Some(existing) => *existing = session.clone(),
+                None => data.sessions.push(session.clone()),
+            }
+        })
+    }
+
+    fn delete_session(&self, key: &SessionKey) -> Result<()> {
+        self.update(|data| {
+            data.sessions.retain(|s| &s.key() != key);
+            if data.active.as_ref() == Some(key) {
+                data.active = None;
+            }
+        })
+    }
+
+    fn set_active(&self, key: Option<&SessionKey>) -> Result<()> {
+        self.update(|data| data.active = key.cloned())
+    }
+
+    fn changed_externally(&self) -> Result<bool> {
+        Ok(self.modified() != self.seen.get())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// SQLite
+// ---------------------------------------------------------------------------
+
+/// One row per session in an embedded SQLite database (WAL mode).
+#[derive(Debug)]
+pub struct SqliteSessionStore {
+    path: PathBuf,
+    conn: Connection,
+    /// `PRAGMA data_version` as last seen; it changes whenever another
+    /// connection commits.
+    data_version: Cell<i64>,
+}
+
+impl SqliteSessionStore {
+    /// Open (or create) the database, migrating the schema and importing
+    /// `core_sessions.json` from the same directory on first use.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create session store dir {}", parent.display())
+            })?;
+        }
+        let conn = Connection::open(&path)
+            .with_context(|| format!("failed to open session store {}", path.display()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+        let mut store = Self {
+            path,
+            conn,
+            data_version: Cell::new(0),
+        };
+        store.migrate()?;
+        store.data_version.set(store.query_data_version()?);
+        Ok(store)
+    }
+
+    pub fn default_path() -> PathBuf {
+        default_eisen_dir().join(SQLITE_FILE_NAME)
+    }
+
+    fn query_data_version(&self) -> Result<i64> {
+        Ok(self
+            .conn
+            .pragma_query_value(None, "data_version", |row| row.get(0))?)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let json_path = self.path.with_file_name(JSON_FILE_NAME);
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: i64 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            bail!(
+                "session store {} has schema version {version}, newer than supported {SCHEMA_VERSION}",
+                self.path.display()
+            );
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                 agent_id      TEXT NOT NULL,
+                 session_id    TEXT NOT NULL,
+                 state         TEXT NOT NULL,
+                 updated_at_ms INTEGER NOT NULL,
+                 PRIMARY KEY (agent_id, session_id)
+             );
+             CREATE TABLE IF NOT EXISTS active_session (
+                 id         INTEGER PRIMARY KEY CHECK (id = 0),
+                 agent_id   TEXT NOT NULL,
+                 session_id TEXT NOT NULL
+             );",
+        )?;
+
+        // Import the JSON store this database replaces
+        let mut imported = 0;
+        if json_path.exists() {
+            let legacy = JsonSessionStore::new(json_path.clone()).load()?;
+            for session in &legacy.sessions {
+                insert_session(&tx, session)?;
+                imported += 1;
+            }
+            if let Some(active) = &legacy.active {
+                write_active(&tx, Some(active))?;
+            }
+        }
+
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        tx.commit()?;
+
+        if imported > 0 || json_path.exists() {
+            let migrated = json_path.with_extension("json.migrated");
+            if let Err(e) = fs::rename(&json_path, &migrated) {
+                warn!(error = %e, path = %json_path.display(), "failed to rename migrated session store");
+            }
+            info!(
+                sessions = imported,
+                from = %json_path.display(),
+                to = %self.path.display(),
+                "migrated session registry to sqlite"
+            );
+        }
+        Ok(())
+    }
+}
+
+fn insert_session(conn: &Connection, session: &SessionState) -> Result<()> {
+    let state = serde_json::to_string(session).context("failed to serialize session")?;
+    conn.execute(
+        "INSERT INTO sessions (agent_id, session_id, state, updated_at_ms)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (agent_id, session_id)
+         DO UPDATE SET state = excluded.state, updated_at_ms = excluded.updated_at_ms",
+        params![
+            session.agent_id,
+            session.session_id,
+            state,
+            session.updated_at_ms as i64
+        ],
+    )?;
+    Ok(())
+}
+
+fn write_active(conn: &Connection, key: Option<&SessionKey>) -> Result<()> {
+    match key {
+        Some(key) => conn.execute(
+            "INSERT OR REPLACE INTO active_session (id, agent_id, session_id) VALUES (0, ?1, ?2)",
+            params![key.agent_id, key.session_id],
+        )?,
+        None => conn.execute("DELETE FROM active_session", [])?,
+    };
+    Ok(())
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn load(&self) -> Result<StoredRegistry> {
+        let start = Instant::now();
+        let mut stmt = self.conn.prepare("SELECT state FROM sessions")?;
+        let mut sessions = Vec::new();
+        for row in stmt.query_map([], |row| row.get::<_, String>(0))? {
+            let raw = row?;
+            match serde_json::from_str::<SessionState>(&raw) {
+                Ok(session) => sessions.push(session),
+                Err(e) => warn!(error = %e, "skipping unreadable session row"),
+            }
+        }
+        let active = self
+            .conn
+            .query_row(
+                "SELECT agent_id, session_id FROM active_session WHERE id = 0",
+                [],
+                |row| {
+                    Ok(SessionKey::new(
+                        &row.get::<_, String>(0)?,
+                        &row.get::<_, String>(1)?,
+                    ))
+                },
+            )
+            .optional()?;
+        debug!(
+            path = %self.path.display(),
+            sessions = sessions.len(),
+            elapsed_ms = start.elapsed().as_millis(),
+            "loaded session registry"
+        );
+        Ok(StoredRegistry { active, sessions })
+    }
+
+    fn put_session(&self, session: &SessionState) -> Result<()> {
+        insert_session(&self.conn, session)
+    }
+
+    fn delete_session(&self, key: &SessionKey) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM sessions WHERE agent_id = ?1 AND session_id = ?2",
+            params![key.agent_id, key.session_id],
+        )?;
+        self.conn.execute(
+            "DELETE FROM active_session WHERE agent_id = ?1 AND session_id = ?2",
+            params![key.agent_id, key.session_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_active(&self, key: Option<&SessionKey>) -> Result<()> {
+        write_active(&self.conn, key)
+    }
+
+    fn changed_externally(&self) -> Result<bool> {
+        let version = self.query_data_version()?;
+        Ok(version != self.data_version.replace(version))
+    }
+}
+
+/// Open the store for `path`, choosing the backend by extension: `.json`
+/// uses `JsonSessionStore`, anything else SQLite.
+pub fn open_path(path: PathBuf) -> Result<Box<dyn SessionStore>> {
+    if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        Ok(Box::new(JsonSessionStore::new(path)))
+    } else {
+        Ok(Box::new(SqliteSessionStore::open(path)?))
+    }
+}
+
+/// Open the default store. `EISEN_SESSION_STORE=json` selects the JSON
+/// file; otherwise SQLite is used.
+pub fn open_default() -> Result<Box<dyn SessionStore>> {
+    match std::env::var("EISEN_SESSION_STORE").as_deref() {
+        Ok("json") => Ok(Box::new(JsonSessionStore::new(
+            JsonSessionStore::default_path(),
+        ))),
+        Ok("sqlite") | Err(_) => Ok(Box::new(SqliteSessionStore::open(
+            SqliteSessionStore::default_path(),
+        )?)),
+        Ok(other) => bail!("unknown EISEN_SESSION_STORE {other:?} (expected json or sqlite)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SessionMode;
+    use tempfile::tempdir;
+
+    fn session(agent: &str, id: &str) -> SessionState {
+        SessionState {
+            agent_id: agent.to_string(),
+            session_id: id.to_string(),
+            mode: SessionMode::SingleAgent,
+            model: None,
+            history: Vec::new(),
+            summary: None,
+            context: Vec::new(),
+            providers: Vec::new(),
+            created_at_ms: 1,
+            updated_at_ms: 1,
+            resumed_at_ms: None,
+            spend: None,
+        }
+    }
+
+    fn roundtrip(store: &dyn SessionStore) {
+        let mut s = session("agent-a", "sess-1");
+        store.put_session(&s).unwrap();
+        store.put_session(&session("agent-a", "sess-2")).unwrap();
+        s.summary = Some("updated".to_string());
+        store.put_session(&s).unwrap();
+        store
+            .set_active(Some(&SessionKey::new("agent-a", "sess-2")))
+            .unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.sessions.len(), 2);
+        let s1 = loaded
+            .sessions
+            .iter()
+            .find(|s| s.session_id == "sess-1")
+            .unwrap();
+        assert_eq!(s1.summary.as_deref(), Some("updated"));
+        assert_eq!(loaded.active, Some(SessionKey::new("agent-a", "sess-2")));
+
+        store
+            .delete_session(&SessionKey::new("agent-a", "sess-2"))
+            .unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.sessions.len(), 1);
+        assert!(loaded.active.is_none());
+    }
+
+    #[test]
+    fn json_store_roundtrip() {
+        let dir = tempdir().unwrap();
+        roundtrip(&JsonSessionStore::new(dir.path().join(JSON_FILE_NAME)));
+    }
+
+    #[test]
+    fn sqlite_store_roundtrip() {
+        let dir = tempdir().unwrap();
+        roundtrip(&SqliteSessionStore::open(dir.path().join(SQLITE_FILE_NAME)).unwrap());
+    }
+
+    #[test]
+    fn sqlite_store_imports_json() {
+        let dir = tempdir().unwrap();
+        let json = JsonSessionStore::new(dir.path().join(JSON_FILE_NAME));
+        json.put_session(&session("agent-a", "sess-1")).unwrap();
+        json.set_active(Some(&SessionKey::new("agent-a", "sess-1")))
+            .unwrap();
+
+        let store = SqliteSessionStore::open(dir.path().join(SQLITE_FILE_NAME)).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.sessions.len(), 1);
+        assert_eq!(loaded.active, Some(SessionKey::new("agent-a", "sess-1")));
+        assert!(!dir.path().join(JSON_FILE_NAME).exists());
+        assert!(dir.path().join("core_sessions.json.migrated").exists());
+
+        // Reopening does not import again
+        drop(store);
+        let store = SqliteSessionStore::open(dir.path().join(SQLITE_FILE_NAME)).unwrap();
+        assert_eq!(store.load().unwrap().sessions.len(), 1);
+    }
+
+    #[test]
+    fn json_store_merges_concurrent_writers() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(JSON_FILE_NAME);
+        let a = JsonSessionStore::new(path.clone());
+        let b = JsonSessionStore::new(path);
+        a.load().unwrap();
+        b.load().unwrap();
+
+        a.put_session(&session("agent-a", "sess-1")).unwrap();
+        assert!(!a.changed_externally().unwrap());
+        assert!(b.changed_externally().unwrap());
+
+        // b never loaded a's session, but writing its own keeps it, and
+        // a's change stays flagged until b reloads
+        b.put_session(&session("agent-b", "sess-1")).unwrap();
+        assert!(b.changed_externally().unwrap());
+        assert_eq!(b.load().unwrap().sessions.len(), 2);
+        assert!(!b.changed_externally().unwrap());
+
+        let loaded = a.load().unwrap();
+        assert_eq!(loaded.sessions.len(), 2);
+        assert!(!a.changed_externally().unwrap());
+    }
+
+    #[test]
+    fn sqlite_store_is_shared_between_connections() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SQLITE_FILE_NAME);
+        let a = SqliteSessionStore::open(path.clone()).unwrap();
+        let b = SqliteSessionStore::open(path).unwrap();
+        a.put_session(&session("agent-a", "sess-1")).unwrap();
+        b.put_session(&session("agent-b", "sess-1")).unwrap();
+        assert_eq!(a.load().unwrap().sessions.len(), 2);
+        assert_eq!(b.load().unwrap().sessions.len(), 2);
+
+        assert!(a.changed_externally().unwrap());
+        assert!(!a.changed_externally().unwrap());
+        a.set_active(Some(&SessionKey::new("agent-a", "sess-1")))
+            .unwrap();
+        assert!(!a.changed_externally().unwrap());
+        assert!(b.changed_externally().unwrap());
+    }
+}
@@ -0,0 +1,301 @@
+//! Symbol spans for attributing line-level file accesses to the functions,
+//! classes and methods they fall in.
+//!
+//! Built from the same `SymbolTree` the graph webview renders, via
+//! `flatten`, so symbol IDs (`file::Class::method`) match the UI's node IDs
+//! exactly.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::flatten::flatten_workspace;
+use crate::parser::tree::SymbolTree;
+use crate::paths::NamedRoot;
+use crate::types::LineRange;
+
+/// Separator between a file path and its symbols in a symbol ID.
+const SYMBOL_SEPARATOR: &str = "::";
+
+/// Whether a tracked path is a symbol ID rather than a file.
+pub fn is_symbol_id(path: &str) -> bool {
+    path.contains(SYMBOL_SEPARATOR)
+}
+
+/// Prefix shared by the IDs of every symbol in `file`.
+pub fn symbol_prefix(file: &str) -> String {
+    format!("{file}{SYMBOL_SEPARATOR}")
+}
+
+/// A symbol's ID and the lines it spans.
+#[derive(Debug, Clone)]
+pub struct SymbolSpan {
+    id: String,
+    lines: LineRange,
+}
+
+/// Line spans of every symbol in the workspace, keyed by the file's
+/// workspace-relative path.
+///
+/// Files that change after the index is built are marked with
+/// `invalidate`. Whoever next needs their spans claims the re-parse with
+/// `take_stale`, runs `parse_file` (off any lock) and stores the result
+/// with `set_file`; lookups use the old spans meanwhile.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    files: HashMap<String, Vec<SymbolSpan>>,
+    stale: HashSet<String>,
+}
+
+impl SymbolIndex {
+    pub fn build(tree: &SymbolTree, root: &Path) -> Self {
+        Self::build_workspace(tree, root, &[])
+    }
+
+    /// Index the primary root and each named root. Symbols under a named
+    /// root are keyed `name:file::Symbol`, matching the tracker's IDs.
+    pub fn build_workspace(
+        tree: &SymbolTree,
+        root: &Path,
+        named: &[(NamedRoot, SymbolTree)],
+    ) -> Self {
+        Self {
+            files: spans_by_file(tree, root, named),
+            stale: HashSet::new(),
+        }
+    }
+
+    /// Mark `file`'s spans as out of date, e.g. after it was written.
+    pub fn invalidate(&mut self, file: &str) {
+        self.stale.insert(file.to_string());
+    }
+
+    /// Whether `file` was invalidated since it was last parsed. Clears the
+    /// mark, so only the first caller re-parses it.
+    pub fn take_stale(&mut self, file: &str) -> bool {
+        self.stale.remove(file)
+    }
+
+    /// Replace `file`'s spans with a fresh `parse_file` result.
+    pub fn set_file(&mut self, file: &str, spans: Vec<SymbolSpan>) {
+        if spans.is_empty() {
+            self.files.remove(file);
+        } else {
+            self.files.insert(file.to_string(), spans);
+        }
+    }
+
+    /// Number of symbols indexed.
+    pub fn len(&self) -> usize {
+        self.files.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// IDs of the symbols in `file` whose span overlaps `range`, outermost
+    /// first. A method and the class around it are both included.
+    pub fn enclosing(&self, file: &str, range: LineRange) -> Vec<&str> {
+        self.files
+            .get(file)
+            .map(|spans| {
+                spans
+                    .iter()
+                    .take_while(|s| s.lines.start <= range.end)
+                    .filter(|s| s.lines.end >= range.start)
+                    .map(|s| s.id.as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Parse the symbol spans of `file` (a tracked ID) from `fs_path`. Empty if
+/// the file is gone or can't be parsed. Blocking.
+pub fn parse_file(file: &str, fs_path: &Path) -> Vec<SymbolSpan> {
+    if !fs_path.is_file() {
+        return Vec::new();
+    }
+    let tree = match SymbolTree::init_file(fs_path) {
+        Ok(tree) => tree,
+        Err(e) => {
+            warn!(path = %fs_path.display(), error = %e, "failed to re-parse symbols");
+            return Vec::new();
+        }
+    };
+    // The tree is rooted at the file's directory, so its IDs start with the
+    // bare file name; re-key them under the tracked path
+    let dir = fs_path.parent().unwrap_or(Path::new(""));
+    spans_by_file(&tree, dir, &[])
+        .into_iter()
+        .flat_map(|(name, spans)| {
+            spans.into_iter().map(move |span| SymbolSpan {
+                id: format!("{file}{}", &span.id[name.len()..]),
+                lines: span.lines,
+            })
+        })
+        .collect()
+}
+
+/// Sorted symbol spans of every file in the trees, keyed by file ID.
+fn spans_by_file(
+    tree: &SymbolTree,
+    root: &Path,
+    named: &[(NamedRoot, SymbolTree)],
+) -> HashMap<String, Vec<SymbolSpan>> {
+    let mut files: HashMap<String, Vec<SymbolSpan>> = HashMap::new();
+    for (id, node) in flatten_workspace(tree, root, named, 0).nodes {
+        let Some(lines) = &node.lines else {
+            continue;
+        };
+        let Some((file, _)) = id.split_once(SYMBOL_SEPARATOR) else {
+            continue;
+        };
+        files.entry(file.to_string()).or_default().push(SymbolSpan {
+            lines: LineRange::new(lines.start, lines.end),
+            id,
+        });
+    }
+    for spans in files.values_mut() {
+        spans.sort_by(|a, b| a.lines.cmp(&b.lines).then_with(|| a.id.cmp(&b.id)));
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatten::flatten;
+
+    fn index(dir: &Path) -> SymbolIndex {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("src/shapes.py"),
+            "\
+import math
+
+
+class Circle:
+    def __init__(self, r):
+        self.r = r
+
+    def area(self):
+        return math.pi * self.r ** 2
+
+
+def unit():
+    return Circle(1)
+",
+        )
+        .unwrap();
+        let tree = SymbolTree::init_tree(dir).unwrap();
+        SymbolIndex::build(&tree, dir)
+    }
+
+    #[test]
+    fn ids_match_flatten() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        let tree = SymbolTree::init_tree(dir.path()).unwrap();
+        let snapshot = flatten(&tree, dir.path(), 0);
+        for spans in index.files.values() {
+            for span in spans {
+                assert!(snapshot.nodes.contains_key(&span.id), "{}", span.id);
+            }
+        }
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn enclosing_symbols_overlap_the_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path());
+        let file = "src/shapes.py";
+
+        assert_eq!(
+            index.enclosing(file, LineRange::line(9)),
+            vec!["src/shapes.py::Circle", "src/shapes.py::Circle::area"]
+        );
+        assert_eq!(
+            index.enclosing(file, LineRange::new(8, 13)),
+            vec![
+                "src/shapes.py::Circle",
+                "src/shapes.py::Circle::area",
+                "src/shapes.py::unit"
+            ]
+        );
+        // Imports sit outside every symbol
+        assert!(index.enclosing(file, LineRange::line(1)).is_empty());
+        assert!(index
+            .enclosing("src/other.py", LineRange::line(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn named_roots_prefix_symbol_ids() {
+        let app = tempfile::tempdir().unwrap();
+        let libs = tempfile::tempdir().unwrap();
+        std::fs::write(app.path().join("main.py"), "def main():\n    pass\n").unwrap();
+        let tree = SymbolTree::init_tree(app.path()).unwrap();
+        index(libs.path());
+        let named = vec![(
+            NamedRoot::new("libs", libs.path().to_path_buf()).unwrap(),
+            SymbolTree::init_tree(libs.path()).unwrap(),
+        )];
+
+        let index = SymbolIndex::build_workspace(&tree, app.path(), &named);
+        assert_eq!(index.len(), 5);
+        assert_eq!(
+            index.enclosing("main.py", LineRange::line(2)),
+            vec!["main.py::main"]
+        );
+        assert_eq!(
+            index.enclosing("libs:src/shapes.py", LineRange::line(13)),
+            vec!["libs:src/shapes.py::unit"]
+        );
+        assert!(is_symbol_id("libs:src/shapes.py::unit"));
+        assert!(!is_symbol_id("libs:src/shapes.py"));
+    }
+
+    #[test]
+    fn invalidated_files_are_reparsed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = index(dir.path());
+        let file = "src/shapes.py";
+        let fs_path = dir.path().join(file);
+        std::fs::write(
+            &fs_path,
+            "def first():\n    pass\n\n\ndef second():\n    pass\n",
+        )
+        .unwrap();
+        assert!(!index.take_stale(file));
+
+        index.invalidate(file);
+        assert!(index.take_stale(file));
+        assert!(!index.take_stale(file), "claimed once");
+        // The old spans answer until the new ones are set
+        assert_eq!(
+            index.enclosing(file, LineRange::line(12)),
+            vec!["src/shapes.py::unit"]
+        );
+        index.set_file(file, parse_file(file, &fs_path));
+        assert_eq!(
+            index.enclosing(file, LineRange::line(5)),
+            vec!["src/shapes.py::second"]
+        );
+        assert_eq!(index.len(), 2);
+
+        std::fs::remove_file(&fs_path).unwrap();
+        index.set_file(file, parse_file(file, &fs_path));
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn symbol_ids_are_recognised() {
+        assert!(is_symbol_id("src/a.rs::Foo::bar"));
+        assert!(!is_symbol_id("src/a.rs"));
+        assert!("src/a.rs::Foo".starts_with(&symbol_prefix("src/a.rs")));
+    }
+}
@@ -11,7 +11,10 @@ use tracing::{debug, warn};
 use crate::orchestrator::OrchestratorAggregator;
 use crate::session_registry::SessionRegistry;
 use crate::tracker::ContextTracker;
-use crate::types::{ClientMessage, RpcResponse, SessionKey, SessionMode, SessionModel};
+use crate::types::{
+    ClientMessage, RpcResponse, SessionKey, SessionMode, SessionModel, ZoneConfig, ZoneMode,
+};
+use crate::zone_registry::ZoneRegistry;
 
 /// Default TCP port for the eisen-core delta server.
 pub const DEFAULT_PORT: u16 = 17320;
@@ -56,6 +59,7 @@ pub async fn serve(
     delta_tx: broadcast::Sender<WireLine>,
     registry: Arc<Mutex<SessionRegistry>>,
     orchestrator: Arc<Mutex<OrchestratorAggregator>>,
+    zones: Arc<Mutex<ZoneRegistry>>,
 ) -> Result<()> {
     loop {
         let (stream, addr) = listener.accept().await?;
@@ -64,9 +68,12 @@ pub async fn serve(
         let delta_rx = delta_tx.subscribe();
         let registry = registry.clone();
         let orchestrator = orchestrator.clone();
+        let zones = zones.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = handle_client(stream, tracker, delta_rx, registry, orchestrator).await {
+            if let Err(e) =
+                handle_client(stream, tracker, delta_rx, registry, orchestrator, zones).await
+            {
                 // Client disconnected or I/O error — not fatal.
                 eprintln!("eisen tcp client error: {e}");
             }
@@ -90,6 +97,7 @@ pub async fn handle_client(
     mut delta_rx: broadcast::Receiver<WireLine>,
     registry: Arc<Mutex<SessionRegistry>>,
     orchestrator: Arc<Mutex<OrchestratorAggregator>>,
+    zones: Arc<Mutex<ZoneRegistry>>,
 ) -> Result<()> {
     let (reader, mut writer) = stream.into_split();
 
@@ -239,6 +247,7 @@ pub async fn handle_client(
                                     params,
                                     &registry_for_reader,
                                     &tracker_for_reader,
+                                    &zones,
                                 )
                                 .await;
                                 let rpc_elapsed_ms = rpc_start.elapsed().as_millis();
@@ -406,12 +415,62 @@ struct SetOrchestratorProvidersParams {
     providers: Vec<SessionKey>,
 }
 
+#[derive(Debug, Deserialize)]
+struct SetHotZoneParams {
+    /// Defaults to the tracker's current session.
+    #[serde(default)]
+    session_id: Option<String>,
+    paths: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ClearHotZoneParams {
+    #[serde(default)]
+    session_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct GetTimelineParams {
+    /// Defaults to the tracker's current session.
+    #[serde(default)]
+    session_id: Option<String>,
+    /// Only events from this turn.
+    #[serde(default)]
+    turn: Option<u32>,
+    /// Only the most recent `limit` events.
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetFileHistoryParams {
+    #[serde(default)]
+    session_id: Option<String>,
+    path: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetZoneParams {
+    agent_id: String,
+    session_id: String,
+    #[serde(flatten)]
+    zone: ZoneConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct SetZoneModeParams {
+    agent_id: String,
+    session_id: String,
+    mode: ZoneMode,
+}
+
 async fn handle_rpc_request(
     id: String,
     method: String,
     params: Option<serde_json::Value>,
     registry: &Arc<Mutex<SessionRegistry>>,
     tracker: &Arc<Mutex<ContextTracker>>,
+    zones: &Arc<Mutex<ZoneRegistry>>,
 ) -> RpcResponse {
     match method.as_str() {
         "list_sessions" => {
@@ -468,7 +527,18 @@ async fn handle_rpc_request(
                 Ok(session) => {
                     let session_id = session.session_id.clone();
                     let mode = session.mode;
-                    tracker.lock().await.set_session_mode(&session_id, mode);
+                    {
+                        let mut t = tracker.lock().await;
+                        t.set_session_mode(&session_id, mode);
+                        // Estimates are for the tracker's own agent only
+                        if let Some(model) = session
+                            .model
+                            .as_ref()
+                            .filter(|_| session.agent_id == t.agent_id())
+                        {
+                            t.set_session_model(&session_id, &model.model_id);
+                        }
+                    }
                     let elapsed_ms = rpc_start.elapsed().as_millis();
                     debug!(elapsed_ms, "create_session handled");
                     match serde_json::to_value(session) {
@@ -497,6 +567,7 @@ async fn handle_rpc_request(
                 Err(err) => return RpcResponse::error(id, 400, err),
             };
             let key = SessionKey::new(&params.agent_id, &params.session_id);
+            zones.lock().await.forget_session(&key);
             match registry.lock().await.close_session(&key) {
                 Ok(closed) => RpcResponse::result(id, serde_json::json!({"closed": closed})),
                 Err(err) => RpcResponse::error(id, 500, err.to_string()),
@@ -622,10 +693,19 @@ async fn handle_rpc_request(
             let key = SessionKey::new(&params.agent_id, &params.session_id);
             let state = registry.lock().await.get_session_state(&key);
             match state {
-                Some(session) => match serde_json::to_value(session) {
-                    Ok(value) => RpcResponse::result(id, value),
-                    Err(err) => RpcResponse::error(id, 500, err.to_string()),
-                },
+                Some(mut session) => {
+                    // The tracker only knows the spend of its own agent
+                    {
+                        let t = tracker.lock().await;
+                        if key.agent_id == t.agent_id() {
+                            session.spend = t.session_spend(&key.session_id);
+                        }
+                    }
+                    match serde_json::to_value(session) {
+                        Ok(value) => RpcResponse::result(id, value),
+                        Err(err) => RpcResponse::error(id, 500, err.to_string()),
+                    }
+                }
                 None => RpcResponse::error(id, 404, "session not found".to_string()),
             }
         }
@@ -649,6 +729,222 @@ async fn handle_rpc_request(
                 Err(err) => RpcResponse::error(id, 500, err.to_string()),
             }
         }
+        "set_hot_zone" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<SetHotZoneParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let key = {
+                let t = tracker.lock().await;
+                let session_id = params
+                    .session_id
+                    .unwrap_or_else(|| t.session_id().to_string());
+                SessionKey::new(t.agent_id(), &session_id)
+            };
+            let zone = zones.lock().await.enforced_zone(&key);
+            let session_id = key.session_id;
+            let mut t = tracker.lock().await;
+            if let Some((zone, paths)) = zone {
+                let outside: Vec<&str> = params
+                    .paths
+                    .iter()
+                    .filter(|p| !zone.is_path_allowed(&t.resolve_path(p).to_string_lossy(), &paths))
+                    .map(|p| p.as_str())
+                    .collect();
+                if !outside.is_empty() {
+                    return RpcResponse::error(
+                        id,
+                        400,
+                        format!("outside session zone: {}", outside.join(", ")),
+                    );
+                }
+            }
+            let paths = t.set_hot_zone(&session_id, &params.paths);
+            debug!(
+                session_id = session_id.as_str(),
+                count = paths.len(),
+                "hot zone updated"
+            );
+            RpcResponse::result(
+                id,
+                serde_json::json!({"session_id": session_id, "paths": paths}),
+            )
+        }
+        "clear_hot_zone" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<ClearHotZoneParams>(value).map_err(|e| e.to_string())
+                }
+                None => Ok(ClearHotZoneParams::default()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let mut t = tracker.lock().await;
+            let session_id = params
+                .session_id
+                .unwrap_or_else(|| t.session_id().to_string());
+            let cleared = t.clear_hot_zone(&session_id);
+            RpcResponse::result(id, serde_json::json!({"cleared": cleared}))
+        }
+        "get_timeline" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<GetTimelineParams>(value).map_err(|e| e.to_string())
+                }
+                None => Ok(GetTimelineParams::default()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let t = tracker.lock().await;
+            let session_id = params
+                .session_id
+                .unwrap_or_else(|| t.session_id().to_string());
+            let events = t.timeline(&session_id, params.turn, params.limit);
+            RpcResponse::result(
+                id,
+                serde_json::json!({"session_id": session_id, "events": events}),
+            )
+        }
+        "get_file_history" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<GetFileHistoryParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let t = tracker.lock().await;
+            let session_id = params
+                .session_id
+                .unwrap_or_else(|| t.session_id().to_string());
+            let events = t.file_history(&session_id, &params.path);
+            RpcResponse::result(
+                id,
+                serde_json::json!({
+                    "session_id": session_id,
+                    "path": params.path,
+                    "events": events,
+                }),
+            )
+        }
+        "set_zone" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<SetZoneParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let key = SessionKey::new(&params.agent_id, &params.session_id);
+            let compiled = match params.zone.compile() {
+                Ok(zone) => zone,
+                Err(err) => return RpcResponse::error(id, 400, err.to_string()),
+            };
+            debug!(
+                session_id = key.session_id.as_str(),
+                allowed = ?params.zone.allowed,
+                denied = ?params.zone.denied,
+                "zone updated"
+            );
+            zones.lock().await.set_zone(key, compiled);
+            match serde_json::to_value(params.zone) {
+                Ok(value) => RpcResponse::result(id, serde_json::json!({"zone": value})),
+                Err(err) => RpcResponse::error(id, 500, err.to_string()),
+            }
+        }
+        "get_zone" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<SessionKeyParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let key = SessionKey::new(&params.agent_id, &params.session_id);
+            let zones = zones.lock().await;
+            match zones.zone_with_source(&key) {
+                Some((zone, source)) => RpcResponse::result(
+                    id,
+                    serde_json::json!({"zone": zone.config(), "source": source.as_str()}),
+                ),
+                None => RpcResponse::result(id, serde_json::json!({"zone": null, "source": null})),
+            }
+        }
+        "clear_zone" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<SessionKeyParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let key = SessionKey::new(&params.agent_id, &params.session_id);
+            let cleared = zones.lock().await.clear_zone(&key);
+            RpcResponse::result(id, serde_json::json!({"cleared": cleared}))
+        }
+        "set_zone_mode" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<SetZoneModeParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let key = SessionKey::new(&params.agent_id, &params.session_id);
+            if zones.lock().await.set_mode(&key, params.mode) {
+                debug!(
+                    session_id = key.session_id.as_str(),
+                    mode = ?params.mode,
+                    "zone mode updated"
+                );
+                RpcResponse::result(id, serde_json::json!({"mode": params.mode}))
+            } else {
+                RpcResponse::error(id, 404, "no zone for session".to_string())
+            }
+        }
+        "get_zone_audit" => {
+            let parsed = match params {
+                Some(value) => {
+                    serde_json::from_value::<SessionKeyParams>(value).map_err(|e| e.to_string())
+                }
+                None => Err("missing params".to_string()),
+            };
+            let params = match parsed {
+                Ok(p) => p,
+                Err(err) => return RpcResponse::error(id, 400, err),
+            };
+            let key = SessionKey::new(&params.agent_id, &params.session_id);
+            let entries = zones.lock().await.audit_log(&key);
+            match serde_json::to_value(entries) {
+                Ok(value) => RpcResponse::result(id, value),
+                Err(err) => RpcResponse::error(id, 500, err.to_string()),
+            }
+        }
         _ => RpcResponse::error(id, 404, "unknown rpc method".to_string()),
     }
 }
@@ -669,7 +965,7 @@ mod tests {
     use super::*;
     use crate::orchestrator::OrchestratorAggregator;
     use crate::session_registry::SessionRegistry;
-    use crate::types::{Action, TrackerConfig};
+    use crate::types::{Action, LineRange, Source, TrackerConfig};
     use tempfile::TempDir;
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::TcpStream;
@@ -686,10 +982,12 @@ mod tests {
     ) {
         let tracker = Arc::new(Mutex::new(ContextTracker::new(TrackerConfig::default())));
         let registry_dir = tempfile::tempdir().unwrap();
-        let registry = Arc::new(Mutex::new(SessionRegistry::load_from_path(
-            registry_dir.path().join("core_sessions.json"),
-        )));
+        let registry = Arc::new(Mutex::new(
+            SessionRegistry::load_from_path(registry_dir.path().join("core_sessions.json"))
+                .unwrap(),
+        ));
         let orchestrator = Arc::new(Mutex::new(OrchestratorAggregator::new()));
+        let zones = Arc::new(Mutex::new(ZoneRegistry::new(None)));
         let (delta_tx, _) = broadcast::channel::<WireLine>(64);
 
         // Bind to port 0 for ephemeral port assignment
@@ -706,9 +1004,10 @@ mod tests {
                 let t2 = t.clone();
                 let reg2 = reg.clone();
                 let orch2 = orch.clone();
+                let zones2 = zones.clone();
                 let rx = tx.subscribe();
                 tokio::spawn(async move {
-                    let _ = handle_client(stream, t2, rx, reg2, orch2).await;
+                    let _ = handle_client(stream, t2, rx, reg2, orch2, zones2).await;
                 });
             }
         });
@@ -818,6 +1117,10 @@ mod tests {
                 last_action: Action::Read,
                 turn_accessed: 3,
                 timestamp_ms: 1700000000000,
+                estimated_tokens: 120,
+                line_ranges: vec![LineRange::new(10, 24)],
+                source: Source::ToolCall,
+                confidence: 1.0,
             }],
             vec![],
         );
@@ -1,56 +1,79 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use crate::types::{Action, Delta, FileNode, SessionMode, Snapshot, TrackerConfig, UsageMessage};
-
-const IGNORED_DIRS: &[&str] = &[
-    "node_modules",
-    "dist",
-    "build",
-    "target",
-    ".git",
-    ".venv",
-    "__pycache__",
-    ".next",
-    ".nuxt",
-    "coverage",
-    ".turbo",
-    ".cache",
-    ".output",
-    "out",
-];
-
-fn is_ignored_segment(seg: &str) -> bool {
-    (seg.starts_with('.') && seg != ".." && seg != ".") || IGNORED_DIRS.contains(&seg)
-}
-
-fn normalize_path(raw: &str, workspace_root: Option<&Path>) -> Option<String> {
-    let p = Path::new(raw);
-    let relative = match workspace_root {
-        Some(root) if p.is_absolute() => p
-            .strip_prefix(root)
-            .ok()?
-            .to_string_lossy()
-            .replace('\\', "/"),
-        _ => raw.replace('\\', "/"),
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::checkpoint::{CheckpointStore, SessionCheckpoint};
+use crate::clock::{self, SharedClock};
+use crate::file_reads::{CompletedReads, PendingReads, TokenEstimate};
+use crate::ignore_policy::IgnorePolicy;
+use crate::paths::{self, NamedRoot, PathResolver, ResolvedPath};
+use crate::pricing::PriceTable;
+use crate::symbols::{self, SymbolIndex};
+use crate::types::{
+    Action, ContextBudget, Cost, Delta, FileNode, LineRange, SessionKey, SessionMode, Snapshot,
+    Source, Spend, TimelineEvent, TokenUsage, TrackerConfig, UsageMessage,
+};
+
+/// Path ID for `raw`: relative to the primary root, `name:relative` under a
+/// named root, or `external:/absolute` outside every root. `None` for
+/// escapes, roots themselves and ignored paths; `ignores` picks the policy
+/// by root name (`None` for the primary root).
+fn normalize_path<'a>(
+    raw: &str,
+    paths: &PathResolver,
+    ignores: impl Fn(Option<&str>) -> &'a IgnorePolicy,
+) -> Option<String> {
+    let id = match paths.resolve(raw) {
+        ResolvedPath::Inside(id) => id,
+        ResolvedPath::Outside(full) => paths::external_id(&full),
+        ResolvedPath::Escape => return None,
     };
-    let relative = relative.strip_prefix("./").unwrap_or(&relative);
+    let (root, relative) = paths.split_id(&id);
     if relative.is_empty() {
         return None;
     }
-    if relative.split('/').any(is_ignored_segment) {
+    if ignores(root).is_ignored(Path::new(relative), false) {
         return None;
     }
-    Some(relative.to_string())
+    Some(id)
 }
 
-/// Current wall-clock time in milliseconds since Unix epoch.
-fn now_ms() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
+/// Files kept in the token cache; the least recently used go first.
+const TOKEN_CACHE_CAPACITY: usize = 4096;
+
+/// An open-ended line range recorded before its file's length was known,
+/// widened once the file's estimate lands.
+#[derive(Debug)]
+struct OpenRange {
+    session_id: String,
+    path: String,
+    start: u32,
+    action: Action,
+    source: Source,
+    ts: u64,
+}
+
+/// Heat multiplier for `elapsed_ms` of cooling with the given half-life.
+fn decay_factor(elapsed_ms: u64, half_life_ms: u64) -> f32 {
+    if half_life_ms == 0 {
+        return 0.0;
+    }
+    0.5f64.powf(elapsed_ms as f64 / half_life_ms as f64) as f32
+}
+
+/// Progress through the history an agent replays after `session/load`.
+#[derive(Debug, Default)]
+struct Replay {
+    /// The previous update was part of a user message
+    in_user_message: bool,
+    /// User messages (i.e. prompts) replayed so far
+    user_messages: u32,
+    /// Paths touched by replayed tool calls
+    touched: HashSet<String>,
 }
 
 /// SessionTracker holds tracking state for a single session.
@@ -71,10 +94,37 @@ struct SessionTracker {
     /// take_pending_usage(). This lets the tick loop broadcast them
     /// without the caller needing to handle the return value.
     pending_usage: Vec<UsageMessage>,
+    /// Paths pinned via `set_hot_zone`. These are re-injected into the
+    /// next `session/prompt` whenever they fall out of context.
+    hot_zone: HashSet<String>,
+    /// Hot-zone paths waiting to be re-injected. Populated by
+    /// handle_compaction / end_turn, drained by take_pending_refresh().
+    pending_refresh: HashSet<String>,
+    /// State changed since the last checkpoint was written.
+    dirty: bool,
+    /// Set while the agent replays history after `session/load`.
+    replay: Option<Replay>,
+    /// Clock time of the last tick; decay covers the time since then.
+    last_tick_ms: u64,
+    /// Every recorded access, oldest first, capped at
+    /// `config.timeline_limit`.
+    timeline: VecDeque<TimelineEvent>,
+    /// Cumulative cost from the agent's usage reports, if it sends any.
+    spend: Option<Spend>,
+    /// Model the session runs on, for estimating cost.
+    model: Option<String>,
+    /// Cost estimated from the turns' token usage, used while the agent
+    /// reports none.
+    estimated_spend: Option<Cost>,
 }
 
 impl SessionTracker {
-    fn new(session_id: String, session_mode: SessionMode, config: TrackerConfig) -> Self {
+    fn new(
+        session_id: String,
+        session_mode: SessionMode,
+        config: TrackerConfig,
+        now_ms: u64,
+    ) -> Self {
         Self {
             session_id,
             session_mode,
@@ -86,19 +136,81 @@ impl SessionTracker {
             config,
             changed_paths: HashSet::new(),
             pending_usage: Vec::new(),
+            hot_zone: HashSet::new(),
+            pending_refresh: HashSet::new(),
+            dirty: false,
+            replay: None,
+            last_tick_ms: now_ms,
+            timeline: VecDeque::new(),
+            spend: None,
+            model: None,
+            estimated_spend: None,
         }
     }
 
+    fn to_checkpoint(&self, agent_id: &str, saved_at_ms: u64) -> SessionCheckpoint {
+        let mut files: Vec<FileNode> = self.files.values().cloned().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut hot_zone: Vec<String> = self.hot_zone.iter().cloned().collect();
+        hot_zone.sort();
+        SessionCheckpoint {
+            agent_id: agent_id.to_string(),
+            session_id: self.session_id.clone(),
+            session_mode: self.session_mode,
+            files,
+            seq: self.seq,
+            current_turn: self.current_turn,
+            last_used_tokens: self.last_used_tokens,
+            context_size: self.context_size,
+            hot_zone,
+            timeline: self.timeline.iter().cloned().collect(),
+            spend: self.spend.clone(),
+            model: self.model.clone(),
+            estimated_spend: self.estimated_spend.clone(),
+            saved_at_ms,
+        }
+    }
+
+    /// Rehydrate from a checkpoint. Every restored file goes out with the
+    /// next delta so connected clients pick the graph back up.
+    fn restore(&mut self, checkpoint: SessionCheckpoint) {
+        self.session_mode = checkpoint.session_mode;
+        self.seq = checkpoint.seq;
+        self.current_turn = checkpoint.current_turn;
+        self.last_used_tokens = checkpoint.last_used_tokens;
+        self.context_size = checkpoint.context_size;
+        self.hot_zone = checkpoint.hot_zone.into_iter().collect();
+        self.timeline = checkpoint.timeline.into();
+        self.trim_timeline();
+        self.spend = checkpoint.spend;
+        self.model = checkpoint.model;
+        self.estimated_spend = checkpoint.estimated_spend;
+        self.changed_paths = checkpoint.files.iter().map(|n| n.path.clone()).collect();
+        self.files = checkpoint
+            .files
+            .into_iter()
+            .map(|n| (n.path.clone(), n))
+            .collect();
+    }
+
     fn set_mode(&mut self, mode: SessionMode) {
         self.session_mode = mode;
+        self.dirty = true;
     }
 
     fn mode(&self) -> SessionMode {
         self.session_mode
     }
 
-    fn file_access(&mut self, path: &str, action: Action) {
-        let ts = now_ms();
+    fn file_access(
+        &mut self,
+        path: &str,
+        action: Action,
+        source: Source,
+        ts: u64,
+        tokens: Option<u32>,
+        lines: Option<LineRange>,
+    ) {
         let node = self
             .files
             .entry(path.to_string())
@@ -109,6 +221,10 @@ impl SessionTracker {
                 last_action: action,
                 turn_accessed: 0,
                 timestamp_ms: 0,
+                estimated_tokens: 0,
+                line_ranges: Vec::new(),
+                source,
+                confidence: 0.0,
             });
 
         node.heat = 1.0;
@@ -116,14 +232,252 @@ impl SessionTracker {
         node.last_action = action;
         node.turn_accessed = self.current_turn;
         node.timestamp_ms = ts;
+        if let Some(tokens) = tokens {
+            node.estimated_tokens = tokens;
+        }
+        if let Some(range) = lines {
+            node.add_line_range(range);
+        }
+        node.source = source;
+        node.confidence = node.confidence.max(source.confidence());
 
         self.changed_paths.insert(path.to_string());
+        self.dirty = true;
+        self.enforce_budget();
+    }
+
+    /// Drop a deleted file along with its symbols. Each dropped path goes
+    /// out in the next delta's `removed` list.
+    fn remove_file(&mut self, path: &str) {
+        let prefix = symbols::symbol_prefix(path);
+        let gone: Vec<String> = self
+            .files
+            .keys()
+            .filter(|p| *p == path || p.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for p in gone {
+            self.files.remove(&p);
+            self.changed_paths.insert(p);
+        }
+        // A deleted file can't be re-injected
+        self.hot_zone.remove(path);
+        self.pending_refresh.remove(path);
+        if let Some(replay) = &mut self.replay {
+            replay.touched.remove(path);
+        }
+        self.dirty = true;
+    }
+
+    /// Re-key a moved file's node under its new path, keeping its heat,
+    /// context state and line ranges. The old path (and its symbols, whose
+    /// IDs embed it) is removed; a hot-zone pin follows the file. Returns
+    /// `false` when `from` isn't tracked, so there is nothing to carry.
+    fn move_file(&mut self, from: &str, to: &str, source: Source, ts: u64) -> bool {
+        let Some(mut node) = self.files.remove(from) else {
+            return false;
+        };
+        let pinned = self.hot_zone.contains(from);
+        let refresh = self.pending_refresh.contains(from);
+        self.remove_file(from);
+        self.changed_paths.insert(from.to_string());
+        node.path = to.to_string();
+        node.last_action = Action::Move;
+        node.timestamp_ms = ts;
+        node.source = source;
+        node.confidence = node.confidence.max(source.confidence());
+        self.files.insert(to.to_string(), node);
+        if pinned {
+            self.hot_zone.insert(to.to_string());
+        }
+        if refresh {
+            self.pending_refresh.insert(to.to_string());
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.touched.insert(to.to_string());
+        }
+        self.changed_paths.insert(to.to_string());
+        self.dirty = true;
+        true
     }
 
-    fn usage_update(&mut self, agent_id: &str, used: u32, size: u32) {
+    fn record_event(&mut self, event: TimelineEvent) {
+        self.timeline.push_back(event);
+        self.trim_timeline();
+        self.dirty = true;
+    }
+
+    fn trim_timeline(&mut self) {
+        let excess = self
+            .timeline
+            .len()
+            .saturating_sub(self.config.timeline_limit);
+        self.timeline.drain(..excess);
+    }
+
+    /// Tokens the in-context files are estimated to occupy.
+    fn estimated_in_context(&self) -> u32 {
+        self.files
+            .values()
+            .filter(|n| n.in_context && !symbols::is_symbol_id(&n.path))
+            .map(|n| n.estimated_tokens)
+            .sum()
+    }
+
+    fn budget(&self) -> Option<ContextBudget> {
+        (self.context_size > 0).then(|| ContextBudget {
+            size: self.context_size,
+            used: self.last_used_tokens,
+            estimated: self.estimated_in_context(),
+        })
+    }
+
+    /// Evict least recently used files (by turn, then access time) until
+    /// the in-context estimate fits the window the agent reported. The most
+    /// recently used file always stays. Symbols leave with their file.
+    fn enforce_budget(&mut self) {
+        if self.context_size == 0 || self.replay.is_some() {
+            return;
+        }
+        let mut total = self.estimated_in_context();
+        if total <= self.context_size {
+            return;
+        }
+        let mut lru: Vec<(u32, u64, String)> = self
+            .files
+            .values()
+            .filter(|n| n.in_context && !symbols::is_symbol_id(&n.path))
+            .map(|n| (n.turn_accessed, n.timestamp_ms, n.path.clone()))
+            .collect();
+        lru.sort();
+        lru.pop();
+        for (_, _, path) in lru {
+            if total <= self.context_size {
+                break;
+            }
+            let Some(node) = self.files.get_mut(&path) else {
+                continue;
+            };
+            node.in_context = false;
+            total -= node.estimated_tokens;
+            if self.hot_zone.contains(&path) {
+                self.pending_refresh.insert(path.clone());
+            }
+            let prefix = symbols::symbol_prefix(&path);
+            for (id, symbol) in &mut self.files {
+                if symbol.in_context && id.starts_with(&prefix) {
+                    symbol.in_context = false;
+                    self.changed_paths.insert(id.clone());
+                }
+            }
+            self.changed_paths.insert(path);
+        }
+        self.dirty = true;
+    }
+
+    /// Start rebuilding the session from replayed history. Turns are
+    /// recounted from zero; the replay is authoritative.
+    fn begin_replay(&mut self) {
+        self.current_turn = 0;
+        self.replay = Some(Replay::default());
+    }
+
+    /// Note a replayed `session/update`. Each new user message after the
+    /// first closes the previous turn.
+    fn replay_update(&mut self, from_user: bool) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        if !from_user {
+            replay.in_user_message = false;
+            return;
+        }
+        if !replay.in_user_message {
+            if replay.user_messages > 0 {
+                self.current_turn += 1;
+            }
+            replay.user_messages += 1;
+            replay.in_user_message = true;
+        }
+    }
+
+    /// Record a replayed access: the turn and action are kept, but heat is
+    /// left alone since nothing actually happened just now.
+    fn replay_access(
+        &mut self,
+        path: &str,
+        action: Action,
+        source: Source,
+        tokens: Option<u32>,
+        lines: Option<LineRange>,
+    ) {
+        let turn = self.current_turn;
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        let node = self
+            .files
+            .entry(path.to_string())
+            .or_insert_with(|| FileNode {
+                path: path.to_string(),
+                heat: 0.0,
+                in_context: false,
+                last_action: action,
+                turn_accessed: 0,
+                timestamp_ms: 0,
+                estimated_tokens: 0,
+                line_ranges: Vec::new(),
+                source,
+                confidence: 0.0,
+            });
+        node.last_action = action;
+        node.turn_accessed = turn;
+        if let Some(tokens) = tokens {
+            node.estimated_tokens = tokens;
+        }
+        if let Some(range) = lines {
+            node.add_line_range(range);
+        }
+        node.source = source;
+        node.confidence = node.confidence.max(source.confidence());
+        replay.touched.insert(path.to_string());
+    }
+
+    /// Close the replay: the last replayed turn is complete, and files the
+    /// agent touched within the last `context_turns` turns are back in
+    /// context.
+    fn finish_replay(&mut self) {
+        let Some(replay) = self.replay.take() else {
+            return;
+        };
+        if replay.user_messages > 0 {
+            self.current_turn += 1;
+        }
+        for path in replay.touched {
+            let Some(node) = self.files.get_mut(&path) else {
+                continue;
+            };
+            node.in_context =
+                self.current_turn.saturating_sub(node.turn_accessed) <= self.config.context_turns;
+            if node.heat <= 0.0 && !node.in_context {
+                // Never shown and already out of context
+                self.files.remove(&path);
+            } else {
+                self.changed_paths.insert(path);
+            }
+        }
+        self.dirty = true;
+        self.enforce_budget();
+    }
+
+    fn usage_update(&mut self, agent_id: &str, used: u32, size: u32, cost: Option<&Cost>) {
+        if let Some(cost) = cost {
+            self.record_cost(cost);
+        }
         let previous = self.last_used_tokens;
         self.last_used_tokens = used;
         self.context_size = size;
+        self.dirty = true;
 
         // Detect compaction: usage dropped by more than threshold
         if previous > 0 {
@@ -132,14 +486,56 @@ impl SessionTracker {
                 self.handle_compaction();
             }
         }
+        // The window may have shrunk (e.g. after a model switch)
+        self.enforce_budget();
 
+        self.queue_usage(agent_id);
+    }
+
+    /// A cost report without token counts: the usage message repeats the
+    /// last known window.
+    fn cost_update(&mut self, agent_id: &str, cost: &Cost) {
+        self.record_cost(cost);
+        self.dirty = true;
+        self.queue_usage(agent_id);
+    }
+
+    /// Add a turn's estimated cost. Only reaches clients while the agent
+    /// hasn't reported a real one.
+    fn add_estimate(&mut self, agent_id: &str, cost: Cost) {
+        match &mut self.estimated_spend {
+            Some(total) if total.currency == cost.currency => total.amount += cost.amount,
+            _ => self.estimated_spend = Some(cost),
+        }
+        self.dirty = true;
+        if self.spend.is_none() {
+            self.queue_usage(agent_id);
+        }
+    }
+
+    /// Reported spend, else the estimate.
+    fn cost(&self) -> Option<Cost> {
+        self.spend
+            .as_ref()
+            .map(Spend::total)
+            .or_else(|| self.estimated_spend.clone())
+    }
+
+    fn record_cost(&mut self, cost: &Cost) {
+        match &mut self.spend {
+            Some(spend) => spend.record(cost),
+            None => self.spend = Some(Spend::new(cost)),
+        }
+    }
+
+    fn queue_usage(&mut self, agent_id: &str) {
         self.pending_usage.push(UsageMessage::new(
             agent_id,
             &self.session_id,
             self.session_mode,
-            used,
-            size,
-            None,
+            self.last_used_tokens,
+            self.context_size,
+            self.cost(),
         ));
     }
 
@@ -149,6 +545,7 @@ impl SessionTracker {
 
     fn end_turn(&mut self) {
         self.current_turn += 1;
+        self.dirty = true;
 
         // Files not accessed within the context window exit context
         for (path, node) in &mut self.files {
@@ -157,15 +554,22 @@ impl SessionTracker {
             {
                 node.in_context = false;
                 self.changed_paths.insert(path.clone());
+                if self.hot_zone.contains(path) {
+                    self.pending_refresh.insert(path.clone());
+                }
             }
         }
     }
 
-    fn tick(&mut self, agent_id: &str) -> Option<Delta> {
-        // Decay heat on files that are NOT in context
+    fn tick(&mut self, agent_id: &str, now_ms: u64) -> Option<Delta> {
+        // Decay heat on files that are NOT in context, by however much time
+        // passed since the last tick, so the tick rate doesn't matter.
+        let elapsed = now_ms.saturating_sub(self.last_tick_ms);
+        self.last_tick_ms = now_ms;
+        let factor = decay_factor(elapsed, self.config.heat_half_life_ms);
         for (path, node) in &mut self.files {
-            if !node.in_context && node.heat > 0.01 {
-                node.heat *= self.config.decay_rate;
+            if factor < 1.0 && !node.in_context && node.heat > 0.01 {
+                node.heat *= factor;
                 // Clamp to zero when negligible
                 if node.heat <= 0.01 {
                     node.heat = 0.0;
@@ -184,14 +588,15 @@ impl SessionTracker {
         let mut removed = Vec::new();
 
         for path in self.changed_paths.drain().collect::<Vec<_>>() {
-            if let Some(node) = self.files.get(&path) {
+            match self.files.get(&path) {
                 // Only include nodes that are still warm or in-context
-                if node.heat > 0.0 || node.in_context {
+                Some(node) if node.heat > 0.0 || node.in_context => {
                     updates.push(node.to_update());
-                } else {
-                    // File heat hit zero and not in context — prune
-                    removed.push(path.clone());
                 }
+                // File heat hit zero and not in context — prune
+                Some(_) => removed.push(path.clone()),
+                // Deleted or moved away
+                None => removed.push(path),
             }
         }
 
@@ -203,6 +608,7 @@ impl SessionTracker {
         if updates.is_empty() && removed.is_empty() {
             return None;
         }
+        self.dirty = true;
 
         Some(Delta::new(
             agent_id,
@@ -230,6 +636,7 @@ impl SessionTracker {
             self.seq,
             nodes,
         )
+        .with_budget(self.budget())
     }
 
     fn handle_compaction(&mut self) {
@@ -239,6 +646,42 @@ impl SessionTracker {
                 self.changed_paths.insert(path.clone());
             }
         }
+        // Everything pinned is gone from the agent's window now
+        self.pending_refresh.extend(self.hot_zone.iter().cloned());
+    }
+
+    fn set_hot_zone(&mut self, paths: HashSet<String>) {
+        // Newly pinned files that aren't already in context go out with the
+        // next prompt; the rest wait until they age out.
+        for path in &paths {
+            let in_context = self.files.get(path).is_some_and(|n| n.in_context);
+            if !in_context {
+                self.pending_refresh.insert(path.clone());
+            }
+        }
+        self.pending_refresh.retain(|p| paths.contains(p));
+        self.hot_zone = paths;
+        self.dirty = true;
+    }
+
+    fn clear_hot_zone(&mut self) -> bool {
+        self.pending_refresh.clear();
+        let had_paths = !self.hot_zone.is_empty();
+        self.hot_zone.clear();
+        self.dirty |= had_paths;
+        had_paths
+    }
+
+    fn take_pending_refresh(&mut self) -> Vec<String> {
+        let mut paths: Vec<String> = self.pending_refresh.drain().collect();
+        paths.sort();
+        paths
+    }
+
+    fn requeue_refresh(&mut self, paths: &[String]) {
+        // Files unpinned in the meantime don't come back
+        let pinned = paths.iter().filter(|p| self.hot_zone.contains(*p));
+        self.pending_refresh.extend(pinned.cloned());
     }
 }
 
@@ -250,11 +693,44 @@ impl SessionTracker {
 pub struct ContextTracker {
     agent_id: String,
     default_session_id: Option<String>,
-    workspace_root: Option<PathBuf>,
+    /// Primary and named workspace roots that paths are keyed against.
+    paths: PathResolver,
+    /// Paths never tracked under the primary root; reloaded when the
+    /// workspace root is set.
+    ignore: IgnorePolicy,
+    /// Ignore policies of the named roots and the external group, by name.
+    root_ignores: HashMap<String, IgnorePolicy>,
     sessions: HashMap<String, SessionTracker>,
     config: TrackerConfig,
     pending_prompt_requests: HashMap<u64, String>,
     pending_terminal_output_ids: HashMap<u64, String>,
+    /// `session/load` / `session/resume` request IDs awaiting a response.
+    pending_load_requests: HashMap<u64, String>,
+    /// Sessions resumed since the last drain, for the session registry.
+    pending_resumed: Vec<String>,
+    /// Whether the agent advertised `promptCapabilities.embeddedContext` in
+    /// its InitializeResponse. Hot-zone re-injection is gated on this.
+    embedded_context: bool,
+    /// Time source for access timestamps, heat decay and checkpoint ages.
+    clock: SharedClock,
+    /// Token estimates by filesystem path, shared by all sessions, with
+    /// the `token_cache_uses` count at their last use.
+    token_cache: HashMap<PathBuf, (TokenEstimate, u64)>,
+    token_cache_uses: u64,
+    /// Files to read off the lock, drained by `take_pending_reads`.
+    pending_reads: PendingReads,
+    /// Open-ended ranges waiting for their file's length.
+    open_ranges: Vec<OpenRange>,
+    /// Notified whenever `pending_reads` gains an entry.
+    reads_wake: Arc<Notify>,
+    /// Symbol spans for the workspace. When set, line-level accesses also
+    /// heat the symbols they fall in.
+    symbols: Option<SymbolIndex>,
+    /// Where per-session state is checkpointed. `None` keeps everything in
+    /// memory.
+    checkpoints: Option<CheckpointStore>,
+    /// Model prices for estimating cost from token usage.
+    prices: PriceTable,
 }
 
 impl ContextTracker {
@@ -262,16 +738,149 @@ impl ContextTracker {
         Self {
             agent_id: String::new(),
             default_session_id: None,
-            workspace_root: None,
+            paths: PathResolver::default(),
+            ignore: IgnorePolicy::load(None, &config.ignore),
+            root_ignores: HashMap::from([(
+                paths::EXTERNAL_ROOT.to_string(),
+                IgnorePolicy::load(None, &config.ignore),
+            )]),
             sessions: HashMap::new(),
             config,
             pending_prompt_requests: HashMap::new(),
             pending_terminal_output_ids: HashMap::new(),
+            pending_load_requests: HashMap::new(),
+            pending_resumed: Vec::new(),
+            embedded_context: false,
+            checkpoints: None,
+            token_cache: HashMap::new(),
+            token_cache_uses: 0,
+            pending_reads: PendingReads::default(),
+            open_ranges: Vec::new(),
+            reads_wake: Arc::new(Notify::new()),
+            symbols: None,
+            clock: clock::system(),
+            prices: PriceTable::default(),
+        }
+    }
+
+    /// Replace the time source (a `ManualClock` in tests). Set before any
+    /// session is created.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Checkpoint session state to `store` and rehydrate sessions from it
+    /// when their ID shows up again. Set before any session is created.
+    pub fn set_checkpoint_store(&mut self, store: CheckpointStore) {
+        self.checkpoints = Some(store);
+    }
+
+    pub fn checkpoint_store(&self) -> Option<&CheckpointStore> {
+        self.checkpoints.as_ref()
+    }
+
+    /// Write every session that changed since its last checkpoint.
+    /// Returns the number of checkpoints written.
+    pub fn save_checkpoints(&mut self) -> Result<usize> {
+        let Some(store) = &self.checkpoints else {
+            return Ok(0);
+        };
+        let now = self.clock.now_ms();
+        let mut written = 0;
+        for session in self.sessions.values_mut() {
+            // Mid-replay state is incomplete; wait for the replay to finish
+            if !session.dirty || session.session_id.is_empty() || session.replay.is_some() {
+                continue;
+            }
+            store.save(&session.to_checkpoint(&self.agent_id, now))?;
+            session.dirty = false;
+            written += 1;
         }
+        Ok(written)
     }
 
+    /// Resolve paths against `root` and load its `.gitignore` and
+    /// `.eisenignore` into the ignore policy.
     pub fn set_workspace_root(&mut self, root: PathBuf) {
-        self.workspace_root = Some(root);
+        self.ignore = IgnorePolicy::load(Some(&root), &self.config.ignore);
+        self.paths.set_root(Some(root));
+    }
+
+    pub fn workspace_root(&self) -> Option<&Path> {
+        self.paths.root()
+    }
+
+    /// Track files under another root (a sibling repo, a shared `libs/`
+    /// checkout) as `name:relative`, with that root's own ignore files.
+    pub fn add_workspace_root(&mut self, root: NamedRoot) {
+        self.root_ignores.insert(
+            root.name.clone(),
+            IgnorePolicy::load(Some(&root.path), &self.config.ignore),
+        );
+        self.paths.add_root(root);
+    }
+
+    /// Roots that path IDs are resolved against.
+    pub fn path_resolver(&self) -> &PathResolver {
+        &self.paths
+    }
+
+    /// Which paths are never tracked. Hand the same policy to the parser
+    /// and the watcher so every view agrees on which files exist.
+    pub fn ignore_policy(&self) -> &IgnorePolicy {
+        &self.ignore
+    }
+
+    /// Ignore policy of the named root `name`, or the primary root's if
+    /// there is no such root.
+    pub fn root_ignore_policy(&self, name: &str) -> &IgnorePolicy {
+        self.root_ignores.get(name).unwrap_or(&self.ignore)
+    }
+
+    fn normalize(&self, raw: &str) -> Option<String> {
+        normalize_path(raw, &self.paths, |root| {
+            root.and_then(|name| self.root_ignores.get(name))
+                .unwrap_or(&self.ignore)
+        })
+    }
+
+    /// Resolve a tracked path ID back to a filesystem path.
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        self.paths.to_fs_path(path)
+    }
+
+    /// Attribute line-level accesses to symbols from `index`, which must be
+    /// built against the workspace root. Symbol nodes are tracked alongside
+    /// files, keyed by their `file::Class::method` ID.
+    pub fn set_symbol_index(&mut self, index: SymbolIndex) {
+        self.symbols = Some(index);
+    }
+
+    /// Have a file's symbol spans re-parsed the next time a line-level
+    /// access needs them. The re-parse runs off the lock (see
+    /// `file_reads`).
+    fn invalidate_symbols(&mut self, path: &str) {
+        if let Some(index) = &mut self.symbols {
+            index.invalidate(path);
+        }
+    }
+
+    pub fn symbol_index(&self) -> Option<&SymbolIndex> {
+        self.symbols.as_ref()
+    }
+
+    /// Record whether the agent accepts `ContentBlock::Resource` in prompts.
+    /// Detected from the InitializeResponse by the extract layer.
+    pub fn set_embedded_context(&mut self, supported: bool) {
+        self.embedded_context = supported;
+    }
+
+    pub fn embedded_context(&self) -> bool {
+        self.embedded_context
     }
 
     /// Set the agent instance ID. Called from the `--agent-id` CLI flag.
@@ -306,20 +915,59 @@ impl ContextTracker {
         self.sessions.get(session_id).map(|s| s.mode())
     }
 
+    /// Start tracking a session if it isn't tracked yet, rehydrating it
+    /// from its checkpoint. Called when the extract layer sees a session ID.
+    pub fn track_session(&mut self, session_id: &str) {
+        if !session_id.is_empty() {
+            self.ensure_session(session_id);
+        }
+    }
+
     pub fn session_ids(&self) -> Vec<String> {
         self.sessions.keys().cloned().collect()
     }
 
     fn ensure_session(&mut self, session_id: &str) -> &mut SessionTracker {
+        if !self.sessions.contains_key(session_id) {
+            let mut session = SessionTracker::new(
+                session_id.to_string(),
+                SessionMode::SingleAgent,
+                self.config.clone(),
+                self.clock.now_ms(),
+            );
+            if let Some(checkpoint) = self.load_checkpoint(session_id) {
+                info!(
+                    session_id,
+                    files = checkpoint.files.len(),
+                    turn = checkpoint.current_turn,
+                    "restored tracker state from checkpoint"
+                );
+                session.restore(checkpoint);
+            }
+            self.sessions.insert(session_id.to_string(), session);
+        }
         self.sessions
-            .entry(session_id.to_string())
-            .or_insert_with(|| {
-                SessionTracker::new(
-                    session_id.to_string(),
-                    SessionMode::SingleAgent,
-                    self.config.clone(),
-                )
-            })
+            .get_mut(session_id)
+            .expect("session was just inserted")
+    }
+
+    fn load_checkpoint(&self, session_id: &str) -> Option<SessionCheckpoint> {
+        let store = self.checkpoints.as_ref()?;
+        if session_id.is_empty() {
+            return None;
+        }
+        let key = SessionKey::new(&self.agent_id, session_id);
+        match store.load(&key, self.clock.now_ms()) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                warn!(
+                    session_id,
+                    error = format!("{e:#}"),
+                    "ignoring unreadable tracker checkpoint"
+                );
+                None
+            }
+        }
     }
 
     // -------------------------------------------------------------------
@@ -336,10 +984,21 @@ impl ContextTracker {
     }
 
     pub fn record_prompt_request(&mut self, id: u64, session_id: &str) {
+        // A new prompt means any replay is over, even if the load
+        // response went missing.
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            session.finish_replay();
+        }
         self.pending_prompt_requests
             .insert(id, session_id.to_string());
     }
 
+    /// Session a `session/prompt` request still awaiting its response
+    /// belongs to.
+    pub fn prompt_session(&self, id: u64) -> Option<&str> {
+        self.pending_prompt_requests.get(&id).map(|s| s.as_str())
+    }
+
     pub fn end_turn_for_prompt_response(&mut self, id: u64) -> bool {
         if let Some(session_id) = self.pending_prompt_requests.remove(&id) {
             self.end_turn_for_session(&session_id);
@@ -348,22 +1007,398 @@ impl ContextTracker {
         false
     }
 
+    /// Record a change the filesystem watcher saw, as a write by the
+    /// session whose prompt is in flight (the most recent one, if several).
+    /// Changes between turns aren't the agent's and are dropped, as are
+    /// changes to files already written this turn, which covers the
+    /// watcher echoing writes seen over ACP. Returns whether it was recorded.
+    pub fn watcher_change(&mut self, path: &str) -> bool {
+        let Some(normalized) = self.normalize(path) else {
+            return false;
+        };
+        // Spans go stale whether or not a turn is in flight
+        self.invalidate_symbols(&normalized);
+        let Some(session_id) = self
+            .pending_prompt_requests
+            .iter()
+            .max_by_key(|(id, _)| **id)
+            .map(|(_, session_id)| session_id.clone())
+        else {
+            return false;
+        };
+        if let Some(session) = self.sessions.get(&session_id) {
+            let written = session.files.get(&normalized).is_some_and(|n| {
+                n.last_action == Action::Write && n.turn_accessed == session.current_turn
+            });
+            if written {
+                return false;
+            }
+        }
+        self.file_access_for_session(&session_id, &normalized, Action::Write, Source::Watcher);
+        true
+    }
+
+    // -------------------------------------------------------------------
+    // Session load / resume
+    // -------------------------------------------------------------------
+
+    /// Record a `session/load` (`replay: true`) or `session/resume` request.
+    ///
+    /// The session becomes the default if none is set yet, and is rehydrated
+    /// from its checkpoint. With `replay`, file accesses from the history
+    /// the agent streams back are recorded without heating files until the
+    /// load response arrives (see `finish_session_load`).
+    pub fn begin_session_load(&mut self, id: Option<u64>, session_id: &str, replay: bool) {
+        if session_id.is_empty() {
+            return;
+        }
+        if self.default_session_id.is_none() {
+            self.default_session_id = Some(session_id.to_string());
+        }
+        let session = self.ensure_session(session_id);
+        if replay {
+            session.begin_replay();
+        }
+        if let Some(id) = id {
+            self.pending_load_requests
+                .insert(id, session_id.to_string());
+        }
+    }
+
+    /// Handle the response to a load/resume request. Ends the replay and,
+    /// if the agent accepted the request, queues the session to be marked
+    /// resumed. Returns false if `id` wasn't a load/resume request.
+    pub fn finish_session_load(&mut self, id: u64, succeeded: bool) -> bool {
+        let Some(session_id) = self.pending_load_requests.remove(&id) else {
+            return false;
+        };
+        if let Some(session) = self.sessions.get_mut(&session_id) {
+            session.finish_replay();
+        }
+        if succeeded {
+            self.pending_resumed.push(session_id);
+        }
+        true
+    }
+
+    pub fn is_replaying(&self, session_id: &str) -> bool {
+        self.sessions
+            .get(session_id)
+            .is_some_and(|s| s.replay.is_some())
+    }
+
+    /// Note a `session/update` for turn bookkeeping during a replay.
+    /// `from_user` is true for user message chunks. No-op outside a replay.
+    pub fn replay_update(&mut self, session_id: &str, from_user: bool) {
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            session.replay_update(from_user);
+        }
+    }
+
+    /// Drain sessions resumed via `session/load` or `session/resume`.
+    /// Called by the tick loop to mark them in the `SessionRegistry`.
+    pub fn take_pending_resumed(&mut self) -> Vec<SessionKey> {
+        std::mem::take(&mut self.pending_resumed)
+            .into_iter()
+            .map(|session_id| SessionKey::new(&self.agent_id, &session_id))
+            .collect()
+    }
+
     /// Record a file access from any extraction channel.
     ///
     /// Sets heat to 1.0, marks the file as in-context, and updates the
     /// turn-accessed counter. If the file is new it is created.
     pub fn file_access(&mut self, path: &str, action: Action) {
         let session_id = self.session_id().to_string();
-        self.file_access_for_session(&session_id, path, action);
+        self.file_access_for_session(&session_id, path, action, Source::Api);
+    }
+
+    /// Record a file access observed on `source` for a session.
+    pub fn file_access_for_session(
+        &mut self,
+        session_id: &str,
+        path: &str,
+        action: Action,
+        source: Source,
+    ) {
+        self.file_access_lines_for_session(session_id, path, action, source, None);
     }
 
-    pub fn file_access_for_session(&mut self, session_id: &str, path: &str, action: Action) {
-        let normalized = match normalize_path(path, self.workspace_root.as_deref()) {
+    /// Record a file access that touched only `lines` (e.g. a tool call
+    /// location with a line, or a partial `fs/read_text_file`). The range
+    /// is merged into the file's `line_ranges`; open-ended ranges are
+    /// clamped to the file's length. With a symbol index set, the symbols
+    /// overlapping the range are recorded with the same action.
+    ///
+    /// Nothing is read from disk here: the file's token estimate and length
+    /// come from the cache and are refreshed by `apply_reads`. An
+    /// open-ended range on a file not estimated yet covers its first line
+    /// until then.
+    ///
+    /// `Action::Delete` drops the file's node instead of heating it.
+    pub fn file_access_lines_for_session(
+        &mut self,
+        session_id: &str,
+        path: &str,
+        action: Action,
+        source: Source,
+        lines: Option<LineRange>,
+    ) {
+        let normalized = match self.normalize(path) {
             Some(p) => p,
             None => return,
         };
-        self.ensure_session(session_id)
-            .file_access(&normalized, action);
+        let now = self.clock.now_ms();
+        if action == Action::Delete {
+            self.invalidate_symbols(&normalized);
+            let session = self.ensure_session(session_id);
+            session.record_event(TimelineEvent {
+                turn: session.current_turn,
+                timestamp_ms: now,
+                path: normalized.clone(),
+                action,
+                source,
+                from: None,
+            });
+            session.remove_file(&normalized);
+            return;
+        }
+        if matches!(action, Action::Write | Action::Move) {
+            self.invalidate_symbols(&normalized);
+        }
+        let estimate = self.cached_estimate(&normalized);
+        self.queue_estimate(&normalized);
+        if let Some(range) = lines.filter(|r| r.end == u32::MAX && estimate.is_none()) {
+            self.open_ranges.push(OpenRange {
+                session_id: session_id.to_string(),
+                path: normalized.clone(),
+                start: range.start,
+                action,
+                source,
+                ts: now,
+            });
+        }
+        let tokens = estimate.map(|e| e.tokens);
+        let lines = lines.map(|range| estimate.unwrap_or_default().clamp(range));
+        // Stale spans still attribute this access; the re-parse is for the
+        // next one
+        if lines.is_some()
+            && self
+                .symbols
+                .as_mut()
+                .is_some_and(|i| i.take_stale(&normalized))
+        {
+            let fs_path = self.resolve_path(&normalized);
+            self.pending_reads
+                .symbols
+                .insert(normalized.clone(), fs_path);
+            self.reads_wake.notify_one();
+        }
+        let symbol_ids: Vec<String> = match (&self.symbols, lines) {
+            (Some(index), Some(range)) => index
+                .enclosing(&normalized, range)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let session = self.ensure_session(session_id);
+        session.record_event(TimelineEvent {
+            turn: session.current_turn,
+            timestamp_ms: now,
+            path: normalized.clone(),
+            action,
+            source,
+            from: None,
+        });
+        if session.replay.is_some() {
+            session.replay_access(&normalized, action, source, tokens, lines);
+            for id in &symbol_ids {
+                session.replay_access(id, action, source, Some(0), None);
+            }
+        } else {
+            // File first: evicting it over budget would take its symbols too
+            session.file_access(&normalized, action, source, now, tokens, lines);
+            for id in &symbol_ids {
+                session.file_access(id, action, source, now, Some(0), None);
+            }
+        }
+    }
+
+    /// Record a file moved or renamed from `from` to `to`. The node carries
+    /// its heat and line ranges over to the new path and the old path goes
+    /// out as removed; the move's timeline event links the two so
+    /// `file_history` follows the file across it. A move of an untracked
+    /// file is recorded as a plain access to the new path.
+    pub fn file_move_for_session(
+        &mut self,
+        session_id: &str,
+        from: &str,
+        to: &str,
+        source: Source,
+    ) {
+        let (from, to) = match (self.normalize(from), self.normalize(to)) {
+            (Some(from), Some(to)) => (from, to),
+            // Moved somewhere we don't track (e.g. into `target/`)
+            (Some(from), None) => {
+                self.file_access_for_session(session_id, &from, Action::Delete, source);
+                return;
+            }
+            (None, Some(to)) => {
+                self.file_access_for_session(session_id, &to, Action::Move, source);
+                return;
+            }
+            (None, None) => return,
+        };
+        if from == to {
+            self.file_access_for_session(session_id, &to, Action::Move, source);
+            return;
+        }
+        self.invalidate_symbols(&from);
+        self.invalidate_symbols(&to);
+        let now = self.clock.now_ms();
+        let session = self.ensure_session(session_id);
+        if !session.move_file(&from, &to, source, now) {
+            self.file_access_for_session(session_id, &to, Action::Move, source);
+            return;
+        }
+        session.record_event(TimelineEvent {
+            turn: session.current_turn,
+            timestamp_ms: now,
+            path: to,
+            action: Action::Move,
+            source,
+            from: Some(from),
+        });
+    }
+
+    /// Cached token estimate for a tracked file, if it has been read.
+    fn cached_estimate(&mut self, path: &str) -> Option<TokenEstimate> {
+        let fs_path = self.resolve_path(path);
+        self.token_cache_uses += 1;
+        let uses = self.token_cache_uses;
+        let (estimate, used) = self.token_cache.get_mut(&fs_path)?;
+        *used = uses;
+        Some(*estimate)
+    }
+
+    /// Have a tracked file's estimate checked against the disk off the
+    /// lock, in case it changed.
+    fn queue_estimate(&mut self, path: &str) {
+        if self.pending_reads.estimates.contains_key(path) {
+            return;
+        }
+        let fs_path = self.resolve_path(path);
+        let cached = self
+            .token_cache
+            .get(&fs_path)
+            .map(|(estimate, _)| *estimate);
+        self.pending_reads
+            .estimates
+            .insert(path.to_string(), (fs_path, cached));
+        self.reads_wake.notify_one();
+    }
+
+    fn cache_estimate(&mut self, fs_path: PathBuf, estimate: TokenEstimate) {
+        self.token_cache_uses += 1;
+        let uses = self.token_cache_uses;
+        if self.token_cache.len() >= TOKEN_CACHE_CAPACITY
+            && !self.token_cache.contains_key(&fs_path)
+        {
+            let oldest = self
+                .token_cache
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                self.token_cache.remove(&oldest);
+            }
+        }
+        self.token_cache.insert(fs_path, (estimate, uses));
+    }
+
+    /// Notified whenever there are reads to take; see `file_reads::run`.
+    pub fn reads_wake(&self) -> Arc<Notify> {
+        self.reads_wake.clone()
+    }
+
+    /// Drain the disk reads queued since the last call, to be done off the
+    /// lock and handed back to `apply_reads`.
+    pub fn take_pending_reads(&mut self) -> PendingReads {
+        std::mem::take(&mut self.pending_reads)
+    }
+
+    /// Apply the results of `take_pending_reads`: cache the estimates,
+    /// update every session's node for the file (re-checking the budget),
+    /// widen open-ended ranges recorded before the file's length was known,
+    /// and swap in re-parsed symbol spans.
+    pub fn apply_reads(&mut self, done: CompletedReads) {
+        if let Some(index) = &mut self.symbols {
+            for (path, spans) in done.symbols {
+                index.set_file(&path, spans);
+            }
+        }
+        let mut lengths = HashMap::new();
+        for (path, fs_path, estimate) in done.estimates {
+            match estimate {
+                Some(estimate) => self.cache_estimate(fs_path, estimate),
+                None => {
+                    self.token_cache.remove(&fs_path);
+                }
+            }
+            let estimate = estimate.unwrap_or_default();
+            for session in self.sessions.values_mut() {
+                let Some(node) = session.files.get_mut(&path) else {
+                    continue;
+                };
+                if node.estimated_tokens != estimate.tokens {
+                    node.estimated_tokens = estimate.tokens;
+                    session.changed_paths.insert(path.clone());
+                    session.dirty = true;
+                    session.enforce_budget();
+                }
+            }
+            lengths.insert(path, estimate);
+        }
+
+        let (ready, waiting): (Vec<OpenRange>, Vec<OpenRange>) =
+            std::mem::take(&mut self.open_ranges)
+                .into_iter()
+                .partition(|open| lengths.contains_key(&open.path));
+        self.open_ranges = waiting;
+        for open in ready {
+            let range = lengths[&open.path].clamp(LineRange::from_line(open.start));
+            if range.end == range.start {
+                continue;
+            }
+            let symbol_ids: Vec<String> = self
+                .symbols
+                .as_ref()
+                .map(|index| {
+                    index
+                        .enclosing(&open.path, range)
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            let Some(session) = self.sessions.get_mut(&open.session_id) else {
+                continue;
+            };
+            let Some(node) = session.files.get_mut(&open.path) else {
+                continue;
+            };
+            node.add_line_range(range);
+            session.changed_paths.insert(open.path.clone());
+            session.dirty = true;
+            for id in &symbol_ids {
+                if session.replay.is_some() {
+                    session.replay_access(id, open.action, open.source, Some(0), None);
+                } else {
+                    session.file_access(id, open.action, open.source, open.ts, Some(0), None);
+                }
+            }
+        }
     }
 
     /// Record a token usage update from the agent.
@@ -379,13 +1414,82 @@ impl ContextTracker {
     /// fire-and-forget; the broadcast happens automatically.
     pub fn usage_update(&mut self, used: u32, size: u32) {
         let session_id = self.session_id().to_string();
-        self.usage_update_for_session(&session_id, used, size);
+        self.usage_update_for_session(&session_id, used, size, None);
+    }
+
+    /// Usage report for `session_id`. `cost` is the agent's cumulative
+    /// session cost, if it reported one; it updates the session's spend,
+    /// which every later usage message carries.
+    pub fn usage_update_for_session(
+        &mut self,
+        session_id: &str,
+        used: u32,
+        size: u32,
+        cost: Option<Cost>,
+    ) {
+        let agent_id = self.agent_id.clone();
+        self.ensure_session(session_id)
+            .usage_update(&agent_id, used, size, cost.as_ref());
+    }
+
+    /// Price table for estimating cost when the agent only reports tokens.
+    pub fn set_price_table(&mut self, prices: PriceTable) {
+        self.prices = prices;
+    }
+
+    pub fn price_table(&self) -> &PriceTable {
+        &self.prices
+    }
+
+    /// Model `session_id` runs on, used to price its token usage.
+    pub fn set_session_model(&mut self, session_id: &str, model_id: &str) {
+        let session = self.ensure_session(session_id);
+        session.model = Some(model_id.to_string());
+        session.dirty = true;
+    }
+
+    pub fn session_model(&self, session_id: &str) -> Option<&str> {
+        self.sessions.get(session_id)?.model.as_deref()
     }
 
-    pub fn usage_update_for_session(&mut self, session_id: &str, used: u32, size: u32) {
+    /// Token usage of one turn. Priced with the session's model and added
+    /// to its estimated spend; nothing happens if the model or its price
+    /// is unknown.
+    pub fn token_usage_for_session(&mut self, session_id: &str, usage: TokenUsage) {
+        let Some(cost) = self
+            .session_model(session_id)
+            .and_then(|model| self.prices.estimate(model, &usage))
+        else {
+            return;
+        };
         let agent_id = self.agent_id.clone();
         self.ensure_session(session_id)
-            .usage_update(&agent_id, used, size);
+            .add_estimate(&agent_id, cost);
+    }
+
+    /// Token usage carried by the response to `session/prompt` request
+    /// `id`, for the session that sent it. Call before
+    /// `end_turn_for_prompt_response`, which forgets the request.
+    pub fn token_usage_for_prompt_response(&mut self, id: u64, usage: TokenUsage) -> bool {
+        let Some(session_id) = self.pending_prompt_requests.get(&id).cloned() else {
+            return false;
+        };
+        self.token_usage_for_session(&session_id, usage);
+        true
+    }
+
+    /// Cost report that came without token counts.
+    pub fn cost_update_for_session(&mut self, session_id: &str, cost: Cost) {
+        let agent_id = self.agent_id.clone();
+        self.ensure_session(session_id)
+            .cost_update(&agent_id, &cost);
+    }
+
+    /// Cumulative cost reported for `session_id` so far, across agent
+    /// restarts, or the estimate from its token usage if the agent never
+    /// reported cost. `None` if neither is known.
+    pub fn session_spend(&self, session_id: &str) -> Option<Cost> {
+        self.sessions.get(session_id).and_then(SessionTracker::cost)
     }
 
     /// Drain any pending usage messages queued by `usage_update()`.
@@ -420,6 +1524,58 @@ impl ContextTracker {
         self.ensure_session(session_id).end_turn();
     }
 
+    // -------------------------------------------------------------------
+    // Hot zone — files the proxy keeps re-injecting into the prompt
+    // -------------------------------------------------------------------
+
+    /// Replace the hot zone for a session. Paths are normalized the same
+    /// way as file accesses; returns the normalized set (sorted).
+    pub fn set_hot_zone(&mut self, session_id: &str, paths: &[String]) -> Vec<String> {
+        let normalized: HashSet<String> = paths.iter().filter_map(|p| self.normalize(p)).collect();
+        let mut result: Vec<String> = normalized.iter().cloned().collect();
+        result.sort();
+        self.ensure_session(session_id).set_hot_zone(normalized);
+        result
+    }
+
+    /// Unpin every file for a session. Returns false if nothing was pinned.
+    pub fn clear_hot_zone(&mut self, session_id: &str) -> bool {
+        self.sessions
+            .get_mut(session_id)
+            .map(|s| s.clear_hot_zone())
+            .unwrap_or(false)
+    }
+
+    pub fn hot_zone(&self, session_id: &str) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .sessions
+            .get(session_id)
+            .map(|s| s.hot_zone.iter().cloned().collect())
+            .unwrap_or_default();
+        paths.sort();
+        paths
+    }
+
+    /// Drain the hot-zone paths that need re-injection for a session.
+    ///
+    /// Called by the upstream proxy right before forwarding a
+    /// `session/prompt`; the caller records each injected file as
+    /// `Action::Refreshed`.
+    pub fn take_pending_refresh(&mut self, session_id: &str) -> Vec<String> {
+        self.sessions
+            .get_mut(session_id)
+            .map(|s| s.take_pending_refresh())
+            .unwrap_or_default()
+    }
+
+    /// Queue hot-zone paths taken by `take_pending_refresh` again, e.g.
+    /// because they couldn't be read. Paths no longer pinned are dropped.
+    pub fn requeue_refresh(&mut self, session_id: &str, paths: &[String]) {
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            session.requeue_refresh(paths);
+        }
+    }
+
     /// Called every 100ms by the tick loop.
     ///
     /// Applies heat decay to non-context files, collects all changes since
@@ -432,20 +1588,22 @@ impl ContextTracker {
 
     pub fn tick_for_session(&mut self, session_id: &str) -> Option<Delta> {
         let agent_id = self.agent_id.clone();
+        let now = self.clock.now_ms();
         self.sessions
             .get_mut(session_id)
-            .and_then(|session| session.tick(&agent_id))
+            .and_then(|session| session.tick(&agent_id, now))
     }
 
     pub fn tick_all(&mut self) -> Vec<Delta> {
         let agent_id = self.agent_id.clone();
+        let now = self.clock.now_ms();
         let session_ids: Vec<String> = self.sessions.keys().cloned().collect();
         let mut deltas = Vec::new();
         for session_id in session_ids {
             if let Some(delta) = self
                 .sessions
                 .get_mut(&session_id)
-                .and_then(|session| session.tick(&agent_id))
+                .and_then(|session| session.tick(&agent_id, now))
             {
                 deltas.push(delta);
             }
@@ -476,6 +1634,59 @@ impl ContextTracker {
         )
     }
 
+    /// Recorded accesses for a session, oldest first. `turn` keeps only
+    /// that turn's events; `limit` keeps only the most recent ones.
+    pub fn timeline(
+        &self,
+        session_id: &str,
+        turn: Option<u32>,
+        limit: Option<usize>,
+    ) -> Vec<TimelineEvent> {
+        let Some(session) = self.sessions.get(session_id) else {
+            return Vec::new();
+        };
+        let mut events: Vec<TimelineEvent> = session
+            .timeline
+            .iter()
+            .filter(|e| turn.is_none_or(|t| e.turn == t))
+            .cloned()
+            .collect();
+        if let Some(limit) = limit {
+            events.drain(..events.len().saturating_sub(limit));
+        }
+        events
+    }
+
+    /// Every recorded access to `path` in a session, oldest first. The
+    /// path is normalized the same way as file accesses. Moves are followed
+    /// back, so a renamed file's history includes its old path's events.
+    pub fn file_history(&self, session_id: &str, path: &str) -> Vec<TimelineEvent> {
+        let (Some(session), Some(normalized)) =
+            (self.sessions.get(session_id), self.normalize(path))
+        else {
+            return Vec::new();
+        };
+        let mut current = normalized;
+        let mut events: Vec<TimelineEvent> = Vec::new();
+        for event in session.timeline.iter().rev() {
+            if event.path != current {
+                continue;
+            }
+            events.push(event.clone());
+            if let Some(from) = &event.from {
+                current = from.clone();
+            }
+        }
+        events.reverse();
+        events
+    }
+
+    /// Token budget of a session's context window, once the agent has
+    /// reported its size.
+    pub fn context_budget(&self, session_id: &str) -> Option<ContextBudget> {
+        self.sessions.get(session_id).and_then(|s| s.budget())
+    }
+
     /// Current sequence number (useful for tests / diagnostics).
     pub fn seq(&self) -> u64 {
         let session_id = self.session_id();
@@ -498,30 +1709,53 @@ impl ContextTracker {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::*;
+    use crate::clock::ManualClock;
+    use crate::parser::walk::count_tokens;
 
     fn default_tracker() -> ContextTracker {
         ContextTracker::new(TrackerConfig::default())
     }
 
+    /// Do the tracker's queued disk reads, as `file_reads::run` would.
+    fn settle(t: &mut ContextTracker) {
+        let done = t.take_pending_reads().read();
+        t.apply_reads(done);
+    }
+
     fn config_with(
         context_turns: u32,
         compaction_threshold: f32,
-        decay_rate: f32,
+        heat_half_life_ms: u64,
     ) -> TrackerConfig {
         TrackerConfig {
             context_turns,
             compaction_threshold,
-            decay_rate,
+            heat_half_life_ms,
+            ..TrackerConfig::default()
         }
     }
 
-    fn default_session<'a>(t: &'a ContextTracker) -> &'a SessionTracker {
+    /// Tracker on a manual clock, for tests that depend on decay.
+    fn manual_tracker(config: TrackerConfig) -> (ContextTracker, ManualClock) {
+        let clock = ManualClock::new(1_000_000);
+        let mut t = ContextTracker::new(config);
+        t.set_clock(clock.shared());
+        (t, clock)
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn default_session(t: &ContextTracker) -> &SessionTracker {
         let session_id = t.session_id();
         t.sessions.get(session_id).expect("default session missing")
     }
 
-    fn default_session_mut<'a>(t: &'a mut ContextTracker) -> &'a mut SessionTracker {
+    fn default_session_mut(t: &mut ContextTracker) -> &mut SessionTracker {
         let session_id = t.session_id().to_string();
         t.ensure_session(&session_id)
     }
@@ -633,7 +1867,7 @@ mod tests {
 
     #[test]
     fn file_exits_context_after_context_turns() {
-        let mut t = ContextTracker::new(config_with(2, 0.5, 0.95));
+        let mut t = ContextTracker::new(config_with(2, 0.5, 1_350));
         t.file_access("/a.rs", Action::Read); // turn 0
 
         // Still in context after 2 turns
@@ -648,7 +1882,7 @@ mod tests {
 
     #[test]
     fn re_access_keeps_file_in_context() {
-        let mut t = ContextTracker::new(config_with(1, 0.5, 0.95));
+        let mut t = ContextTracker::new(config_with(1, 0.5, 1_350));
         t.file_access("/a.rs", Action::Read); // turn 0
 
         t.end_turn(); // turn 1
@@ -687,32 +1921,56 @@ mod tests {
 
     #[test]
     fn tick_decays_non_context_files() {
-        let mut t = ContextTracker::new(config_with(0, 0.5, 0.90));
+        let (mut t, clock) = manual_tracker(config_with(0, 0.5, 1_000));
         t.file_access("/a.rs", Action::Read); // turn 0, in_context=true
 
         t.end_turn(); // turn 1, gap=1 > 0, file exits context
         assert!(!default_session(&t).files["/a.rs"].in_context);
 
         // First tick: drains dirty from file_access+end_turn AND applies
-        // decay (file is !in_context, heat=1.0 > 1.0*0.90 = 0.90)
+        // one half-life of decay (heat=1.0 > 0.5)
+        clock.advance(ms(1_000));
         let delta = t.tick();
         assert!(delta.is_some());
         let d = delta.unwrap();
         assert_eq!(d.updates.len(), 1);
-        assert!((d.updates[0].heat - 0.90).abs() < 0.001);
+        assert!((d.updates[0].heat - 0.5).abs() < 0.001);
 
-        // Second tick: 0.90 * 0.90 = 0.81
+        // Second tick, half a half-life later: 0.5 * 0.707 = 0.354
+        clock.advance(ms(500));
         let delta2 = t.tick().unwrap();
-        assert!((delta2.updates[0].heat - 0.81).abs() < 0.001);
+        assert!((delta2.updates[0].heat - 0.354).abs() < 0.001);
+
+        // No time passed, no decay
+        assert!(t.tick().is_none());
+    }
+
+    #[test]
+    fn decay_does_not_depend_on_tick_rate() {
+        let heat_after = |tick_ms: u64| {
+            let (mut t, clock) = manual_tracker(config_with(0, 0.5, 1_350));
+            t.file_access("/a.rs", Action::Read);
+            t.end_turn();
+            for _ in 0..(2_000 / tick_ms) {
+                clock.advance(ms(tick_ms));
+                t.tick();
+            }
+            default_session(&t).files["/a.rs"].heat
+        };
+        let fast = heat_after(100);
+        let idle = heat_after(500);
+        assert!((fast - idle).abs() < 0.001, "{fast} vs {idle}");
+        assert!((fast - 0.358).abs() < 0.001);
     }
 
     #[test]
     fn tick_clamps_heat_to_zero() {
-        let mut t = ContextTracker::new(config_with(0, 0.5, 0.001));
+        let (mut t, clock) = manual_tracker(config_with(0, 0.5, 10));
         t.file_access("/a.rs", Action::Read);
         t.end_turn(); // exits context
 
-        // First tick: heat=1.0 * 0.001 = 0.001 < 0.01 > clamped to 0.
+        // First tick: 10 half-lives, heat=1.0 * 2^-10 < 0.01 > clamped to 0.
+        clock.advance(ms(100));
         // File is removed (heat=0, !in_context).
         let delta = t.tick();
         assert!(delta.is_some());
@@ -825,17 +2083,90 @@ mod tests {
     }
 
     #[test]
-    fn take_pending_usage_drains() {
+    fn take_pending_usage_drains() {
+        let mut t = default_tracker();
+        t.usage_update(100_000, 200_000);
+        t.usage_update(50_000, 200_000);
+
+        let msgs = t.take_pending_usage();
+        assert_eq!(msgs.len(), 2);
+
+        // Second call returns empty
+        let msgs2 = t.take_pending_usage();
+        assert!(msgs2.is_empty());
+    }
+
+    fn usd(amount: f64) -> Cost {
+        Cost {
+            amount,
+            currency: "USD".to_string(),
+            estimated: false,
+        }
+    }
+
+    #[test]
+    fn reported_cost_accumulates_into_session_spend() {
+        let mut t = default_tracker();
+        assert!(t.session_spend("").is_none());
+        t.usage_update_for_session("", 1_000, 200_000, Some(usd(0.25)));
+        t.usage_update_for_session("", 2_000, 200_000, Some(usd(0.75)));
+        assert_eq!(t.session_spend(""), Some(usd(0.75)));
+
+        // The agent's counter restarted; earlier spend carries over
+        t.cost_update_for_session("", usd(0.5));
+        assert_eq!(t.session_spend(""), Some(usd(1.25)));
+
+        // Reports without cost keep the spend on the message
+        t.usage_update(3_000, 200_000);
+        let msgs = t.take_pending_usage();
+        assert_eq!(msgs.len(), 4);
+        assert_eq!(msgs[0].cost, Some(usd(0.25)));
+        // cost_update repeats the last window
+        assert_eq!((msgs[2].used, msgs[2].size), (2_000, 200_000));
+        assert_eq!(msgs[3].cost, Some(usd(1.25)));
+
+        // A new currency starts over
+        t.cost_update_for_session(
+            "",
+            Cost {
+                amount: 2.0,
+                currency: "EUR".to_string(),
+                estimated: false,
+            },
+        );
+        assert_eq!(t.session_spend("").unwrap().currency, "EUR");
+        assert_eq!(t.session_spend("").unwrap().amount, 2.0);
+    }
+
+    #[test]
+    fn token_usage_is_priced_until_the_agent_reports_cost() {
         let mut t = default_tracker();
-        t.usage_update(100_000, 200_000);
-        t.usage_update(50_000, 200_000);
-
+        let turn = TokenUsage {
+            input: 1_000_000,
+            output: 100_000,
+            ..TokenUsage::default()
+        };
+        // Unknown model: nothing to price
+        t.token_usage_for_session("", turn);
+        assert!(t.session_spend("").is_none());
+
+        t.set_session_model("", "claude-sonnet-4-20250514");
+        t.record_prompt_request(7, "");
+        assert!(t.token_usage_for_prompt_response(7, turn));
+        t.token_usage_for_session("", turn);
+        let spend = t.session_spend("").unwrap();
+        assert!((spend.amount - 9.0).abs() < 1e-9);
+        assert!(spend.estimated);
         let msgs = t.take_pending_usage();
         assert_eq!(msgs.len(), 2);
+        assert!(msgs[1].cost.as_ref().unwrap().estimated);
 
-        // Second call returns empty
-        let msgs2 = t.take_pending_usage();
-        assert!(msgs2.is_empty());
+        // A reported cost replaces the estimate
+        t.cost_update_for_session("", usd(2.0));
+        t.token_usage_for_session("", turn);
+        assert_eq!(t.session_spend(""), Some(usd(2.0)));
+        assert_eq!(t.take_pending_usage().len(), 1);
+        assert!(!t.token_usage_for_prompt_response(8, turn));
     }
 
     // ---------------------------------------------------------------
@@ -844,11 +2175,12 @@ mod tests {
 
     #[test]
     fn snapshot_excludes_cold_files() {
-        let mut t = ContextTracker::new(config_with(0, 0.5, 0.001));
+        let (mut t, clock) = manual_tracker(config_with(0, 0.5, 10));
         t.file_access("/a.rs", Action::Read);
         t.end_turn(); // exits context
 
         // Drain dirty + decay to zero
+        clock.advance(ms(100));
         t.tick();
         t.tick();
 
@@ -957,9 +2289,9 @@ mod tests {
         let mut t = default_tracker();
         t.file_access("", Action::Read);
 
+        // normalize_path drops empty paths before they reach the graph
         let snap = t.snapshot();
-        assert_eq!(snap.nodes.len(), 1);
-        assert!(snap.nodes.contains_key(""));
+        assert!(snap.nodes.is_empty());
     }
 
     // ---------------------------------------------------------------
@@ -968,7 +2300,7 @@ mod tests {
 
     #[test]
     fn thousand_nodes_tick_performance() {
-        let mut t = ContextTracker::new(config_with(0, 0.5, 0.95));
+        let (mut t, clock) = manual_tracker(config_with(0, 0.5, 1_350));
 
         // Add 1000 files
         for i in 0..1000 {
@@ -977,10 +2309,12 @@ mod tests {
         t.end_turn(); // all exit context
 
         // First tick: should process all 1000 nodes
+        clock.advance(ms(100));
         let delta = t.tick().unwrap();
         assert_eq!(delta.updates.len(), 1000);
 
         // Second tick: still 1000 decaying
+        clock.advance(ms(100));
         let delta2 = t.tick().unwrap();
         assert_eq!(delta2.updates.len(), 1000);
 
@@ -1120,11 +2454,12 @@ mod tests {
 
     #[test]
     fn tick_after_all_files_pruned() {
-        let mut t = ContextTracker::new(config_with(0, 0.5, 0.001));
+        let (mut t, clock) = manual_tracker(config_with(0, 0.5, 10));
         t.file_access("/a.rs", Action::Read);
         t.end_turn();
 
         // First tick prunes the file
+        clock.advance(ms(100));
         let d = t.tick().unwrap();
         assert!(!d.removed.is_empty());
 
@@ -1139,9 +2474,10 @@ mod tests {
 
     #[test]
     fn re_access_after_prune() {
-        let mut t = ContextTracker::new(config_with(0, 0.5, 0.001));
+        let (mut t, clock) = manual_tracker(config_with(0, 0.5, 10));
         t.file_access("/a.rs", Action::Read);
         t.end_turn();
+        clock.advance(ms(100));
         t.tick(); // prunes /a.rs
         assert!(!default_session(&t).files.contains_key("/a.rs"));
 
@@ -1173,4 +2509,754 @@ mod tests {
         assert_eq!(msgs[1].used, 110_000);
         assert_eq!(msgs[2].used, 120_000);
     }
+
+    // ---------------------------------------------------------------
+    // Hot zone re-injection
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn hot_zone_queues_files_not_in_context() {
+        let mut t = default_tracker();
+        t.file_access("/a.rs", Action::Read);
+
+        let pinned = t.set_hot_zone("", &["/a.rs".to_string(), "/b.rs".to_string()]);
+        assert_eq!(pinned, vec!["/a.rs".to_string(), "/b.rs".to_string()]);
+
+        // /a.rs is already in context, only /b.rs needs injecting
+        assert_eq!(t.take_pending_refresh(""), vec!["/b.rs".to_string()]);
+        assert!(t.take_pending_refresh("").is_empty());
+    }
+
+    #[test]
+    fn compaction_queues_whole_hot_zone() {
+        let mut t = default_tracker();
+        t.file_access("/a.rs", Action::Read);
+        t.file_access("/b.rs", Action::Read);
+        t.set_hot_zone("", &["/a.rs".to_string()]);
+        assert!(t.take_pending_refresh("").is_empty());
+
+        t.usage_update(180_000, 200_000);
+        t.usage_update(45_000, 200_000); // compaction
+
+        assert_eq!(t.take_pending_refresh(""), vec!["/a.rs".to_string()]);
+    }
+
+    #[test]
+    fn hot_file_leaving_context_is_queued() {
+        let mut t = ContextTracker::new(config_with(0, 0.5, 1_350));
+        t.file_access("/a.rs", Action::Read);
+        t.file_access("/b.rs", Action::Read);
+        t.set_hot_zone("", &["/a.rs".to_string()]);
+
+        t.end_turn(); // both exit context, only the pinned one is queued
+        assert_eq!(t.take_pending_refresh(""), vec!["/a.rs".to_string()]);
+    }
+
+    #[test]
+    fn clear_hot_zone_drops_pending() {
+        let mut t = default_tracker();
+        t.set_hot_zone("", &["/a.rs".to_string()]);
+        assert!(t.clear_hot_zone(""));
+        assert!(t.take_pending_refresh("").is_empty());
+        assert!(t.hot_zone("").is_empty());
+        assert!(!t.clear_hot_zone(""));
+    }
+
+    fn checkpointed_tracker(dir: &Path) -> ContextTracker {
+        let mut t = default_tracker();
+        t.set_agent_id("opencode-a1".to_string());
+        t.set_checkpoint_store(CheckpointStore::new(dir.to_path_buf()));
+        t
+    }
+
+    #[test]
+    fn checkpointed_state_is_restored_for_known_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut t = checkpointed_tracker(dir.path());
+        t.set_session_id("s1".to_string());
+        t.file_access("/a.rs", Action::Write);
+        t.usage_update_for_session("s1", 5_000, 200_000, Some(usd(0.3)));
+        t.set_session_model("s1", "gpt-4o");
+        t.end_turn();
+        t.set_hot_zone("s1", &["/a.rs".to_string()]);
+        let seq = t.tick().unwrap().seq;
+        assert_eq!(t.save_checkpoints().unwrap(), 1);
+        // Nothing changed since the last write
+        assert_eq!(t.save_checkpoints().unwrap(), 0);
+
+        // A fresh process sees the session again
+        let mut t = checkpointed_tracker(dir.path());
+        t.track_session("s1");
+        assert_eq!(t.session_ids(), vec!["s1".to_string()]);
+        let snap = t.snapshot_for_session("s1");
+        assert_eq!(snap.seq, seq);
+        assert!(snap.nodes["/a.rs"].in_context);
+        assert_eq!(t.hot_zone("s1"), vec!["/a.rs".to_string()]);
+        assert_eq!(t.file_history("s1", "/a.rs").len(), 1);
+        assert_eq!(t.session_spend("s1"), Some(usd(0.3)));
+        assert_eq!(t.session_model("s1"), Some("gpt-4o"));
+
+        t.set_session_id("s1".to_string());
+        assert_eq!(t.current_turn(), 1);
+        // Restored files are re-broadcast with the next delta
+        let delta = t.tick().unwrap();
+        assert_eq!(delta.seq, seq + 1);
+        assert_eq!(delta.updates.len(), 1);
+
+        // Unknown sessions and other agents start empty
+        t.track_session("s2");
+        assert!(t.snapshot_for_session("s2").nodes.is_empty());
+        let mut other = default_tracker();
+        other.set_agent_id("claude-b2".to_string());
+        other.set_checkpoint_store(CheckpointStore::new(dir.path().to_path_buf()));
+        other.track_session("s1");
+        assert!(other.snapshot_for_session("s1").nodes.is_empty());
+    }
+
+    #[test]
+    fn expired_checkpoints_are_not_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, clock) = manual_tracker(TrackerConfig::default());
+        t.set_agent_id("opencode-a1".to_string());
+        let store = CheckpointStore::new(dir.path().to_path_buf()).with_max_age(ms(60_000));
+        t.set_checkpoint_store(store.clone());
+        t.set_session_id("s1".to_string());
+        t.file_access("/a.rs", Action::Read);
+        t.save_checkpoints().unwrap();
+
+        let restart = |clock: &ManualClock| {
+            let mut t = ContextTracker::new(TrackerConfig::default());
+            t.set_clock(clock.shared());
+            t.set_agent_id("opencode-a1".to_string());
+            t.set_checkpoint_store(store.clone());
+            t.track_session("s1");
+            t.snapshot_for_session("s1").nodes.len()
+        };
+        clock.advance(ms(60_000));
+        assert_eq!(restart(&clock), 1);
+        clock.advance(ms(1));
+        assert_eq!(restart(&clock), 0);
+    }
+
+    // ---------------------------------------------------------------
+    // token budget
+    // ---------------------------------------------------------------
+
+    /// Tracker rooted at `dir` with one file per `(name, repeat)` pair.
+    fn tracker_with_files(dir: &Path, files: &[(&str, usize)]) -> (ContextTracker, ManualClock) {
+        for (name, repeat) in files {
+            std::fs::write(dir.join(name), "fn item() { let x = 1; }\n".repeat(*repeat)).unwrap();
+        }
+        let (mut t, clock) = manual_tracker(TrackerConfig::default());
+        t.set_workspace_root(dir.to_path_buf());
+        (t, clock)
+    }
+
+    fn tokens_of(dir: &Path, name: &str) -> u32 {
+        count_tokens(&std::fs::read_to_string(dir.join(name)).unwrap())
+    }
+
+    #[test]
+    fn file_access_estimates_tokens_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, _clock) = tracker_with_files(dir.path(), &[("a.rs", 20)]);
+        t.file_access("a.rs", Action::Read);
+        t.file_access("missing.rs", Action::Read);
+        settle(&mut t);
+
+        let snap = t.snapshot();
+        let expected = tokens_of(dir.path(), "a.rs");
+        assert!(expected > 0);
+        assert_eq!(snap.nodes["a.rs"].estimated_tokens, expected);
+        assert_eq!(snap.nodes["missing.rs"].estimated_tokens, 0);
+        // No budget until the agent reports its window
+        assert!(snap.budget.is_none());
+
+        t.usage_update(5_000, 200_000);
+        let budget = t.snapshot().budget.unwrap();
+        assert_eq!(budget.size, 200_000);
+        assert_eq!(budget.used, 5_000);
+        assert_eq!(budget.estimated, expected);
+        assert_eq!(t.context_budget(""), Some(budget));
+    }
+
+    #[test]
+    fn over_budget_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, clock) =
+            tracker_with_files(dir.path(), &[("a.rs", 40), ("b.rs", 40), ("c.rs", 40)]);
+        let per_file = tokens_of(dir.path(), "a.rs");
+        t.usage_update(1_000, per_file * 2);
+
+        t.file_access("a.rs", Action::Read);
+        clock.advance(ms(10));
+        t.file_access("b.rs", Action::Read);
+        clock.advance(ms(10));
+        // Re-reading a keeps it fresh; b is now the oldest
+        t.file_access("a.rs", Action::Read);
+        clock.advance(ms(10));
+        t.file_access("c.rs", Action::Read);
+        settle(&mut t);
+
+        let snap = t.snapshot();
+        assert!(snap.nodes["a.rs"].in_context);
+        assert!(!snap.nodes["b.rs"].in_context);
+        assert!(snap.nodes["c.rs"].in_context);
+        assert_eq!(snap.budget.unwrap().estimated, per_file * 2);
+
+        // A smaller window evicts down to the most recent file
+        t.usage_update(1_000, per_file);
+        let snap = t.snapshot();
+        assert!(!snap.nodes["a.rs"].in_context);
+        assert!(snap.nodes["c.rs"].in_context);
+
+        // Even a file bigger than the whole window stays in context
+        t.usage_update(1_000, 1);
+        assert!(t.snapshot().nodes["c.rs"].in_context);
+    }
+
+    // ---------------------------------------------------------------
+    // line ranges
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn line_ranges_merge_overlapping_and_adjacent() {
+        let mut t = default_tracker();
+        let s = "";
+        for range in [
+            LineRange::new(20, 30),
+            LineRange::new(1, 5),
+            LineRange::new(6, 8),
+            LineRange::new(25, 40),
+            LineRange::line(50),
+        ] {
+            t.file_access_lines_for_session(s, "/a.rs", Action::Read, Source::Api, Some(range));
+        }
+        // Whole-file accesses leave the ranges alone
+        t.file_access("/a.rs", Action::Read);
+
+        let node = &t.snapshot().nodes["/a.rs"];
+        assert_eq!(
+            node.line_ranges,
+            vec![
+                LineRange::new(1, 8),
+                LineRange::new(20, 40),
+                LineRange::line(50)
+            ]
+        );
+        assert_eq!(
+            node.to_update().line_ranges,
+            node.line_ranges,
+            "ranges go out with deltas"
+        );
+    }
+
+    #[test]
+    fn open_ended_ranges_clamp_to_file_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, _clock) = tracker_with_files(dir.path(), &[("a.rs", 12)]);
+        t.file_access_lines_for_session(
+            "",
+            "a.rs",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::from_line(4)),
+        );
+        // Unknown length: only the first line is certain
+        t.file_access_lines_for_session(
+            "",
+            "missing.rs",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::from_line(4)),
+        );
+        // Neither length is known until the files are read
+        assert_eq!(
+            t.snapshot().nodes["a.rs"].line_ranges,
+            vec![LineRange::line(4)]
+        );
+        settle(&mut t);
+
+        let snap = t.snapshot();
+        assert_eq!(snap.nodes["a.rs"].line_ranges, vec![LineRange::new(4, 12)]);
+        assert_eq!(
+            snap.nodes["missing.rs"].line_ranges,
+            vec![LineRange::line(4)]
+        );
+    }
+
+    #[test]
+    fn large_files_are_estimated_from_a_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        // 6000 lines of 100 bytes, past MAX_TOKENIZED_BYTES
+        std::fs::write(
+            dir.path().join("big.log"),
+            format!("{}\n", "x".repeat(99)).repeat(6000),
+        )
+        .unwrap();
+        let (mut t, _clock) = tracker_with_files(dir.path(), &[]);
+        t.file_access_lines_for_session(
+            "",
+            "big.log",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::from_line(10)),
+        );
+        settle(&mut t);
+
+        let node = &t.snapshot().nodes["big.log"];
+        assert_eq!(node.estimated_tokens, 600_000 / 4);
+        let end = node.line_ranges[0].end;
+        assert!((5_900..=6_000).contains(&end), "extrapolated {end} lines");
+    }
+
+    #[test]
+    fn token_cache_is_bounded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, _clock) = tracker_with_files(dir.path(), &[]);
+        for i in 0..=TOKEN_CACHE_CAPACITY {
+            let name = format!("f{i}.rs");
+            std::fs::write(dir.path().join(&name), "fn f() {}\n").unwrap();
+            t.file_access(&name, Action::Read);
+            settle(&mut t);
+        }
+        assert_eq!(t.token_cache.len(), TOKEN_CACHE_CAPACITY);
+        // The least recently used entry made room for the newest
+        assert!(!t.token_cache.contains_key(&dir.path().join("f0.rs")));
+        let newest = format!("f{TOKEN_CACHE_CAPACITY}.rs");
+        assert!(t.token_cache.contains_key(&dir.path().join(newest)));
+    }
+
+    // ---------------------------------------------------------------
+    // symbols
+    // ---------------------------------------------------------------
+
+    fn symbol_tracker(dir: &Path) -> (ContextTracker, ManualClock) {
+        std::fs::write(
+            dir.join("shapes.py"),
+            "class Circle:\n    def area(self):\n        return 1\n\n\ndef unit():\n    return Circle()\n",
+        )
+        .unwrap();
+        let tree = crate::parser::tree::SymbolTree::init_tree(dir).unwrap();
+        let (mut t, clock) = tracker_with_files(dir, &[]);
+        t.set_symbol_index(SymbolIndex::build(&tree, dir));
+        (t, clock)
+    }
+
+    #[test]
+    fn line_accesses_heat_enclosing_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, _clock) = symbol_tracker(dir.path());
+        t.file_access_lines_for_session(
+            "",
+            "shapes.py",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::line(3)),
+        );
+        // Whole-file accesses can't be attributed
+        t.file_access("shapes.py", Action::Write);
+
+        let snap = t.snapshot();
+        let area = &snap.nodes["shapes.py::Circle::area"];
+        assert_eq!(area.last_action, Action::Read);
+        assert!(area.in_context);
+        assert_eq!(area.estimated_tokens, 0);
+        assert!(snap.nodes.contains_key("shapes.py::Circle"));
+        assert!(!snap.nodes.contains_key("shapes.py::unit"));
+        // Symbols don't count against the budget twice
+        t.usage_update(100, 100_000);
+        assert_eq!(
+            t.context_budget("").unwrap().estimated,
+            snap.nodes["shapes.py"].estimated_tokens
+        );
+
+        let delta = t.tick().unwrap();
+        let mut paths: Vec<&str> = delta.updates.iter().map(|u| u.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec!["shapes.py", "shapes.py::Circle", "shapes.py::Circle::area"]
+        );
+    }
+
+    #[test]
+    fn written_files_get_fresh_symbol_spans() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, _clock) = symbol_tracker(dir.path());
+        std::fs::write(
+            dir.path().join("shapes.py"),
+            "def square():\n    return 1\n\n\ndef circle():\n    return 2\n",
+        )
+        .unwrap();
+        t.file_access("shapes.py", Action::Write);
+        let read_line_2 = |t: &mut ContextTracker| {
+            t.file_access_lines_for_session(
+                "",
+                "shapes.py",
+                Action::Read,
+                Source::Api,
+                Some(LineRange::line(2)),
+            )
+        };
+        // The old spans attribute the access that triggers the re-parse
+        read_line_2(&mut t);
+        assert!(t.snapshot().nodes.contains_key("shapes.py::Circle::area"));
+        assert!(!t.snapshot().nodes.contains_key("shapes.py::square"));
+        settle(&mut t);
+        read_line_2(&mut t);
+        assert!(t.snapshot().nodes.contains_key("shapes.py::square"));
+
+        // Out-of-band edits reported by the watcher invalidate too
+        std::fs::write(dir.path().join("shapes.py"), "def other():\n    pass\n").unwrap();
+        t.watcher_change(&dir.path().join("shapes.py").to_string_lossy());
+        t.file_access_lines_for_session(
+            "",
+            "shapes.py",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::line(1)),
+        );
+        settle(&mut t);
+        t.file_access_lines_for_session(
+            "",
+            "shapes.py",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::line(1)),
+        );
+        assert!(t.snapshot().nodes.contains_key("shapes.py::other"));
+    }
+
+    #[test]
+    fn evicted_file_takes_its_symbols_out_of_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, clock) = symbol_tracker(dir.path());
+        std::fs::write(dir.path().join("big.rs"), "let x = 1;\n".repeat(200)).unwrap();
+        t.file_access_lines_for_session(
+            "",
+            "shapes.py",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::line(6)),
+        );
+        clock.advance(ms(10));
+        t.file_access("big.rs", Action::Read);
+        settle(&mut t);
+
+        let big = t.snapshot().nodes["big.rs"].estimated_tokens;
+        t.usage_update(100, big);
+        let snap = t.snapshot();
+        assert!(!snap.nodes["shapes.py"].in_context);
+        assert!(!snap.nodes["shapes.py::unit"].in_context);
+        assert!(snap.nodes["big.rs"].in_context);
+    }
+
+    // ---------------------------------------------------------------
+    // timeline
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn timeline_records_every_access_by_turn() {
+        let (mut t, clock) = manual_tracker(TrackerConfig::default());
+        t.set_session_id("s1".to_string());
+        t.file_access_for_session("s1", "/a.rs", Action::Read, Source::Fs);
+        clock.advance(ms(5));
+        t.file_access_for_session("s1", "/b.rs", Action::Search, Source::SearchResult);
+        t.end_turn();
+        clock.advance(ms(5));
+        t.file_access_for_session("s1", "/a.rs", Action::Write, Source::Diff);
+
+        let all = t.timeline("s1", None, None);
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            all[0],
+            TimelineEvent {
+                turn: 0,
+                timestamp_ms: 1_000_000,
+                path: "/a.rs".to_string(),
+                action: Action::Read,
+                source: Source::Fs,
+                from: None,
+            }
+        );
+        assert_eq!(all[2].turn, 1);
+        assert_eq!(all[2].timestamp_ms, 1_000_010);
+
+        let turn0: Vec<String> = t
+            .timeline("s1", Some(0), None)
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(turn0, vec!["/a.rs", "/b.rs"]);
+        assert_eq!(t.timeline("s1", None, Some(1))[0].action, Action::Write);
+
+        // History keeps the first read even though the node now says Write
+        let history = t.file_history("s1", "/a.rs");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].action, Action::Read);
+        assert_eq!(t.snapshot().nodes["/a.rs"].last_action, Action::Write);
+        assert!(t.file_history("s2", "/a.rs").is_empty());
+    }
+
+    #[test]
+    fn timeline_drops_oldest_events_past_the_limit() {
+        let mut t = ContextTracker::new(TrackerConfig {
+            timeline_limit: 2,
+            ..TrackerConfig::default()
+        });
+        for path in ["/a.rs", "/b.rs", "/c.rs"] {
+            t.file_access(path, Action::Read);
+        }
+        let paths: Vec<String> = t
+            .timeline("", None, None)
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        assert_eq!(paths, vec!["/b.rs", "/c.rs"]);
+        assert!(t.file_history("", "/a.rs").is_empty());
+    }
+
+    // ---------------------------------------------------------------
+    // delete / move
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn delete_removes_node_immediately() {
+        let mut t = default_tracker();
+        t.file_access("/a.rs", Action::Write);
+        t.file_access("/b.rs", Action::Read);
+        t.tick();
+        t.set_hot_zone("", &["/a.rs".to_string()]);
+
+        t.file_access("/a.rs", Action::Delete);
+        let snap = t.snapshot();
+        assert!(!snap.nodes.contains_key("/a.rs"));
+        assert!(snap.nodes.contains_key("/b.rs"));
+        assert!(t.hot_zone("").is_empty());
+
+        let d = t.tick().unwrap();
+        assert!(d.updates.is_empty());
+        assert_eq!(d.removed, vec!["/a.rs".to_string()]);
+        let history = t.file_history("", "/a.rs");
+        assert_eq!(history.last().unwrap().action, Action::Delete);
+    }
+
+    #[test]
+    fn delete_takes_symbols_with_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut t, _clock) = symbol_tracker(dir.path());
+        t.file_access_lines_for_session(
+            "",
+            "shapes.py",
+            Action::Read,
+            Source::Api,
+            Some(LineRange::line(3)),
+        );
+        t.tick();
+        t.file_access("shapes.py", Action::Delete);
+        assert!(t.snapshot().nodes.is_empty());
+        let mut removed = t.tick().unwrap().removed;
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec!["shapes.py", "shapes.py::Circle", "shapes.py::Circle::area"]
+        );
+    }
+
+    #[test]
+    fn move_carries_node_to_new_path() {
+        let (mut t, clock) = manual_tracker(TrackerConfig::default());
+        t.file_access_lines_for_session(
+            "",
+            "/a.rs",
+            Action::Read,
+            Source::Fs,
+            Some(LineRange::new(1, 5)),
+        );
+        t.set_hot_zone("", &["/a.rs".to_string()]);
+        for _ in 0..4 {
+            t.end_turn();
+        }
+        clock.advance(ms(1_000));
+        t.tick();
+        let before = t.snapshot().nodes["/a.rs"].clone();
+        assert!(!before.in_context);
+        assert!(before.heat < 1.0);
+
+        t.file_move_for_session("", "/a.rs", "/b.rs", Source::ToolCall);
+        let snap = t.snapshot();
+        assert!(!snap.nodes.contains_key("/a.rs"));
+        let moved = &snap.nodes["/b.rs"];
+        assert_eq!(moved.heat, before.heat);
+        assert_eq!(moved.in_context, before.in_context);
+        assert_eq!(moved.turn_accessed, before.turn_accessed);
+        assert_eq!(moved.line_ranges, before.line_ranges);
+        assert_eq!(moved.last_action, Action::Move);
+        assert_eq!(moved.source, Source::ToolCall);
+        assert_eq!(t.hot_zone(""), vec!["/b.rs".to_string()]);
+
+        let d = t.tick().unwrap();
+        assert_eq!(d.removed, vec!["/a.rs".to_string()]);
+        assert_eq!(d.updates.len(), 1);
+        assert_eq!(d.updates[0].path, "/b.rs");
+    }
+
+    #[test]
+    fn move_history_follows_the_file() {
+        let mut t = default_tracker();
+        t.file_access("/a.rs", Action::Read);
+        t.file_access("/other.rs", Action::Read);
+        t.file_move_for_session("", "/a.rs", "/b.rs", Source::ToolCall);
+        t.file_access("/b.rs", Action::Write);
+        // A new file at the old path has its own history
+        t.file_access("/a.rs", Action::Write);
+
+        let history = t.file_history("", "/b.rs");
+        let steps: Vec<(&str, Action)> = history
+            .iter()
+            .map(|e| (e.path.as_str(), e.action))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("/a.rs", Action::Read),
+                ("/b.rs", Action::Move),
+                ("/b.rs", Action::Write),
+            ]
+        );
+        assert_eq!(history[1].from.as_deref(), Some("/a.rs"));
+        assert_eq!(t.file_history("", "/a.rs").len(), 2);
+    }
+
+    #[test]
+    fn move_of_untracked_file_is_an_access() {
+        let mut t = default_tracker();
+        t.file_move_for_session("", "/a.rs", "/b.rs", Source::ToolCall);
+        let snap = t.snapshot();
+        assert!(!snap.nodes.contains_key("/a.rs"));
+        assert_eq!(snap.nodes["/b.rs"].heat, 1.0);
+        assert_eq!(snap.nodes["/b.rs"].last_action, Action::Move);
+    }
+
+    // ---------------------------------------------------------------
+    // watcher
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn watcher_changes_only_count_during_a_turn() {
+        let mut t = default_tracker();
+        assert!(!t.watcher_change("/gen.rs"));
+        assert!(t.snapshot().nodes.is_empty());
+
+        t.record_prompt_request(1, "s1");
+        t.record_prompt_request(2, "s2");
+        assert!(t.watcher_change("/gen.rs"));
+        // Attributed to the most recent prompt's session
+        let node = &t.snapshot_for_session("s2").nodes["/gen.rs"];
+        assert_eq!(node.last_action, Action::Write);
+        assert_eq!(node.source, Source::Watcher);
+        assert!(node.confidence < 1.0);
+        assert!(t.snapshot_for_session("s1").nodes.is_empty());
+        // Ignored dirs are dropped like any other access
+        assert!(!t.watcher_change("/target/debug/app"));
+
+        t.end_turn_for_prompt_response(2);
+        t.end_turn_for_prompt_response(1);
+        assert!(!t.watcher_change("/other.rs"));
+    }
+
+    #[test]
+    fn watcher_skips_files_already_written_this_turn() {
+        let mut t = default_tracker();
+        t.record_prompt_request(1, "");
+        t.file_access_for_session("", "/a.rs", Action::Write, Source::Fs);
+        assert!(!t.watcher_change("/a.rs"));
+        assert_eq!(t.snapshot().nodes["/a.rs"].source, Source::Fs);
+        assert_eq!(t.file_history("", "/a.rs").len(), 1);
+
+        // A read doesn't explain the change
+        t.file_access_for_session("", "/b.rs", Action::Read, Source::Fs);
+        assert!(t.watcher_change("/b.rs"));
+        assert_eq!(t.snapshot().nodes["/b.rs"].last_action, Action::Write);
+    }
+
+    // ---------------------------------------------------------------
+    // ignore policy
+    // ---------------------------------------------------------------
+
+    #[test]
+    fn workspace_ignore_files_and_overrides_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(dir.path().join(".eisenignore"), "!dist\n").unwrap();
+        let mut t = ContextTracker::new(TrackerConfig {
+            ignore: vec!["fixtures".to_string()],
+            ..TrackerConfig::default()
+        });
+        t.set_workspace_root(dir.path().to_path_buf());
+
+        for path in [
+            "server.log",
+            "tests/fixtures/a.json",
+            "node_modules/x/index.js",
+            ".github/workflows/ci.yml",
+            "dist/app.js",
+            "src/main.rs",
+        ] {
+            t.file_access(path, Action::Read);
+        }
+        let mut paths: Vec<String> = t.snapshot().nodes.into_keys().collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![".github/workflows/ci.yml", "dist/app.js", "src/main.rs"]
+        );
+    }
+
+    // ---------------------------------------------------------------
+    // workspace roots
+    // ---------------------------------------------------------------
+
+    fn multi_root_tracker() -> ContextTracker {
+        let mut t = default_tracker();
+        t.set_workspace_root(PathBuf::from("/ws/app"));
+        t.add_workspace_root(NamedRoot::new("libs", PathBuf::from("/ws/libs")).unwrap());
+        t
+    }
+
+    #[test]
+    fn named_roots_and_external_paths_get_prefixed_ids() {
+        let mut t = multi_root_tracker();
+        t.file_access("/ws/app/src/main.rs", Action::Read);
+        t.file_access("/ws/libs/core/lib.rs", Action::Read);
+        t.file_access("/etc/hosts", Action::Read);
+        // Ignore rules apply below each root
+        t.file_access("/ws/libs/node_modules/x/index.js", Action::Read);
+        t.file_access("/ws/libs", Action::Read);
+
+        let mut paths: Vec<String> = t.snapshot().nodes.into_keys().collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec!["external:/etc/hosts", "libs:core/lib.rs", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn prefixed_ids_resolve_back_to_their_root() {
+        let t = multi_root_tracker();
+        assert_eq!(
+            t.resolve_path("src/main.rs"),
+            PathBuf::from("/ws/app/src/main.rs")
+        );
+        assert_eq!(
+            t.resolve_path("libs:core/lib.rs"),
+            PathBuf::from("/ws/libs/core/lib.rs")
+        );
+        assert_eq!(
+            t.resolve_path("external:/etc/hosts"),
+            PathBuf::from("/etc/hosts")
+        );
+    }
 }
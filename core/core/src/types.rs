@@ -1,7 +1,11 @@
+use anyhow::{anyhow, bail};
+use ignore::gitignore::{Gitignore, GitignoreBuilder, Glob};
+use ignore::Match;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::paths::{self, PathResolver, ResolvedPath};
 
 // ---------------------------------------------------------------------------
 // Action — the type of file access observed from ACP messages
@@ -22,6 +26,74 @@ pub enum Action {
     Search,
     /// Agent attempted out-of-zone file access (blocked by proxy)
     Blocked,
+    /// Agent made an out-of-zone file access that was let through
+    /// (zone in warn mode, or a tool call reported after the fact)
+    Warned,
+    /// Proxy re-injected a hot-zone file into the prompt after it left context
+    Refreshed,
+    /// Agent deleted file. The node is dropped rather than stored.
+    Delete,
+    /// Agent moved or renamed file; the node now lives at the new path
+    Move,
+}
+
+// ---------------------------------------------------------------------------
+// Source — the extraction channel an access was observed on
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// Embedded resource or resource link in `session/prompt`
+    Prompt,
+    /// Location on a `tool_call` / `tool_call_update`
+    ToolCall,
+    /// Diff block in tool call content
+    Diff,
+    /// Path parsed from a search tool's text output
+    SearchResult,
+    /// Redirect target parsed from a shell command title
+    ShellRedirect,
+    /// Path parsed from a `terminal/output` response
+    TerminalOutput,
+    /// `fs/read_text_file` or `fs/write_text_file` request
+    Fs,
+    /// Recorded by the proxy itself (hot-zone refresh, zone enforcement)
+    Proxy,
+    /// Change seen by the filesystem watcher during a turn
+    Watcher,
+    /// Recorded directly through the tracker API
+    #[default]
+    Api,
+}
+
+impl Source {
+    /// How likely an access from this source is to be real, 0.0 to 1.0.
+    /// Structured ACP fields are trusted; paths scraped from free text are
+    /// not.
+    pub fn confidence(self) -> f32 {
+        match self {
+            Source::Prompt
+            | Source::ToolCall
+            | Source::Diff
+            | Source::Fs
+            | Source::Proxy
+            | Source::Api => 1.0,
+            // The change is real, but the user or another process may have
+            // made it rather than the agent
+            Source::Watcher => 0.8,
+            // Line-leading paths with an extension in search tool output
+            Source::SearchResult => 0.6,
+            // `2>&1`, `> /dev/null` and the like parse as targets too
+            Source::ShellRedirect => 0.4,
+            // Any line starting with `/`
+            Source::TerminalOutput => 0.3,
+        }
+    }
+}
+
+fn full_confidence() -> f32 {
+    1.0
 }
 
 // ---------------------------------------------------------------------------
@@ -42,6 +114,63 @@ pub struct FileNode {
     /// Wall-clock milliseconds (epoch) when this file was last accessed.
     /// Used by the orchestrator for LWW merge ordering across agents.
     pub timestamp_ms: u64,
+    /// Estimated tokens the file occupies in the agent's context
+    /// (o200k_base count of its contents; 0 if it couldn't be read)
+    #[serde(default)]
+    pub estimated_tokens: u32,
+    /// Lines the agent is known to have accessed, sorted and merged.
+    /// Empty when only whole-file accesses were seen.
+    #[serde(default)]
+    pub line_ranges: Vec<LineRange>,
+    /// Channel the most recent access was extracted from
+    #[serde(default)]
+    pub source: Source,
+    /// Highest `Source::confidence` of any access to this node, so a file
+    /// confirmed by a tool call isn't hidden by a later heuristic hit
+    #[serde(default = "full_confidence")]
+    pub confidence: f32,
+}
+
+/// An inclusive, 1-based range of lines within a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl LineRange {
+    pub fn new(start: u32, end: u32) -> Self {
+        let start = start.max(1);
+        Self {
+            start,
+            end: end.max(start),
+        }
+    }
+
+    /// A single line.
+    pub fn line(line: u32) -> Self {
+        Self::new(line, line)
+    }
+
+    /// Lines from `start` to the end of the file. The tracker clamps the
+    /// end once it knows how long the file is.
+    pub fn from_line(start: u32) -> Self {
+        Self::new(start, u32::MAX)
+    }
+}
+
+/// One recorded access in a session's timeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub turn: u32,
+    /// Clock milliseconds when the access was recorded
+    pub timestamp_ms: u64,
+    pub path: String,
+    pub action: Action,
+    pub source: Source,
+    /// Previous path of a moved file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
 }
 
 // ---------------------------------------------------------------------------
@@ -57,6 +186,14 @@ pub struct NodeUpdate {
     pub turn_accessed: u32,
     /// Wall-clock milliseconds (epoch) when this event was recorded.
     pub timestamp_ms: u64,
+    #[serde(default)]
+    pub estimated_tokens: u32,
+    #[serde(default)]
+    pub line_ranges: Vec<LineRange>,
+    #[serde(default)]
+    pub source: Source,
+    #[serde(default = "full_confidence")]
+    pub confidence: f32,
 }
 
 // ---------------------------------------------------------------------------
@@ -109,6 +246,15 @@ pub struct SessionState {
     pub providers: Vec<SessionKey>,
     pub created_at_ms: u64,
     pub updated_at_ms: u64,
+    /// Last time the agent was reattached to this session via
+    /// `session/load` or `session/resume`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_at_ms: Option<u64>,
+    /// Cumulative cost the agent reported for this session. Kept by the
+    /// tracker and filled in by `get_session_state`; the registry doesn't
+    /// store it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spend: Option<Cost>,
 }
 
 impl SessionState {
@@ -125,6 +271,8 @@ pub struct SessionSummary {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<SessionModel>,
     pub updated_at_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resumed_at_ms: Option<u64>,
     pub is_active: bool,
 }
 
@@ -142,6 +290,21 @@ pub struct Snapshot {
     pub session_mode: SessionMode,
     pub seq: u64,
     pub nodes: HashMap<String, FileNode>,
+    /// Token budget of the session's context window; absent until the
+    /// agent has reported its window size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget: Option<ContextBudget>,
+}
+
+/// How full a session's context window is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContextBudget {
+    /// Window size reported by the agent
+    pub size: u32,
+    /// Tokens the agent reports as used
+    pub used: u32,
+    /// Sum of `estimated_tokens` over the in-context files
+    pub estimated: u32,
 }
 
 /// Incremental update — only changed nodes since last emission.
@@ -171,10 +334,68 @@ pub struct UsageMessage {
     pub cost: Option<Cost>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Cost {
     pub amount: f64,
     pub currency: String,
+    /// Computed from token counts and the local price table rather than
+    /// reported by the agent (see `pricing.rs`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+/// Tokens a prompt turn used, from the agent's `PromptResponse` usage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+    pub thought: u64,
+    pub cache_read: u64,
+    pub cache_write: u64,
+}
+
+/// Running total of a session's cost reports.
+///
+/// Agents report cumulative session cost, so each report replaces the last.
+/// A report below the previous one means the agent's counter restarted
+/// (e.g. a new agent process after `session/resume`), and what was spent
+/// before carries over. A report in another currency starts over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Spend {
+    /// Spent before the agent's counter last restarted.
+    pub carried: f64,
+    /// The agent's latest cumulative report.
+    pub reported: f64,
+    pub currency: String,
+}
+
+impl Spend {
+    pub fn new(cost: &Cost) -> Self {
+        Self {
+            carried: 0.0,
+            reported: cost.amount,
+            currency: cost.currency.clone(),
+        }
+    }
+
+    pub fn record(&mut self, cost: &Cost) {
+        if cost.currency != self.currency {
+            *self = Self::new(cost);
+            return;
+        }
+        if cost.amount < self.reported {
+            self.carried += self.reported;
+        }
+        self.reported = cost.amount;
+    }
+
+    pub fn total(&self) -> Cost {
+        Cost {
+            amount: self.carried + self.reported,
+            currency: self.currency.clone(),
+            estimated: false,
+        }
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -249,8 +470,15 @@ pub struct TrackerConfig {
     pub context_turns: u32,
     /// Usage drop ratio that signals compaction (default: 0.5)
     pub compaction_threshold: f32,
-    /// Heat multiplier per tick for non-context files (default: 0.95)
-    pub decay_rate: f32,
+    /// Time for a non-context file's heat to halve, in milliseconds
+    /// (default: 1350, about 0.95 per 100ms tick)
+    pub heat_half_life_ms: u64,
+    /// Access events kept per session for the timeline; the oldest are
+    /// dropped first (default: 10000)
+    pub timeline_limit: usize,
+    /// Extra gitignore-style patterns, applied after `.gitignore` and
+    /// `.eisenignore`; `!pattern` re-includes (default: none)
+    pub ignore: Vec<String>,
 }
 
 impl Default for TrackerConfig {
@@ -258,7 +486,9 @@ impl Default for TrackerConfig {
         Self {
             context_turns: 3,
             compaction_threshold: 0.5,
-            decay_rate: 0.95,
+            heat_half_life_ms: 1_350,
+            timeline_limit: 10_000,
+            ignore: Vec::new(),
         }
     }
 }
@@ -267,16 +497,59 @@ impl Default for TrackerConfig {
 // Zone configuration — blocker zone enforcement (Phase 3)
 // ---------------------------------------------------------------------------
 
+/// Whether a zone blocks violations, only reports them, or is disabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneMode {
+    /// Block out-of-zone access (JSON-RPC error / rejected permission)
+    #[default]
+    Enforce,
+    /// Forward out-of-zone access but broadcast `BlockedAccess` with
+    /// `enforced: false` and record it in the audit log
+    Warn,
+    /// Zone is configured but not checked
+    Off,
+}
+
+/// How the proxy handles out-of-zone edits made by the agent's own tools
+/// (reported via `session/update` tool calls rather than `fs/*` requests).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallPolicy {
+    /// Reject `session/request_permission` for out-of-zone tool calls.
+    /// Tool calls that never ask for permission are still reported.
+    #[default]
+    Deny,
+    /// Let the tool call through and broadcast `BlockedAccess` afterwards.
+    Report,
+}
+
 /// Zone configuration for agent file access enforcement.
 ///
 /// When a zone is configured, the proxy blocks file reads/writes outside
 /// the allowed patterns. Denied patterns take priority over allowed.
+/// Compile with [`ZoneConfig::compile`] before checking paths. Unknown
+/// fields are rejected so a misspelled key can't silently widen a zone.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ZoneConfig {
     /// Glob patterns for allowed paths (e.g., ["src/ui/**", "shared/**"])
     pub allowed: Vec<String>,
     /// Glob patterns for explicitly denied paths (e.g., ["**/.env"])
+    #[serde(default)]
     pub denied: Vec<String>,
+    /// Enforce, warn or off (default: enforce)
+    #[serde(default)]
+    pub mode: ZoneMode,
+    /// Handling of out-of-zone tool calls (default: deny via permission flow)
+    #[serde(default)]
+    pub tool_calls: ToolCallPolicy,
+    /// Strip out-of-zone paths from search results before they reach the editor
+    #[serde(default)]
+    pub redact_search_results: bool,
+    /// Match patterns case-insensitively (default: case-sensitive)
+    #[serde(default)]
+    pub case_insensitive: bool,
 }
 
 impl ZoneConfig {
@@ -285,130 +558,195 @@ impl ZoneConfig {
         Self {
             allowed,
             denied: Vec::new(),
+            mode: ZoneMode::default(),
+            tool_calls: ToolCallPolicy::default(),
+            redact_search_results: false,
+            case_insensitive: false,
         }
     }
 
     /// Check if a path is permitted under this zone configuration.
     ///
+    /// Convenience wrapper that compiles the patterns on every call; callers
+    /// checking many paths should use [`ZoneConfig::compile`] once instead.
+    /// A config with invalid patterns allows nothing.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.compile()
+            .map(|zone| zone.is_allowed(path))
+            .unwrap_or(false)
+    }
+
+    /// Compile the allowed/denied patterns into a [`CompiledZone`].
+    ///
+    /// Patterns use gitignore syntax (`*`, `**`, `?`, `[a-z]`, `{a,b}`, and
+    /// `!` to negate an earlier pattern in the same list) and anchoring: a
+    /// pattern containing a `/` other than a trailing one is relative to the
+    /// workspace root, anything else matches at any depth. `package.json`
+    /// matches `src/package.json` too; `/package.json` only the top-level
+    /// file.
+    ///
+    /// Returns an error naming the offending pattern if any fails to parse.
+    pub fn compile(&self) -> anyhow::Result<CompiledZone> {
+        Ok(CompiledZone {
+            allowed: compile_patterns(&self.allowed, self.case_insensitive)?,
+            denied: compile_patterns(&self.denied, self.case_insensitive)?,
+            config: self.clone(),
+        })
+    }
+}
+
+/// A [`ZoneConfig`] with its patterns compiled into gitignore matchers.
+///
+/// Derefs to the underlying config for `mode`, `tool_calls`, etc.
+#[derive(Debug, Clone)]
+pub struct CompiledZone {
+    config: ZoneConfig,
+    allowed: Gitignore,
+    denied: Gitignore,
+}
+
+impl CompiledZone {
+    pub fn config(&self) -> &ZoneConfig {
+        &self.config
+    }
+
+    /// Same zone with a different mode. The patterns are not recompiled.
+    pub fn with_mode(&self, mode: ZoneMode) -> Self {
+        let mut zone = self.clone();
+        zone.config.mode = mode;
+        zone
+    }
+
+    /// Check if a path is permitted under this zone.
+    ///
     /// A path is allowed if:
-    /// 1. It matches at least one allowed pattern, AND
-    /// 2. It does NOT match any denied pattern (denied overrides allowed)
+    /// 1. It matches an allowed pattern (and is not re-excluded by a later
+    ///    `!` pattern), AND
+    /// 2. It does NOT match a denied pattern (denied overrides allowed)
     ///
-    /// Paths are matched against glob patterns using a simple glob matcher.
-    /// Both the path and patterns are compared after stripping any leading `/`.
+    /// A pattern matching a directory also matches everything below it.
+    /// `.`/`..` segments are collapsed first and any leading `/` is ignored;
+    /// a path that climbs out through `..` is never allowed. Use
+    /// `is_path_allowed` for paths that may be absolute.
     pub fn is_allowed(&self, path: &str) -> bool {
-        let normalized = path.strip_prefix('/').unwrap_or(path);
+        self.explain(path).is_allowed()
+    }
 
-        // Denied patterns take priority
-        for pattern in &self.denied {
-            let pat = pattern.strip_prefix('/').unwrap_or(pattern);
-            if glob_match(pat, normalized) {
-                return false;
+    /// Check an agent-reported path, resolving it against the workspace
+    /// root first. Paths outside the workspace and traversal escapes are
+    /// always denied.
+    pub fn is_path_allowed(&self, path: &str, resolver: &PathResolver) -> bool {
+        self.explain_path(path, resolver).is_allowed()
+    }
+
+    /// Like [`CompiledZone::is_allowed`], but reports which pattern decided.
+    pub fn explain(&self, path: &str) -> ZoneDecision {
+        let normalized = match paths::resolve(path, None, false) {
+            ResolvedPath::Inside(normalized) => normalized,
+            ResolvedPath::Outside(_) | ResolvedPath::Escape => {
+                return ZoneDecision::OutsideWorkspace
             }
+        };
+        let normalized = normalized.strip_prefix('/').unwrap_or(&normalized);
+        if normalized.is_empty() {
+            return ZoneDecision::NotAllowed;
         }
 
-        // Must match at least one allowed pattern
-        for pattern in &self.allowed {
-            let pat = pattern.strip_prefix('/').unwrap_or(pattern);
-            if glob_match(pat, normalized) {
-                return true;
-            }
+        // Denied patterns take priority
+        if let Match::Ignore(glob) = self.denied.matched_path_or_any_parents(normalized, false) {
+            return ZoneDecision::Denied {
+                pattern: display_pattern(glob),
+            };
         }
 
-        false
+        // Must match at least one allowed pattern
+        match self.allowed.matched_path_or_any_parents(normalized, false) {
+            Match::Ignore(glob) => ZoneDecision::Allowed {
+                pattern: display_pattern(glob),
+            },
+            Match::Whitelist(glob) => ZoneDecision::Excluded {
+                pattern: display_pattern(glob),
+            },
+            Match::None => ZoneDecision::NotAllowed,
+        }
     }
-}
 
-/// Simple glob matching supporting `*` (single segment) and `**` (any depth).
-///
-/// This is a minimal implementation sufficient for workspace path matching.
-/// Supports patterns like:
-///   - `src/ui/**`       matches `src/ui/foo.ts`, `src/ui/sub/bar.tsx`
-///   - `*.config.js`     matches `eslint.config.js`
-///   - `package.json`    matches `package.json` exactly
-///   - `**/.env`         matches `.env`, `sub/.env`, `a/b/.env`
-fn glob_match(pattern: &str, path: &str) -> bool {
-    glob_match_impl(
-        &pattern.split('/').collect::<Vec<_>>(),
-        &path.split('/').collect::<Vec<_>>(),
-    )
-}
-
-fn glob_match_impl(pattern_parts: &[&str], path_parts: &[&str]) -> bool {
-    if pattern_parts.is_empty() {
-        return path_parts.is_empty();
-    }
-
-    let pat = pattern_parts[0];
-
-    if pat == "**" {
-        // "**" can match zero or more path segments
-        // Try matching remaining pattern against every suffix of the path
-        for i in 0..=path_parts.len() {
-            if glob_match_impl(&pattern_parts[1..], &path_parts[i..]) {
-                return true;
-            }
+    /// Like [`CompiledZone::is_path_allowed`], but reports which pattern
+    /// decided.
+    pub fn explain_path(&self, path: &str, resolver: &PathResolver) -> ZoneDecision {
+        match resolver.resolve(path) {
+            ResolvedPath::Inside(relative) => self.explain(&relative),
+            ResolvedPath::Outside(_) | ResolvedPath::Escape => ZoneDecision::OutsideWorkspace,
         }
-        return false;
     }
+}
 
-    if path_parts.is_empty() {
-        return false;
+/// Why a zone allows or denies a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZoneDecision {
+    /// Matched this allowed pattern
+    Allowed { pattern: String },
+    /// Matched this denied pattern (denied overrides allowed)
+    Denied { pattern: String },
+    /// Re-excluded from the allowed set by this `!` pattern
+    Excluded { pattern: String },
+    /// No allowed pattern matches
+    NotAllowed,
+    /// Outside the workspace root, or escapes it through `..` or a symlink
+    OutsideWorkspace,
+}
+
+impl ZoneDecision {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, ZoneDecision::Allowed { .. })
     }
+}
 
-    // Match single segment (supports `*` as wildcard within segment)
-    if segment_match(pat, path_parts[0]) {
-        glob_match_impl(&pattern_parts[1..], &path_parts[1..])
-    } else {
-        false
-    }
+/// Pattern as the user wrote it.
+fn display_pattern(glob: &Glob) -> String {
+    glob.original().to_string()
 }
 
-/// Match a single path segment against a pattern segment.
-/// Supports `*` as a wildcard matching any characters within the segment.
-fn segment_match(pattern: &str, segment: &str) -> bool {
-    if pattern == "*" {
-        return true;
-    }
-    if !pattern.contains('*') {
-        return pattern == segment;
-    }
+impl std::ops::Deref for CompiledZone {
+    type Target = ZoneConfig;
 
-    // Split pattern by '*' and match parts
-    let parts: Vec<&str> = pattern.split('*').collect();
-    let mut pos = 0;
+    fn deref(&self) -> &ZoneConfig {
+        &self.config
+    }
+}
 
-    for (i, part) in parts.iter().enumerate() {
-        if part.is_empty() {
-            continue;
+/// Build a gitignore matcher from zone patterns. Patterns are added as
+/// written, so they anchor the way gitignore lines do.
+fn compile_patterns(patterns: &[String], case_insensitive: bool) -> anyhow::Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new("");
+    builder.case_insensitive(case_insensitive)?;
+    for pattern in patterns {
+        let body = pattern.strip_prefix('!').unwrap_or(pattern);
+        if body.trim_start_matches('/').trim().is_empty() {
+            bail!("invalid zone pattern {pattern:?}: pattern is empty");
         }
-        if let Some(found) = segment[pos..].find(part) {
-            if i == 0 && found != 0 {
-                // First part must match at the start
-                return false;
-            }
-            pos += found + part.len();
-        } else {
-            return false;
+        // A gitignore line starting with `#` is a comment and would never match
+        if pattern.starts_with('#') {
+            bail!("invalid zone pattern {pattern:?}: escape a leading `#` as `\\#`");
         }
+        builder
+            .add_line(None, pattern)
+            .map_err(|e| anyhow!("invalid zone pattern {pattern:?}: {e}"))?;
     }
-
-    // If pattern doesn't end with *, remaining segment must be consumed
-    if !pattern.ends_with('*') {
-        return pos == segment.len();
-    }
-
-    true
+    builder
+        .build()
+        .map_err(|e| anyhow!("invalid zone patterns: {e}"))
 }
 
 // ---------------------------------------------------------------------------
 // BlockedAccess — wire message for out-of-zone access attempts
 // ---------------------------------------------------------------------------
 
-/// Notification broadcast when the proxy blocks an out-of-zone file access.
+/// Notification broadcast when the proxy detects an out-of-zone file access.
 ///
 /// Sent over TCP so the orchestrator (Python) can detect blocked attempts
-/// and route them through the A2A router.
+/// and route them through the A2A router. `enforced` is false when the
+/// access went through anyway (warn mode, or a tool call reported after it ran).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockedAccess {
     #[serde(rename = "type")]
@@ -416,24 +754,63 @@ pub struct BlockedAccess {
     pub agent_id: String,
     pub session_id: String,
     pub path: String,
-    /// "read" or "write"
+    /// "read", "write" or "search"
     pub action: String,
+    /// Whether the proxy actually prevented the access
+    pub enforced: bool,
     pub timestamp_ms: u64,
 }
 
 impl BlockedAccess {
-    pub fn new(agent_id: &str, session_id: &str, path: &str, action: &str) -> Self {
-        let ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
+    pub fn new(
+        agent_id: &str,
+        session_id: &str,
+        path: &str,
+        action: &str,
+        enforced: bool,
+        timestamp_ms: u64,
+    ) -> Self {
         Self {
             msg_type: "blocked".to_string(),
             agent_id: agent_id.to_string(),
             session_id: session_id.to_string(),
             path: path.to_string(),
             action: action.to_string(),
-            timestamp_ms: ts,
+            enforced,
+            timestamp_ms,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ZoneChanged — wire message for runtime zone reconfiguration
+// ---------------------------------------------------------------------------
+
+/// Notification broadcast when a session's zone is set or cleared at runtime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneChanged {
+    #[serde(rename = "type")]
+    pub msg_type: String, // always "zone_changed"
+    pub agent_id: String,
+    pub session_id: String,
+    /// Zone now in effect for the session; null means unrestricted
+    pub zone: Option<ZoneConfig>,
+    pub timestamp_ms: u64,
+}
+
+impl ZoneChanged {
+    pub fn new(
+        agent_id: &str,
+        session_id: &str,
+        zone: Option<ZoneConfig>,
+        timestamp_ms: u64,
+    ) -> Self {
+        Self {
+            msg_type: "zone_changed".to_string(),
+            agent_id: agent_id.to_string(),
+            session_id: session_id.to_string(),
+            zone,
+            timestamp_ms,
         }
     }
 }
@@ -457,8 +834,14 @@ impl Snapshot {
             session_mode,
             seq,
             nodes,
+            budget: None,
         }
     }
+
+    pub fn with_budget(mut self, budget: Option<ContextBudget>) -> Self {
+        self.budget = budget;
+        self
+    }
 }
 
 impl Delta {
@@ -512,7 +895,29 @@ impl FileNode {
             last_action: self.last_action,
             turn_accessed: self.turn_accessed,
             timestamp_ms: self.timestamp_ms,
+            estimated_tokens: self.estimated_tokens,
+            line_ranges: self.line_ranges.clone(),
+            source: self.source,
+            confidence: self.confidence,
+        }
+    }
+
+    /// Add `range` to `line_ranges`, merging it with any ranges it
+    /// overlaps or touches.
+    pub fn add_line_range(&mut self, range: LineRange) {
+        let ranges = &mut self.line_ranges;
+        let at = ranges.partition_point(|r| r.start < range.start);
+        ranges.insert(at, range);
+        let mut merged: Vec<LineRange> = Vec::with_capacity(ranges.len());
+        for r in ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if r.start <= last.end.saturating_add(1) => {
+                    last.end = last.end.max(r.end);
+                }
+                _ => merged.push(r),
+            }
         }
+        *ranges = merged;
     }
 }
 
@@ -0,0 +1,189 @@
+//! Filesystem watcher for edits that never show up in ACP.
+//!
+//! Agents can change files through their own shell (`sed -i`,
+//! `git checkout`, code generators) without listing them in a tool call's
+//! locations. With `observe --watch` the workspace root is watched
+//! recursively, and files created or modified while a turn is in flight are
+//! recorded as writes by the active session (see
+//! `ContextTracker::watcher_change`).
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, warn};
+
+use crate::ignore_policy::IgnorePolicy;
+use crate::tracker::ContextTracker;
+
+/// Keeps the OS watch alive; dropping it stops watching.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Watch `root` recursively and forward every changed file that
+    /// `ignore` doesn't exclude to `tx`.
+    pub fn start(
+        root: &Path,
+        ignore: IgnorePolicy,
+        tx: mpsc::UnboundedSender<PathBuf>,
+    ) -> Result<Self> {
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!(error = %e, "file watcher error");
+                    return;
+                }
+            };
+            for path in changed_files(&event, &ignore) {
+                // Receiver gone means the proxy is shutting down
+                let _ = tx.send(path);
+            }
+        })
+        .context("failed to create file watcher")?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch {}", root.display()))?;
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Files an event created or changed the contents of. Metadata-only
+/// changes, accesses and removals don't count; neither do directories or
+/// ignored paths.
+fn changed_files(event: &Event, ignore: &IgnorePolicy) -> Vec<PathBuf> {
+    let paths: &[PathBuf] = match event.kind {
+        EventKind::Create(_)
+        | EventKind::Modify(ModifyKind::Data(_))
+        | EventKind::Modify(ModifyKind::Any) => &event.paths,
+        // Renames report the destination last
+        EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both)) => event
+            .paths
+            .last()
+            .map(std::slice::from_ref)
+            .unwrap_or_default(),
+        _ => return Vec::new(),
+    };
+    paths
+        .iter()
+        .filter(|path| !ignore.is_ignored(path, false) && !path.is_dir())
+        .cloned()
+        .collect()
+}
+
+/// Feed watcher changes into the tracker until the watcher is dropped.
+pub async fn run(tracker: Arc<Mutex<ContextTracker>>, mut rx: mpsc::UnboundedReceiver<PathBuf>) {
+    while let Some(path) = rx.recv().await {
+        let path = path.to_string_lossy();
+        if tracker.lock().await.watcher_change(&path) {
+            debug!(path = %path, "watcher write");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, MetadataKind};
+
+    fn policy(root: &Path) -> IgnorePolicy {
+        IgnorePolicy::load(Some(root), &[])
+    }
+
+    fn event(kind: EventKind, paths: &[&Path]) -> Event {
+        paths
+            .iter()
+            .fold(Event::new(kind), |e, p| e.add_path(p.to_path_buf()))
+    }
+
+    #[test]
+    fn content_changes_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.rs");
+        std::fs::write(&file, "fn a() {}").unwrap();
+
+        let created = event(EventKind::Create(CreateKind::File), &[&file]);
+        assert_eq!(
+            changed_files(&created, &policy(dir.path())),
+            vec![file.clone()]
+        );
+        let modified = event(
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            &[&file],
+        );
+        assert_eq!(
+            changed_files(&modified, &policy(dir.path())),
+            vec![file.clone()]
+        );
+
+        let touched = event(
+            EventKind::Modify(ModifyKind::Metadata(MetadataKind::WriteTime)),
+            &[&file],
+        );
+        assert!(changed_files(&touched, &policy(dir.path())).is_empty());
+        let removed = event(EventKind::Remove(notify::event::RemoveKind::File), &[&file]);
+        assert!(changed_files(&removed, &policy(dir.path())).is_empty());
+    }
+
+    #[test]
+    fn renames_report_the_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("a.rs");
+        let to = dir.path().join("b.rs");
+        let renamed = event(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            &[&from, &to],
+        );
+        assert_eq!(changed_files(&renamed, &policy(dir.path())), vec![to]);
+    }
+
+    #[test]
+    fn ignored_dirs_and_directories_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        let created = event(
+            EventKind::Create(CreateKind::Any),
+            &[
+                &dir.path().join("target/debug/app"),
+                &dir.path().join(".git/index"),
+                &dir.path().join("src"),
+            ],
+        );
+        assert!(changed_files(&created, &policy(dir.path())).is_empty());
+    }
+
+    #[tokio::test]
+    async fn watched_edits_reach_the_tracker_during_a_turn() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let mut t = ContextTracker::new(Default::default());
+        t.set_workspace_root(root.clone());
+        t.record_prompt_request(1, "");
+        let tracker = Arc::new(Mutex::new(t));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let _watcher = FileWatcher::start(&root, policy(&root), tx).unwrap();
+        let task = tokio::spawn(run(tracker.clone(), rx));
+        std::fs::write(root.join("gen.rs"), "fn generated() {}").unwrap();
+
+        let mut recorded = false;
+        for _ in 0..100 {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            if tracker.lock().await.snapshot().nodes.contains_key("gen.rs") {
+                recorded = true;
+                break;
+            }
+        }
+        task.abort();
+        assert!(recorded, "watcher change never reached the tracker");
+        let t = tracker.lock().await;
+        let node = &t.snapshot().nodes["gen.rs"];
+        assert_eq!(node.last_action, crate::types::Action::Write);
+        assert_eq!(node.source, crate::types::Source::Watcher);
+    }
+}
@@ -0,0 +1,429 @@
+//! Declarative zone profiles loaded from `.eisen/zones.toml`.
+//!
+//! Instead of repeating `--zone`/`--deny` flags, a workspace can describe
+//! its zones once:
+//!
+//! ```toml
+//! default = "ui"                        # profile for unassigned agents
+//! shared = ["package.json", "docs/**"]  # allowed in every profile
+//!
+//! [profiles.ui]
+//! allowed = ["src/ui/**"]
+//! denied = ["src/ui/**/*.secret"]
+//!
+//! [profiles.core]
+//! allowed = ["core/**"]
+//! mode = "warn"
+//!
+//! [agents]
+//! opencode = "core"                     # matches "opencode" and "opencode-*"
+//! ```
+//!
+//! Profiles take every `ZoneConfig` field; unknown keys are an error.
+//! `observe` picks the profile assigned to its `--agent-id` and reloads it
+//! when the file changes. Its `--zone-mode`, `--zone-case-insensitive`,
+//! `--tool-calls` and `--redact-search` flags apply on top of the profile,
+//! on every reload.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::paths::PathResolver;
+use crate::tracker::ContextTracker;
+use crate::types::{CompiledZone, SessionKey, ToolCallPolicy, ZoneConfig, ZoneDecision, ZoneMode};
+use crate::zone_registry::ZoneRegistry;
+
+/// Zone file location, relative to the workspace root.
+pub const ZONE_FILE: &str = ".eisen/zones.toml";
+
+/// How often `watch` checks the zone file for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings given on the command line that override the selected profile's.
+/// Unset fields keep the profile's value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileOverrides {
+    pub mode: Option<ZoneMode>,
+    pub tool_calls: Option<ToolCallPolicy>,
+    /// Only ever turns case-insensitive matching on
+    pub case_insensitive: bool,
+    /// Only ever turns redaction on
+    pub redact_search_results: bool,
+}
+
+impl ProfileOverrides {
+    pub fn apply(&self, mut config: ZoneConfig) -> ZoneConfig {
+        if let Some(mode) = self.mode {
+            config.mode = mode;
+        }
+        if let Some(tool_calls) = self.tool_calls {
+            config.tool_calls = tool_calls;
+        }
+        config.case_insensitive |= self.case_insensitive;
+        config.redact_search_results |= self.redact_search_results;
+        config
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneFile {
+    /// Profile used for agents without an entry in `agents`
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Patterns allowed in every profile
+    #[serde(default)]
+    pub shared: Vec<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ZoneConfig>,
+    /// Agent ID (or ID prefix) -> profile name
+    #[serde(default)]
+    pub agents: BTreeMap<String, String>,
+}
+
+impl ZoneFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&raw).with_context(|| format!("invalid zone file {}", path.display()))
+    }
+
+    /// Parse and validate a zone file: profile references must exist and
+    /// every profile must compile.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let file: ZoneFile = toml::from_str(raw)?;
+        if let Some(name) = &file.default {
+            if !file.profiles.contains_key(name) {
+                bail!("default profile {name:?} is not defined");
+            }
+        }
+        for (agent, name) in &file.agents {
+            if !file.profiles.contains_key(name) {
+                bail!("agent {agent:?} is assigned to undefined profile {name:?}");
+            }
+        }
+        for name in file.profiles.keys() {
+            file.compile_profile(name)?;
+        }
+        Ok(file)
+    }
+
+    /// Profile assigned to an agent. An `agents` key matches the exact ID
+    /// or, since instance IDs carry a random suffix, any ID starting with
+    /// `<key>-`; the longest matching key wins. Falls back to `default`.
+    pub fn profile_name_for(&self, agent_id: &str) -> Option<&str> {
+        if let Some(name) = self.agents.get(agent_id) {
+            return Some(name);
+        }
+        self.agents
+            .iter()
+            .filter(|(key, _)| {
+                agent_id
+                    .strip_prefix(key.as_str())
+                    .is_some_and(|rest| rest.starts_with('-'))
+            })
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, name)| name.as_str())
+            .or(self.default.as_deref())
+    }
+
+    /// A profile's config with the shared patterns added to `allowed`.
+    ///
+    /// Shared patterns come last, so they win over `!` exclusions in the
+    /// profile; `denied` still overrides them.
+    pub fn profile(&self, name: &str) -> Option<ZoneConfig> {
+        let mut config = self.profiles.get(name)?.clone();
+        config.allowed.extend(self.shared.iter().cloned());
+        Some(config)
+    }
+
+    pub fn compile_profile(&self, name: &str) -> Result<CompiledZone> {
+        self.compile_profile_with(name, &ProfileOverrides::default())
+    }
+
+    fn compile_profile_with(
+        &self,
+        name: &str,
+        overrides: &ProfileOverrides,
+    ) -> Result<CompiledZone> {
+        let Some(config) = self.profile(name) else {
+            bail!("profile {name:?} is not defined");
+        };
+        overrides
+            .apply(config)
+            .compile()
+            .with_context(|| format!("profile {name:?}"))
+    }
+
+    /// Compiled zone for an agent, with `overrides` applied, or `None` if no
+    /// profile applies.
+    pub fn zone_for_agent(
+        &self,
+        agent_id: &str,
+        overrides: &ProfileOverrides,
+    ) -> Result<Option<CompiledZone>> {
+        self.profile_name_for(agent_id)
+            .map(|name| self.compile_profile_with(name, overrides))
+            .transpose()
+    }
+
+    /// Check a path against the given profiles (all profiles if `names` is
+    /// empty), explaining each decision.
+    pub fn check(
+        &self,
+        path: &str,
+        resolver: &PathResolver,
+        names: &[String],
+    ) -> Result<Vec<ProfileCheck>> {
+        let names: Vec<&String> = if names.is_empty() {
+            self.profiles.keys().collect()
+        } else {
+            names.iter().collect()
+        };
+        names
+            .into_iter()
+            .map(|name| {
+                let zone = self.compile_profile(name)?;
+                let decision = zone.explain_path(path, resolver);
+                let shared = matches!(
+                    &decision,
+                    ZoneDecision::Allowed { pattern } if self.shared.contains(pattern)
+                        && !self.profiles[name].allowed.contains(pattern)
+                );
+                Ok(ProfileCheck {
+                    profile: name.clone(),
+                    mode: zone.mode,
+                    decision,
+                    shared,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Result of checking a path against one profile.
+#[derive(Debug, Clone)]
+pub struct ProfileCheck {
+    pub profile: String,
+    pub mode: ZoneMode,
+    pub decision: ZoneDecision,
+    /// The allowing pattern came from the top-level `shared` list
+    pub shared: bool,
+}
+
+impl std::fmt::Display for ProfileCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let verdict = if self.decision.is_allowed() {
+            "allowed"
+        } else {
+            "denied"
+        };
+        write!(f, "{}: {verdict} — ", self.profile)?;
+        match &self.decision {
+            ZoneDecision::Allowed { pattern } if self.shared => {
+                write!(f, "matches shared pattern {pattern:?}")?
+            }
+            ZoneDecision::Allowed { pattern } => write!(f, "matches allowed pattern {pattern:?}")?,
+            ZoneDecision::Denied { pattern } => write!(f, "matches denied pattern {pattern:?}")?,
+            ZoneDecision::Excluded { pattern } => write!(f, "excluded by {pattern:?}")?,
+            ZoneDecision::NotAllowed => write!(f, "no allowed pattern matches")?,
+            ZoneDecision::OutsideWorkspace => write!(f, "outside the workspace root")?,
+        }
+        match self.mode {
+            ZoneMode::Enforce => Ok(()),
+            ZoneMode::Warn => write!(f, " (warn mode: not enforced)"),
+            ZoneMode::Off => write!(f, " (zone off: not checked)"),
+        }
+    }
+}
+
+/// Default zone file location for a workspace.
+pub fn default_path(root: &Path) -> PathBuf {
+    root.join(ZONE_FILE)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Poll the zone file and swap in the agent's profile, with `overrides`
+/// applied, as the default zone whenever it changes. Edits that fail to
+/// parse are logged and ignored, keeping the previous zone in effect.
+pub async fn watch(
+    path: PathBuf,
+    tracker: Arc<Mutex<ContextTracker>>,
+    zones: Arc<Mutex<ZoneRegistry>>,
+    overrides: ProfileOverrides,
+) {
+    let mut last = modified(&path);
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = modified(&path);
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        let key = {
+            let t = tracker.lock().await;
+            SessionKey::new(t.agent_id(), t.session_id())
+        };
+        match ZoneFile::load(&path).and_then(|file| file.zone_for_agent(&key.agent_id, &overrides))
+        {
+            Ok(zone) => {
+                info!(
+                    path = %path.display(),
+                    allowed = ?zone.as_ref().map(|z| &z.allowed),
+                    "zone file reloaded"
+                );
+                zones.lock().await.set_default_zone(zone, &key);
+            }
+            Err(e) => warn!(
+                path = %path.display(),
+                error = format!("{e:#}"),
+                "zone file reload failed; keeping previous zone"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+default = "ui"
+shared = ["package.json", "docs/**"]
+
+[profiles.ui]
+allowed = ["src/ui/**", "!src/ui/internal/**"]
+denied = ["**/.env"]
+
+[profiles.core]
+allowed = ["core/**"]
+mode = "warn"
+
+[agents]
+opencode = "core"
+"opencode-special" = "ui"
+"#;
+
+    #[test]
+    fn agents_are_matched_by_id_or_prefix() {
+        let file = ZoneFile::parse(FILE).unwrap();
+        assert_eq!(file.profile_name_for("opencode"), Some("core"));
+        assert_eq!(file.profile_name_for("opencode-a1b2c3"), Some("core"));
+        assert_eq!(file.profile_name_for("opencode-special-x"), Some("ui"));
+        assert_eq!(file.profile_name_for("opencoder"), Some("ui"));
+        assert_eq!(file.profile_name_for("claude-code"), Some("ui"));
+
+        let zone = file
+            .zone_for_agent("opencode-a1b2c3", &ProfileOverrides::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(zone.mode, ZoneMode::Warn);
+        assert!(zone.is_allowed("core/lib.rs"));
+        assert!(zone.is_allowed("docs/guide.md"));
+        assert!(!zone.is_allowed("src/ui/app.tsx"));
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_the_profile() {
+        let file = ZoneFile::parse(FILE).unwrap();
+        let overrides = ProfileOverrides {
+            mode: Some(ZoneMode::Enforce),
+            tool_calls: Some(ToolCallPolicy::Report),
+            case_insensitive: true,
+            redact_search_results: true,
+        };
+        let zone = file
+            .zone_for_agent("opencode", &overrides)
+            .unwrap()
+            .unwrap();
+        assert_eq!(zone.mode, ZoneMode::Enforce);
+        assert_eq!(zone.tool_calls, ToolCallPolicy::Report);
+        assert!(zone.redact_search_results);
+        assert!(zone.is_allowed("CORE/lib.rs"));
+
+        // Unset overrides keep the profile's settings
+        let zone = file
+            .zone_for_agent("opencode", &ProfileOverrides::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(zone.mode, ZoneMode::Warn);
+        assert!(!zone.is_allowed("CORE/lib.rs"));
+    }
+
+    #[test]
+    fn undefined_profiles_are_rejected() {
+        let err = ZoneFile::parse("default = \"missing\"").unwrap_err();
+        assert!(err.to_string().contains("missing"));
+
+        let err = ZoneFile::parse("[agents]\nopencode = \"missing\"").unwrap_err();
+        assert!(err.to_string().contains("opencode"));
+
+        let err = ZoneFile::parse("[profiles.ui]\nallowed = [\"{a,b\"]").unwrap_err();
+        assert!(format!("{err:#}").contains("ui"));
+
+        let file = ZoneFile::parse("").unwrap();
+        assert!(file
+            .zone_for_agent("opencode", &ProfileOverrides::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn unknown_profile_keys_are_rejected() {
+        let err = ZoneFile::parse("[profiles.ui]\nallowed = [\"src/**\"]\ndenyed = [\"**/.env\"]")
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("denyed"));
+    }
+
+    #[test]
+    fn check_explains_each_profile() {
+        let file = ZoneFile::parse(FILE).unwrap();
+        let paths = PathResolver::new(Some(PathBuf::from("/ws")));
+
+        let checks = file.check("/ws/docs/a.md", &paths, &[]).unwrap();
+        let lines: Vec<String> = checks.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "core: allowed — matches shared pattern \"docs/**\" (warn mode: not enforced)",
+                "ui: allowed — matches shared pattern \"docs/**\"",
+            ]
+        );
+
+        let ui = vec!["ui".to_string()];
+        let check = |path: &str| file.check(path, &paths, &ui).unwrap()[0].to_string();
+        assert_eq!(
+            check("src/ui/app.tsx"),
+            "ui: allowed — matches allowed pattern \"src/ui/**\""
+        );
+        assert_eq!(
+            check("src/ui/internal/x.ts"),
+            "ui: denied — excluded by \"!src/ui/internal/**\""
+        );
+        assert_eq!(
+            check("src/ui/.env"),
+            "ui: denied — matches denied pattern \"**/.env\""
+        );
+        assert_eq!(
+            check("core/lib.rs"),
+            "ui: denied — no allowed pattern matches"
+        );
+        assert_eq!(
+            check("../etc/passwd"),
+            "ui: denied — outside the workspace root"
+        );
+        assert!(file.check("a", &paths, &["nope".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! Per-session zone configuration.
+//!
+//! The default zone comes from the `--zone`/`--deny` CLI flags. Individual
+//! sessions can be given their own zone at runtime through the `set_zone` /
+//! `clear_zone` RPCs, so changing an agent's boundaries no longer requires
+//! restarting it. Changes are queued as `ZoneChanged` messages and drained
+//! by the tick loop for broadcast, the same way usage updates are.
+//!
+//! The registry also keeps a bounded audit log of each session's zone
+//! violations, both enforced and those let through in warn mode. It holds
+//! the `PathResolver` that turns agent-reported paths into
+//! workspace-relative ones before they are matched.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::clock::{self, SharedClock};
+use crate::paths::PathResolver;
+use crate::types::{BlockedAccess, CompiledZone, SessionKey, ZoneChanged, ZoneMode};
+
+/// Maximum number of violations kept in each session's audit log.
+const AUDIT_LOG_CAPACITY: usize = 1000;
+
+/// Maximum number of sessions with an audit log. Past it, the log whose
+/// latest violation is oldest is dropped.
+const AUDIT_LOG_SESSIONS: usize = 64;
+
+/// Where the zone in effect for a session comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoneSource {
+    /// Set for this session via `set_zone`
+    Session,
+    /// Fallback zone from the CLI flags
+    Default,
+}
+
+impl ZoneSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ZoneSource::Session => "session",
+            ZoneSource::Default => "default",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ZoneRegistry {
+    // Zones are shared so the proxy can hold on to one without cloning it
+    // for every message it checks.
+    default_zone: Option<Arc<CompiledZone>>,
+    sessions: HashMap<SessionKey, Arc<CompiledZone>>,
+    /// Modes set via `set_mode` on sessions that had only the default zone.
+    /// Their entries in `sessions` are copies of the default, re-derived
+    /// whenever it changes.
+    default_modes: HashMap<SessionKey, ZoneMode>,
+    /// Change notifications queued by set_zone / clear_zone, drained by
+    /// take_pending_changes() in the tick loop.
+    pending_changes: Vec<ZoneChanged>,
+    /// Most recent violations per session, oldest first. Each session has
+    /// its own ring so a noisy one can't push out another's entries.
+    audit: HashMap<SessionKey, VecDeque<BlockedAccess>>,
+    /// Workspace root (and symlink policy) paths are resolved against.
+    paths: Arc<PathResolver>,
+    /// Timestamps `ZoneChanged` and `BlockedAccess` messages.
+    clock: SharedClock,
+}
+
+impl Default for ZoneRegistry {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl ZoneRegistry {
+    pub fn new(default_zone: Option<CompiledZone>) -> Self {
+        Self {
+            default_zone: default_zone.map(Arc::new),
+            sessions: HashMap::new(),
+            default_modes: HashMap::new(),
+            pending_changes: Vec::new(),
+            audit: HashMap::new(),
+            paths: Arc::default(),
+            clock: clock::system(),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    pub fn set_path_resolver(&mut self, paths: PathResolver) {
+        self.paths = Arc::new(paths);
+    }
+
+    pub fn path_resolver(&self) -> &PathResolver {
+        &self.paths
+    }
+
+    /// Shared handle to the path resolver, for use without the registry lock.
+    pub fn shared_path_resolver(&self) -> Arc<PathResolver> {
+        Arc::clone(&self.paths)
+    }
+
+    pub fn default_zone(&self) -> Option<&CompiledZone> {
+        self.default_zone.as_deref()
+    }
+
+    /// Zone in effect for a session: its own zone if one was set, otherwise
+    /// the default. `None` means the session is unrestricted.
+    pub fn zone_for(&self, key: &SessionKey) -> Option<&CompiledZone> {
+        self.zone_with_source(key).map(|(zone, _)| zone)
+    }
+
+    pub fn zone_with_source(&self, key: &SessionKey) -> Option<(&CompiledZone, ZoneSource)> {
+        if let Some(zone) = self.sessions.get(key) {
+            return Some((zone, ZoneSource::Session));
+        }
+        self.default_zone
+            .as_deref()
+            .map(|zone| (zone, ZoneSource::Default))
+    }
+
+    /// Shared handle to the zone in effect for a session, for use without
+    /// the registry lock.
+    pub fn shared_zone_for(&self, key: &SessionKey) -> Option<Arc<CompiledZone>> {
+        self.sessions
+            .get(key)
+            .or(self.default_zone.as_ref())
+            .map(Arc::clone)
+    }
+
+    /// The session's zone, with the path resolver to check paths against
+    /// it, if that zone is enforced.
+    pub fn enforced_zone(
+        &self,
+        key: &SessionKey,
+    ) -> Option<(Arc<CompiledZone>, Arc<PathResolver>)> {
+        self.shared_zone_for(key)
+            .filter(|zone| zone.mode == ZoneMode::Enforce)
+            .map(|zone| (zone, self.shared_path_resolver()))
+    }
+
+    /// Replace the default zone (e.g. after the zone file was edited).
+    /// `key` is the session the change is announced for, along with any
+    /// session running the default zone under a mode of its own.
+    pub fn set_default_zone(&mut self, zone: Option<CompiledZone>, key: &SessionKey) {
+        self.default_zone = zone.map(Arc::new);
+        let derived: Vec<(SessionKey, ZoneMode)> = self
+            .default_modes
+            .iter()
+            .map(|(key, mode)| (key.clone(), *mode))
+            .collect();
+        for (derived_key, mode) in &derived {
+            match &self.default_zone {
+                Some(zone) => {
+                    let zone = Arc::new(zone.with_mode(*mode));
+                    self.sessions.insert(derived_key.clone(), zone);
+                }
+                None => {
+                    self.sessions.remove(derived_key);
+                }
+            }
+        }
+        self.queue_change(key);
+        for (derived_key, _) in derived {
+            if &derived_key != key {
+                self.queue_change(&derived_key);
+            }
+        }
+    }
+
+    /// Replace the zone for a session.
+    pub fn set_zone(&mut self, key: SessionKey, zone: CompiledZone) {
+        self.default_modes.remove(&key);
+        self.sessions.insert(key.clone(), Arc::new(zone));
+        self.queue_change(&key);
+    }
+
+    /// Drop the session's own zone so it falls back to the default.
+    /// Returns false if the session had no zone of its own.
+    pub fn clear_zone(&mut self, key: &SessionKey) -> bool {
+        self.default_modes.remove(key);
+        let removed = self.sessions.remove(key).is_some();
+        if removed {
+            self.queue_change(key);
+        }
+        removed
+    }
+
+    /// Change the mode of the zone in effect for a session. A session that
+    /// only has the default zone gets its own copy with the new mode, which
+    /// follows later changes to the default. Returns false if the session
+    /// has no zone at all.
+    pub fn set_mode(&mut self, key: &SessionKey, mode: ZoneMode) -> bool {
+        let on_default = !self.sessions.contains_key(key) || self.default_modes.contains_key(key);
+        let Some(zone) = self.zone_for(key) else {
+            return false;
+        };
+        let zone = Arc::new(zone.with_mode(mode));
+        if on_default {
+            self.default_modes.insert(key.clone(), mode);
+        }
+        self.sessions.insert(key.clone(), zone);
+        self.queue_change(key);
+        true
+    }
+
+    /// Append a violation to its session's audit log, dropping that
+    /// session's oldest entry once the log is full.
+    pub fn record_violation(&mut self, entry: BlockedAccess) {
+        let key = SessionKey::new(&entry.agent_id, &entry.session_id);
+        if !self.audit.contains_key(&key) && self.audit.len() >= AUDIT_LOG_SESSIONS {
+            let stalest = self
+                .audit
+                .iter()
+                .min_by_key(|(_, log)| log.back().map_or(0, |e| e.timestamp_ms))
+                .map(|(key, _)| key.clone());
+            if let Some(stalest) = stalest {
+                self.audit.remove(&stalest);
+            }
+        }
+        let log = self.audit.entry(key).or_default();
+        if log.len() >= AUDIT_LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(entry);
+    }
+
+    /// Audit log entries for a session, oldest first.
+    pub fn audit_log(&self, key: &SessionKey) -> Vec<BlockedAccess> {
+        self.audit
+            .get(key)
+            .map(|log| log.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop everything kept for a closed session: its own zone, mode
+    /// override and audit log.
+    pub fn forget_session(&mut self, key: &SessionKey) {
+        self.sessions.remove(key);
+        self.default_modes.remove(key);
+        self.audit.remove(key);
+    }
+
+    /// Drain queued `ZoneChanged` messages.
+    pub fn take_pending_changes(&mut self) -> Vec<ZoneChanged> {
+        std::mem::take(&mut self.pending_changes)
+    }
+
+    fn queue_change(&mut self, key: &SessionKey) {
+        let zone = self.zone_for(key).map(|zone| zone.config().clone());
+        self.pending_changes.push(ZoneChanged::new(
+            &key.agent_id,
+            &key.session_id,
+            zone,
+            self.clock.now_ms(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ZoneConfig;
+
+    fn zone(allowed: &str) -> CompiledZone {
+        ZoneConfig::new(vec![allowed.to_string()])
+            .compile()
+            .unwrap()
+    }
+
+    #[test]
+    fn session_zone_overrides_default() {
+        let mut zones = ZoneRegistry::new(Some(zone("src/**")));
+        let key = SessionKey::new("agent-a", "sess-1");
+        let other = SessionKey::new("agent-a", "sess-2");
+
+        assert_eq!(zones.zone_for(&key).unwrap().allowed, vec!["src/**"]);
+
+        zones.set_zone(key.clone(), zone("docs/**"));
+        let (active, source) = zones.zone_with_source(&key).unwrap();
+        assert_eq!(active.allowed, vec!["docs/**"]);
+        assert_eq!(source, ZoneSource::Session);
+
+        // Other sessions keep the default
+        let (active, source) = zones.zone_with_source(&other).unwrap();
+        assert_eq!(active.allowed, vec!["src/**"]);
+        assert_eq!(source, ZoneSource::Default);
+    }
+
+    #[test]
+    fn clear_zone_falls_back_to_default() {
+        let mut zones = ZoneRegistry::new(None);
+        let key = SessionKey::new("agent-a", "sess-1");
+
+        zones.set_zone(key.clone(), zone("docs/**"));
+        assert!(zones.clear_zone(&key));
+        assert!(zones.zone_for(&key).is_none());
+        assert!(!zones.clear_zone(&key));
+    }
+
+    #[test]
+    fn set_mode_copies_default_zone() {
+        let mut zones = ZoneRegistry::new(Some(zone("src/**")));
+        let key = SessionKey::new("agent-a", "sess-1");
+        let other = SessionKey::new("agent-a", "sess-2");
+
+        assert!(zones.set_mode(&key, ZoneMode::Warn));
+        let (active, source) = zones.zone_with_source(&key).unwrap();
+        assert_eq!(active.mode, ZoneMode::Warn);
+        assert_eq!(active.allowed, vec!["src/**"]);
+        assert_eq!(source, ZoneSource::Session);
+        assert_eq!(zones.zone_for(&other).unwrap().mode, ZoneMode::Enforce);
+
+        let mut empty = ZoneRegistry::new(None);
+        assert!(!empty.set_mode(&key, ZoneMode::Off));
+    }
+
+    #[test]
+    fn set_mode_copies_follow_the_default() {
+        let mut zones = ZoneRegistry::new(Some(zone("src/**")));
+        let key = SessionKey::new("agent-a", "sess-1");
+        let own = SessionKey::new("agent-a", "sess-2");
+        zones.set_mode(&key, ZoneMode::Warn);
+        zones.set_zone(own.clone(), zone("docs/**"));
+        zones.set_mode(&own, ZoneMode::Warn);
+        zones.take_pending_changes();
+
+        // A reload swaps in the new patterns but keeps the session's mode
+        zones.set_default_zone(Some(zone("lib/**")), &own);
+        let active = zones.zone_for(&key).unwrap();
+        assert_eq!(active.allowed, vec!["lib/**"]);
+        assert_eq!(active.mode, ZoneMode::Warn);
+        // Sessions with a zone of their own are left alone
+        assert_eq!(zones.zone_for(&own).unwrap().allowed, vec!["docs/**"]);
+        let changes = zones.take_pending_changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].session_id, "sess-1");
+
+        zones.set_default_zone(None, &key);
+        assert!(zones.zone_for(&key).is_none());
+        zones.set_default_zone(Some(zone("app/**")), &key);
+        assert_eq!(zones.zone_for(&key).unwrap().mode, ZoneMode::Warn);
+
+        // Clearing the zone drops the mode override
+        assert!(zones.clear_zone(&key));
+        assert_eq!(zones.zone_for(&key).unwrap().mode, ZoneMode::Enforce);
+    }
+
+    #[test]
+    fn audit_log_is_per_session_and_bounded() {
+        let mut zones = ZoneRegistry::new(None);
+        let key = SessionKey::new("agent-a", "sess-1");
+
+        zones.record_violation(BlockedAccess::new(
+            "agent-a", "sess-1", "/a", "read", false, 1,
+        ));
+        zones.record_violation(BlockedAccess::new(
+            "agent-a", "sess-2", "/b", "read", true, 2,
+        ));
+        let entries = zones.audit_log(&key);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "/a");
+        assert!(!entries[0].enforced);
+
+        for i in 0..AUDIT_LOG_CAPACITY {
+            zones.record_violation(BlockedAccess::new(
+                "agent-a",
+                "sess-1",
+                &format!("/f{i}"),
+                "write",
+                true,
+                i as u64,
+            ));
+        }
+        let entries = zones.audit_log(&key);
+        assert_eq!(entries.len(), AUDIT_LOG_CAPACITY);
+        assert_eq!(entries[0].path, "/f0");
+
+        // A full log for one session leaves the others alone
+        let other = SessionKey::new("agent-a", "sess-2");
+        assert_eq!(zones.audit_log(&other).len(), 1);
+        assert_eq!(zones.audit_log(&other)[0].path, "/b");
+    }
+
+    #[test]
+    fn audit_logs_are_dropped_with_their_session() {
+        let mut zones = ZoneRegistry::new(Some(zone("src/**")));
+        let key = SessionKey::new("agent-a", "sess-1");
+        zones.set_zone(key.clone(), zone("docs/**"));
+        zones.record_violation(BlockedAccess::new(
+            "agent-a", "sess-1", "/a", "read", true, 1,
+        ));
+
+        zones.forget_session(&key);
+        assert!(zones.audit_log(&key).is_empty());
+        assert_eq!(zones.zone_for(&key).unwrap().allowed, vec!["src/**"]);
+
+        // Past the session cap, the log that went quiet first is dropped
+        for i in 0..=AUDIT_LOG_SESSIONS {
+            let session = format!("sess-{i}");
+            let ts = if i == 3 { 0 } else { i as u64 + 1 };
+            zones.record_violation(BlockedAccess::new(
+                "agent-a", &session, "/a", "read", true, ts,
+            ));
+        }
+        assert_eq!(zones.audit.len(), AUDIT_LOG_SESSIONS);
+        assert!(zones
+            .audit_log(&SessionKey::new("agent-a", "sess-3"))
+            .is_empty());
+        assert_eq!(
+            zones.audit_log(&SessionKey::new("agent-a", "sess-0")).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn changes_are_queued() {
+        let mut zones = ZoneRegistry::new(None);
+        let key = SessionKey::new("agent-a", "sess-1");
+
+        zones.set_zone(key.clone(), zone("docs/**"));
+        zones.clear_zone(&key);
+
+        let changes = zones.take_pending_changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].msg_type, "zone_changed");
+        assert_eq!(changes[0].session_id, "sess-1");
+        assert_eq!(changes[0].zone.as_ref().unwrap().allowed, vec!["docs/**"]);
+        assert!(changes[1].zone.is_none());
+        assert!(zones.take_pending_changes().is_empty());
+    }
+}
@@ -12,6 +12,7 @@ use eisen_core::session_registry::SessionRegistry;
 use eisen_core::tcp::{self, WireLine};
 use eisen_core::tracker::ContextTracker;
 use eisen_core::types::{Action, TrackerConfig};
+use eisen_core::zone_registry::ZoneRegistry;
 use tempfile::TempDir;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
@@ -27,6 +28,7 @@ struct TestServer {
     delta_tx: broadcast::Sender<WireLine>,
     _registry: Arc<Mutex<SessionRegistry>>,
     _orchestrator: Arc<Mutex<OrchestratorAggregator>>,
+    _zones: Arc<Mutex<ZoneRegistry>>,
     _registry_dir: TempDir,
 }
 
@@ -38,10 +40,12 @@ impl TestServer {
     async fn start_with_config(config: TrackerConfig) -> Self {
         let tracker = Arc::new(Mutex::new(ContextTracker::new(config)));
         let registry_dir = tempfile::tempdir().unwrap();
-        let registry = Arc::new(Mutex::new(SessionRegistry::load_from_path(
-            registry_dir.path().join("core_sessions.json"),
-        )));
+        let registry = Arc::new(Mutex::new(
+            SessionRegistry::load_from_path(registry_dir.path().join("core_sessions.json"))
+                .unwrap(),
+        ));
         let orchestrator = Arc::new(Mutex::new(OrchestratorAggregator::new()));
+        let zones = Arc::new(Mutex::new(ZoneRegistry::new(None)));
         let (delta_tx, _) = broadcast::channel::<WireLine>(64);
 
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -51,6 +55,7 @@ impl TestServer {
         let t = tracker.clone();
         let reg = registry.clone();
         let orch = orchestrator.clone();
+        let z = zones.clone();
         let tx = delta_tx.clone();
         tokio::spawn(async move {
             loop {
@@ -58,20 +63,25 @@ impl TestServer {
                 let t2 = t.clone();
                 let reg2 = reg.clone();
                 let orch2 = orch.clone();
+                let z2 = z.clone();
                 let rx = tx.subscribe();
                 tokio::spawn(async move {
-                    let _ = tcp::handle_client(stream, t2, rx, reg2, orch2).await;
+                    let _ = tcp::handle_client(stream, t2, rx, reg2, orch2, z2).await;
                 });
             }
         });
 
         // Tick loop (mirrors main.rs but faster for tests)
         let t = tracker.clone();
+        let z = zones.clone();
         let tx = delta_tx.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_millis(50));
             loop {
                 interval.tick().await;
+                for change in z.lock().await.take_pending_changes() {
+                    tcp::broadcast_line(&tx, &change);
+                }
                 let mut guard = t.lock().await;
                 if let Some(delta) = guard.tick() {
                     tcp::broadcast_line(&tx, &delta);
@@ -88,6 +98,7 @@ impl TestServer {
             delta_tx,
             _registry: registry,
             _orchestrator: orchestrator,
+            _zones: zones,
             _registry_dir: registry_dir,
         }
     }
@@ -176,6 +187,12 @@ async fn snapshot_wire_format() {
         "last_action must be string"
     );
     assert!(node["turn_accessed"].is_u64(), "turn_accessed must be u64");
+    assert!(
+        node["estimated_tokens"].is_u64(),
+        "estimated_tokens must be u64"
+    );
+    // No usage reported yet, so no budget
+    assert!(msg.get("budget").is_none(), "budget omitted before usage");
 
     // Verify both nodes present
     assert!(msg["nodes"]["/home/user/src/db.ts"].is_object());
@@ -215,6 +232,10 @@ async fn delta_wire_format() {
     assert!(update["in_context"].is_boolean());
     assert!(update["last_action"].is_string());
     assert!(update["turn_accessed"].is_u64());
+    assert!(update["estimated_tokens"].is_u64());
+    assert!(update["line_ranges"].is_array());
+    assert_eq!(update["source"], "api");
+    assert!(update["confidence"].is_f64());
 }
 
 /// Validate all Action variants serialize to the correct snake_case strings.
@@ -229,6 +250,10 @@ async fn action_serialization() {
         ("/d", Action::Write, "write"),
         ("/e", Action::Search, "search"),
         ("/f", Action::Blocked, "blocked"),
+        ("/g", Action::Refreshed, "refreshed"),
+        ("/h", Action::Warned, "warned"),
+        ("/i", Action::Move, "move"),
+        ("/j", Action::Delete, "delete"),
     ];
 
     {
@@ -236,20 +261,45 @@ async fn action_serialization() {
         for (path, action, _) in &actions {
             t.file_access(path, *action);
         }
+        // Drain the changes so no delta arrives ahead of the RPC result
+        t.tick();
     }
 
     let mut client = srv.connect().await;
     let msg = client.read_msg().await;
     assert_eq!(msg["type"], "snapshot");
 
-    for (path, _, expected_str) in &actions {
+    for (path, action, expected_str) in &actions {
         let node = &msg["nodes"][path];
+        if *action == Action::Delete {
+            // Deleted files leave the graph; the action shows up in the timeline
+            assert!(node.is_null());
+            continue;
+        }
         assert_eq!(
             node["last_action"].as_str().unwrap(),
             *expected_str,
             "Action for {path} should serialize as {expected_str}"
         );
     }
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "a1",
+            "method": "get_timeline",
+            "params": {}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    let events = resp["result"]["events"].as_array().unwrap();
+    for (path, _, expected_str) in &actions {
+        let event = events
+            .iter()
+            .find(|e| e["path"] == *path)
+            .unwrap_or_else(|| panic!("no timeline event for {path}"));
+        assert_eq!(event["action"], *expected_str);
+    }
 }
 
 /// Validate request_snapshot round-trip.
@@ -316,7 +366,8 @@ async fn removed_files_in_delta() {
     let config = TrackerConfig {
         context_turns: 0, // exit context immediately on end_turn
         compaction_threshold: 0.5,
-        decay_rate: 0.001, // heat drops below 0.01 in one tick
+        heat_half_life_ms: 1, // heat drops below 0.01 within one tick
+        ..TrackerConfig::default()
     };
     let srv = TestServer::start_with_config(config).await;
 
@@ -341,16 +392,15 @@ async fn removed_files_in_delta() {
             }
         }
         // snapshot won't have it if it already decayed
-        if msg["type"] == "snapshot" {
-            if !msg["nodes"]
+        if msg["type"] == "snapshot"
+            && !msg["nodes"]
                 .as_object()
                 .unwrap()
                 .contains_key("/ephemeral.rs")
-            {
-                // File was already pruned before we connected — also acceptable
-                found_removed = true;
-                break;
-            }
+        {
+            // File was already pruned before we connected — also acceptable
+            found_removed = true;
+            break;
         }
     }
     assert!(
@@ -433,6 +483,7 @@ async fn usage_message_with_cost() {
         Some(eisen_core::types::Cost {
             amount: 0.04,
             currency: "USD".to_string(),
+            estimated: false,
         }),
     );
     tcp::broadcast_line(&srv.delta_tx, &usage);
@@ -478,8 +529,14 @@ async fn blocked_access_wire_format() {
     let _snap = client.read_msg().await;
 
     // Broadcast a BlockedAccess message directly
-    let blocked =
-        eisen_core::types::BlockedAccess::new("agent-0", "sess_1", "/core/auth.rs", "read");
+    let blocked = eisen_core::types::BlockedAccess::new(
+        "agent-0",
+        "sess_1",
+        "/core/auth.rs",
+        "read",
+        true,
+        1700000000000,
+    );
     tcp::broadcast_line(&srv.delta_tx, &blocked);
 
     let msg = client.read_msg().await;
@@ -488,6 +545,7 @@ async fn blocked_access_wire_format() {
     assert_eq!(msg["session_id"], "sess_1");
     assert_eq!(msg["path"], "/core/auth.rs");
     assert_eq!(msg["action"], "read");
+    assert_eq!(msg["enforced"], true);
     assert!(msg["timestamp_ms"].is_u64(), "timestamp_ms must be u64");
     assert!(
         msg["timestamp_ms"].as_u64().unwrap() > 0,
@@ -498,8 +556,14 @@ async fn blocked_access_wire_format() {
 /// Validate BlockedAccess serialization round-trip.
 #[tokio::test]
 async fn blocked_access_round_trip() {
-    let blocked =
-        eisen_core::types::BlockedAccess::new("test-agent", "sess_2", "/secret/.env", "write");
+    let blocked = eisen_core::types::BlockedAccess::new(
+        "test-agent",
+        "sess_2",
+        "/secret/.env",
+        "write",
+        false,
+        1700000000000,
+    );
     let json = serde_json::to_string(&blocked).unwrap();
     let parsed: eisen_core::types::BlockedAccess = serde_json::from_str(&json).unwrap();
     assert_eq!(parsed.msg_type, "blocked");
@@ -507,9 +571,138 @@ async fn blocked_access_round_trip() {
     assert_eq!(parsed.session_id, "sess_2");
     assert_eq!(parsed.path, "/secret/.env");
     assert_eq!(parsed.action, "write");
+    assert!(!parsed.enforced);
     assert_eq!(parsed.timestamp_ms, blocked.timestamp_ms);
 }
 
+/// set_zone over RPC is acknowledged and broadcast as a zone_changed message.
+#[tokio::test]
+async fn zone_changed_via_rpc() {
+    let srv = TestServer::start().await;
+    let mut client = srv.connect().await;
+    let _snap = client.read_msg().await;
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "z1",
+            "method": "set_zone",
+            "params": {
+                "agent_id": "agent-0",
+                "session_id": "sess_1",
+                "allowed": ["src/ui/**"],
+                "denied": ["**/.env"]
+            }
+        }))
+        .await;
+
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    assert_eq!(resp["id"], "z1");
+    assert_eq!(resp["result"]["zone"]["allowed"][0], "src/ui/**");
+
+    let msg = client.read_msg().await;
+    assert_eq!(msg["type"], "zone_changed");
+    assert_eq!(msg["agent_id"], "agent-0");
+    assert_eq!(msg["session_id"], "sess_1");
+    assert_eq!(msg["zone"]["allowed"][0], "src/ui/**");
+    assert_eq!(msg["zone"]["denied"][0], "**/.env");
+    assert!(msg["timestamp_ms"].is_u64(), "timestamp_ms must be u64");
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "z2",
+            "method": "get_zone",
+            "params": {"agent_id": "agent-0", "session_id": "sess_1"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["result"]["source"], "session");
+    assert_eq!(resp["result"]["zone"]["mode"], "enforce");
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "z2b",
+            "method": "set_zone_mode",
+            "params": {"agent_id": "agent-0", "session_id": "sess_1", "mode": "warn"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["result"]["mode"], "warn");
+    let msg = client.read_msg().await;
+    assert_eq!(msg["type"], "zone_changed");
+    assert_eq!(msg["zone"]["mode"], "warn");
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "z3",
+            "method": "clear_zone",
+            "params": {"agent_id": "agent-0", "session_id": "sess_1"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["result"]["cleared"], true);
+
+    let msg = client.read_msg().await;
+    assert_eq!(msg["type"], "zone_changed");
+    assert!(
+        msg["zone"].is_null(),
+        "cleared zone with no default is null"
+    );
+}
+
+/// set_hot_zone rejects paths outside the session's enforced zone,
+/// including external ones.
+#[tokio::test]
+async fn hot_zone_respects_session_zone() {
+    let srv = TestServer::start().await;
+    let mut client = srv.connect().await;
+    let _snap = client.read_msg().await;
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "z1",
+            "method": "set_zone",
+            "params": {"agent_id": "", "session_id": "sess_1", "allowed": ["src/**"]}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    let msg = client.read_msg().await;
+    assert_eq!(msg["type"], "zone_changed");
+
+    for (id, path) in [("h1", "/etc/passwd"), ("h2", "external:/etc/passwd")] {
+        client
+            .send(&serde_json::json!({
+                "type": "rpc",
+                "id": id,
+                "method": "set_hot_zone",
+                "params": {"session_id": "sess_1", "paths": ["src/a.rs", path]}
+            }))
+            .await;
+        let resp = client.read_msg().await;
+        assert_eq!(resp["type"], "rpc_error", "{path} is outside the zone");
+        assert_eq!(resp["error"]["code"], 400);
+    }
+    assert!(srv.tracker.lock().await.hot_zone("sess_1").is_empty());
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "h3",
+            "method": "set_hot_zone",
+            "params": {"session_id": "sess_1", "paths": ["src/a.rs"]}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    assert_eq!(resp["result"]["paths"][0], "src/a.rs");
+}
+
 /// Validate ndJSON framing: each message is exactly one line.
 #[tokio::test]
 async fn ndjson_framing() {
@@ -531,3 +724,122 @@ async fn ndjson_framing() {
     // Must not contain embedded newlines (single line)
     assert_eq!(line.matches('\n').count(), 1);
 }
+
+/// get_timeline and get_file_history return the recorded access events.
+#[tokio::test]
+async fn timeline_rpcs() {
+    let srv = TestServer::start().await;
+    {
+        let mut t = srv.tracker.lock().await;
+        t.file_access("/home/user/src/auth.ts", Action::Read);
+        t.end_turn();
+        t.file_access("/home/user/src/db.ts", Action::Search);
+        t.file_access("/home/user/src/auth.ts", Action::Write);
+        // Drain the changes so no delta arrives ahead of the RPC results
+        t.tick();
+    }
+    let mut client = srv.connect().await;
+    let _snap = client.read_msg().await;
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "t1",
+            "method": "get_timeline",
+            "params": {"turn": 1}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    let events = resp["result"]["events"].as_array().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["turn"], 1);
+    assert_eq!(events[0]["path"], "/home/user/src/db.ts");
+    assert_eq!(events[0]["action"], "search");
+    assert_eq!(events[0]["source"], "api");
+    assert!(events[0]["timestamp_ms"].is_u64());
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "t2",
+            "method": "get_file_history",
+            "params": {"path": "/home/user/src/auth.ts"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    let events = resp["result"]["events"].as_array().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["action"], "read");
+    assert_eq!(events[0]["turn"], 0);
+    assert_eq!(events[1]["action"], "write");
+}
+
+/// get_session_state reports the cumulative cost the agent reported.
+#[tokio::test]
+async fn session_state_includes_spend() {
+    let srv = TestServer::start().await;
+    {
+        let mut t = srv.tracker.lock().await;
+        let cost = |amount| eisen_core::types::Cost {
+            amount,
+            currency: "USD".to_string(),
+            estimated: false,
+        };
+        t.usage_update_for_session("sess-1", 1_000, 200_000, Some(cost(0.6)));
+        // Counter restarted with the agent
+        t.usage_update_for_session("sess-1", 2_000, 200_000, Some(cost(0.1)));
+        t.take_pending_usage();
+        t.tick();
+    }
+    let mut client = srv.connect().await;
+    let _snap = client.read_msg().await;
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "s1",
+            "method": "create_session",
+            "params": {"agent_id": "", "session_id": "sess-1", "mode": "single_agent"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "s2",
+            "method": "get_session_state",
+            "params": {"agent_id": "", "session_id": "sess-1"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    let spend = &resp["result"]["spend"];
+    assert!((spend["amount"].as_f64().unwrap() - 0.7).abs() < 1e-9);
+    assert_eq!(spend["currency"], "USD");
+
+    // Another agent's session with the same ID doesn't get this spend
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "s3",
+            "method": "create_session",
+            "params": {"agent_id": "other", "session_id": "sess-1", "mode": "single_agent"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    client
+        .send(&serde_json::json!({
+            "type": "rpc",
+            "id": "s4",
+            "method": "get_session_state",
+            "params": {"agent_id": "other", "session_id": "sess-1"}
+        }))
+        .await;
+    let resp = client.read_msg().await;
+    assert_eq!(resp["type"], "rpc_result");
+    assert!(resp["result"]["spend"].is_null());
+}
@@ -1,6 +1,9 @@
 //! Tests for Phase 3 zone enforcement: ZoneConfig matching, denied overrides,
 //! and the glob matching implementation.
 
+use std::path::PathBuf;
+
+use eisen_core::paths::{NamedRoot, PathResolver};
 use eisen_core::types::ZoneConfig;
 
 // -----------------------------------------------------------------------
@@ -82,12 +85,27 @@ fn denied_specific_file() {
 
 #[test]
 fn exact_file_match() {
-    let zone = ZoneConfig::new(vec!["package.json".to_string()]);
+    let zone = ZoneConfig::new(vec!["/package.json".to_string()]);
     assert!(zone.is_allowed("package.json"));
     assert!(!zone.is_allowed("src/package.json"));
     assert!(!zone.is_allowed("other.json"));
 }
 
+#[test]
+fn slashless_pattern_matches_at_any_depth() {
+    let zone = ZoneConfig::new(vec!["package.json".to_string()]);
+    assert!(zone.is_allowed("package.json"));
+    assert!(zone.is_allowed("src/package.json"));
+    assert!(!zone.is_allowed("other.json"));
+
+    let mut zone = ZoneConfig::new(vec!["**".to_string()]);
+    zone.denied = vec![".env".to_string(), "*.secret".to_string()];
+    assert!(!zone.is_allowed(".env"));
+    assert!(!zone.is_allowed("config/.env"));
+    assert!(!zone.is_allowed("src/ui/keys.secret"));
+    assert!(zone.is_allowed("src/ui/app.tsx"));
+}
+
 #[test]
 fn exact_file_in_subdirectory_pattern() {
     let zone = ZoneConfig::new(vec!["config/tsconfig.json".to_string()]);
@@ -101,7 +119,7 @@ fn exact_file_in_subdirectory_pattern() {
 
 #[test]
 fn star_pattern_in_filename() {
-    let zone = ZoneConfig::new(vec!["*.config.js".to_string()]);
+    let zone = ZoneConfig::new(vec!["/*.config.js".to_string()]);
     assert!(zone.is_allowed("eslint.config.js"));
     assert!(zone.is_allowed("tailwind.config.js"));
     assert!(!zone.is_allowed("src/eslint.config.js")); // star only matches one segment
@@ -214,3 +232,145 @@ fn case_sensitive_matching() {
     assert!(zone.is_allowed("src/UI/button.tsx"));
     assert!(!zone.is_allowed("src/ui/button.tsx"));
 }
+
+// -----------------------------------------------------------------------
+// Extended glob syntax (braces, classes, negation)
+// -----------------------------------------------------------------------
+
+#[test]
+fn brace_alternatives() {
+    let zone = ZoneConfig::new(vec!["src/{api,core}/**".to_string()]);
+    assert!(zone.is_allowed("src/api/routes.ts"));
+    assert!(zone.is_allowed("src/core/db/pool.rs"));
+    assert!(!zone.is_allowed("src/ui/app.tsx"));
+}
+
+#[test]
+fn character_class() {
+    let zone = ZoneConfig::new(vec!["**/*.[jt]s".to_string()]);
+    assert!(zone.is_allowed("index.js"));
+    assert!(zone.is_allowed("src/app.ts"));
+    assert!(!zone.is_allowed("src/app.rs"));
+}
+
+#[test]
+fn negation_in_allowed() {
+    let zone = ZoneConfig::new(vec!["src/**".to_string(), "!**/generated/**".to_string()]);
+    assert!(zone.is_allowed("src/api/routes.ts"));
+    assert!(!zone.is_allowed("src/generated/schema.ts"));
+    assert!(!zone.is_allowed("src/api/generated/client.ts"));
+}
+
+#[test]
+fn negation_in_denied() {
+    let mut zone = ZoneConfig::new(vec!["**".to_string()]);
+    zone.denied = vec!["secrets/**".to_string(), "!secrets/public/**".to_string()];
+    assert!(!zone.is_allowed("secrets/keys.json"));
+    assert!(zone.is_allowed("secrets/public/cert.pem"));
+}
+
+#[test]
+fn directory_pattern_covers_contents() {
+    let zone = ZoneConfig::new(vec!["/docs".to_string()]);
+    assert!(zone.is_allowed("docs"));
+    assert!(zone.is_allowed("docs/guide/intro.md"));
+    assert!(!zone.is_allowed("src/docs/readme.md"));
+}
+
+#[test]
+fn case_insensitive_matching() {
+    let mut zone = ZoneConfig::new(vec!["src/UI/**".to_string()]);
+    zone.case_insensitive = true;
+    assert!(zone.is_allowed("src/UI/button.tsx"));
+    assert!(zone.is_allowed("src/ui/button.tsx"));
+}
+
+// -----------------------------------------------------------------------
+// Compilation
+// -----------------------------------------------------------------------
+
+#[test]
+fn invalid_pattern_rejected() {
+    let zone = ZoneConfig::new(vec!["src/[z-a]/**".to_string()]);
+    let err = zone.compile().unwrap_err().to_string();
+    assert!(
+        err.contains("src/[z-a]/**"),
+        "error should name the pattern: {err}"
+    );
+    // An invalid config never matches
+    assert!(!zone.is_allowed("src/ui/button.tsx"));
+
+    let mut zone = ZoneConfig::new(vec!["src/**".to_string()]);
+    zone.denied = vec!["{a,b".to_string()];
+    assert!(zone.compile().is_err());
+
+    // Would be a gitignore comment, matching nothing
+    let zone = ZoneConfig::new(vec!["#notes.md".to_string()]);
+    assert!(zone.compile().is_err());
+    let zone = ZoneConfig::new(vec!["\\#notes.md".to_string()]);
+    assert!(zone.is_allowed("#notes.md"));
+}
+
+#[test]
+fn compiled_zone_matches_config() {
+    let mut config = ZoneConfig::new(vec!["src/**".to_string()]);
+    config.denied = vec!["**/.env".to_string()];
+    let zone = config.compile().unwrap();
+    assert!(zone.is_allowed("src/main.rs"));
+    assert!(zone.is_allowed("/src/main.rs"));
+    assert!(!zone.is_allowed("src/.env"));
+    assert!(!zone.is_allowed(""));
+    assert_eq!(zone.config().allowed, vec!["src/**"]);
+}
+
+// -----------------------------------------------------------------------
+// Path resolution
+// -----------------------------------------------------------------------
+
+#[test]
+fn dot_segments_are_normalized() {
+    let zone = ZoneConfig::new(vec!["src/ui/**".to_string()]);
+    assert!(zone.is_allowed("./src/ui/button.tsx"));
+    assert!(zone.is_allowed("src/core/../ui/button.tsx"));
+    assert!(!zone.is_allowed("src/ui/../core/auth.rs"));
+    // Traversal above the root is always denied
+    assert!(!zone.is_allowed("src/ui/../../../etc/passwd"));
+    assert!(!zone.is_allowed("../src/ui/button.tsx"));
+}
+
+#[test]
+fn absolute_paths_resolved_against_workspace_root() {
+    let zone = ZoneConfig::new(vec!["src/ui/**".to_string()])
+        .compile()
+        .unwrap();
+    let paths = PathResolver::new(Some(PathBuf::from("/workspace")));
+    assert!(zone.is_path_allowed("/workspace/src/ui/a.ts", &paths));
+    assert!(zone.is_path_allowed("src/ui/a.ts", &paths));
+    assert!(!zone.is_path_allowed("/workspace/src/core/a.ts", &paths));
+    assert!(!zone.is_path_allowed("/other/src/ui/a.ts", &paths));
+    assert!(!zone.is_path_allowed("/workspace/src/ui/../../../etc/passwd", &paths));
+    assert!(!zone.is_path_allowed("src/ui/../../../etc/passwd", &paths));
+}
+
+#[test]
+fn named_root_paths_match_prefixed_patterns() {
+    let zone = ZoneConfig::new(vec!["src/**".to_string(), "libs:core/**".to_string()])
+        .compile()
+        .unwrap();
+    let paths = PathResolver::new(Some(PathBuf::from("/ws/app"))).with_roots([NamedRoot::new(
+        "libs",
+        PathBuf::from("/ws/libs"),
+    )
+    .unwrap()]);
+    assert!(zone.is_path_allowed("/ws/app/src/main.rs", &paths));
+    assert!(zone.is_path_allowed("/ws/libs/core/lib.rs", &paths));
+    assert!(!zone.is_path_allowed("/ws/libs/util/fmt.rs", &paths));
+    // An unprefixed pattern only covers the primary root
+    assert!(!zone.is_path_allowed("/ws/libs/src/lib.rs", &paths));
+    assert!(!zone.is_path_allowed("/etc/hosts", &paths));
+
+    // A slash-less pattern matches in every root
+    let zone = ZoneConfig::new(vec!["*.md".to_string()]).compile().unwrap();
+    assert!(zone.is_path_allowed("/ws/app/docs/guide.md", &paths));
+    assert!(zone.is_path_allowed("/ws/libs/README.md", &paths));
+}
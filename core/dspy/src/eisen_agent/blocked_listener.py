@@ -27,8 +27,9 @@ class BlockedAccessEvent:
     agent_id: str
     session_id: str
     path: str
-    action: str  # "read" | "write"
+    action: str  # "read" | "write" | "search"
     timestamp_ms: int
+    enforced: bool = True  # False when the access went through (warn mode)
 
 
 class BlockedAccessListener:
@@ -129,6 +130,7 @@ class BlockedAccessListener:
             path=msg.get("path", ""),
             action=msg.get("action", ""),
             timestamp_ms=msg.get("timestamp_ms", 0),
+            enforced=msg.get("enforced", True),
         )
 
         async with self._lock:
@@ -136,9 +138,14 @@ class BlockedAccessListener:
 
         logger.info(
             f"Blocked access detected: agent={event.agent_id} "
-            f"path={event.path} action={event.action}"
+            f"path={event.path} action={event.action} enforced={event.enforced}"
         )
 
+        # The agent already has the file when the zone only warned, so there
+        # is nothing to resolve.
+        if not event.enforced:
+            return
+
         # Resolve through A2A router
         # Extract a symbol hint from the file path
         symbol_hint = self._path_to_symbol_hint(event.path)
@@ -73,6 +73,26 @@ async def test_handle_blocked_triggers_resolution(mock_router):
     assert call_args.kwargs["symbol_name"] == "auth"  # extracted from path
 
 
+async def test_handle_warned_skips_resolution(mock_router):
+    listener = BlockedAccessListener(mock_router)
+
+    msg = {
+        "type": "blocked",
+        "agent_id": "claude-code-0",
+        "session_id": "sess_1",
+        "path": "/core/auth.rs",
+        "action": "read",
+        "enforced": False,
+        "timestamp_ms": 1700000000000,
+    }
+    await listener._handle_blocked(msg)
+
+    # Recorded for the audit trail, but the agent already has the file
+    assert len(listener.blocked_events) == 1
+    assert listener.blocked_events[0].enforced is False
+    mock_router.resolve.assert_not_called()
+
+
 async def test_handle_blocked_stores_resolution(mock_router):
     listener = BlockedAccessListener(mock_router)
 